@@ -0,0 +1,38 @@
+/* List the legal moves of the starting position and search it for a best move. */
+#include <stdio.h>
+
+#include "yukari.h"
+
+#define STARTPOS "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+
+int main(void) {
+    YukariBoard *board = NULL;
+    if (yukari_board_new(STARTPOS, &board) != YUKARI_STATUS_OK) {
+        fprintf(stderr, "could not parse the starting position\n");
+        return 1;
+    }
+
+    uint16_t moves[256];
+    size_t count = 0;
+    if (yukari_board_legal_moves(board, moves, 256, &count) != YUKARI_STATUS_OK) {
+        fprintf(stderr, "could not generate moves\n");
+        yukari_board_free(board);
+        return 1;
+    }
+    printf("%zu legal moves:", count);
+    for (size_t i = 0; i < count; i++) {
+        char text[6];
+        yukari_move_text(moves[i], text, sizeof text);
+        printf(" %s", text);
+    }
+    printf("\n");
+    yukari_board_free(board);
+
+    char best[6];
+    if (yukari_search(STARTPOS, 500, best, sizeof best) != YUKARI_STATUS_OK) {
+        fprintf(stderr, "search failed\n");
+        return 1;
+    }
+    printf("bestmove %s\n", best);
+    return 0;
+}
@@ -0,0 +1,95 @@
+#ifndef YUKARI_H
+#define YUKARI_H
+
+/* Generated by cbindgen from yukari-ffi; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call.
+typedef enum YukariStatus {
+  // The call succeeded.
+  YUKARI_STATUS_OK = 0,
+  // A pointer argument was null.
+  YUKARI_STATUS_NULL_POINTER,
+  // A string argument was not valid UTF-8.
+  YUKARI_STATUS_BAD_STRING,
+  // The FEN could not be parsed, or describes an illegal position.
+  YUKARI_STATUS_BAD_FEN,
+  // The move is not legal in the position.
+  YUKARI_STATUS_ILLEGAL_MOVE,
+  // The output buffer is too small for the result.
+  YUKARI_STATUS_BUFFER_TOO_SMALL,
+  // The position has no legal moves to search.
+  YUKARI_STATUS_NO_LEGAL_MOVES,
+  // The library panicked, which is only caught in builds that unwind; the arguments are
+  // left as they were where possible.
+  YUKARI_STATUS_PANIC,
+} YukariStatus;
+
+// A chess position, created by `yukari_board_new` and freed by `yukari_board_free`.
+typedef struct YukariBoard YukariBoard;
+
+// Create a board from a FEN, storing it in `*out`.
+//
+// # Safety
+// `fen` must be a NUL-terminated string and `out` must be valid for writes.
+enum YukariStatus yukari_board_new(const char *fen, struct YukariBoard **out);
+
+// Free a board created by `yukari_board_new`. Freeing null does nothing.
+//
+// # Safety
+// `board` must be null or a board from `yukari_board_new` that has not been freed.
+void yukari_board_free(struct YukariBoard *board);
+
+// Write the legal moves of a position into `moves`, which holds `capacity` moves, and
+// their number into `*count`.
+//
+// A position has at most 218 legal moves, so a buffer of 256 is always enough. If `moves`
+// is too small, `*count` still receives the number of moves.
+//
+// # Safety
+// `board` must be a live board, `moves` must be valid for writes of `capacity` moves and
+// `count` must be valid for writes.
+enum YukariStatus yukari_board_legal_moves(const struct YukariBoard *board,
+                                           uint16_t *moves,
+                                           size_t capacity,
+                                           size_t *count);
+
+// Play a packed move on a board.
+//
+// # Safety
+// `board` must be a live board.
+enum YukariStatus yukari_board_make_move(struct YukariBoard *board, uint16_t m);
+
+// Write the FEN of a position into `buf`, which holds `len` bytes. 90 bytes are always
+// enough.
+//
+// # Safety
+// `board` must be a live board and `buf` must be valid for writes of `len` bytes.
+enum YukariStatus yukari_board_fen(const struct YukariBoard *board, char *buf, size_t len);
+
+// Write the Zobrist hash of a position into `*hash`.
+//
+// # Safety
+// `board` must be a live board and `hash` must be valid for writes.
+enum YukariStatus yukari_board_hash(const struct YukariBoard *board, uint64_t *hash);
+
+// Write a packed move in coordinate notation (such as `e7e8q`) into `buf`, which holds
+// `len` bytes. 6 bytes are always enough.
+//
+// # Safety
+// `buf` must be valid for writes of `len` bytes.
+enum YukariStatus yukari_move_text(uint16_t m, char *buf, size_t len);
+
+// Search a position for `movetime_ms` milliseconds and write the best move, in coordinate
+// notation, into `buf`, which holds `len` bytes. This blocks until the search finishes.
+//
+// # Safety
+// `fen` must be a NUL-terminated string and `buf` must be valid for writes of `len` bytes.
+enum YukariStatus yukari_search(const char *fen, uint32_t movetime_ms, char *buf, size_t len);
+
+#endif /* YUKARI_H */
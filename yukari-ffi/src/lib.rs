@@ -0,0 +1,441 @@
+//! A C interface to the move generator and search, for GUIs that are not written in Rust.
+//!
+//! Every function returns a [`YukariStatus`] and writes its results through pointers. Bad
+//! arguments are reported through the status rather than by panicking. The workspace release
+//! profile aborts on panic, so a panic from a bug in the library ends the host process; only
+//! builds that unwind, such as debug builds, catch it and report [`YukariStatus::Panic`]
+//! instead.
+//!
+//! Moves cross the boundary packed into 16 bits: the source square in bits 0-5, the
+//! destination square in bits 6-11 and the promotion piece in bits 12-14 (0 for none, then
+//! knight, bishop, rook and queen). Squares count from a1 = 0 to h8 = 63, rank by rank.
+//!
+//! The header in `include/yukari.h` is generated from this file with cbindgen, and
+//! `cargo test -p yukari-ffi --features c-test` builds and runs the C example in `c/`.
+
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use tinyvec::ArrayVec;
+use yukari::{SearchParams, TranspositionTable};
+use yukari_movegen::{Board, Colour, Move, Piece, Square, Zobrist};
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YukariStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A pointer argument was null.
+    NullPointer,
+    /// A string argument was not valid UTF-8.
+    BadString,
+    /// The FEN could not be parsed, or describes an illegal position.
+    BadFen,
+    /// The move is not legal in the position.
+    IllegalMove,
+    /// The output buffer is too small for the result.
+    BufferTooSmall,
+    /// The position has no legal moves to search.
+    NoLegalMoves,
+    /// The library panicked, which is only caught in builds that unwind; the arguments are
+    /// left as they were where possible.
+    Panic,
+}
+
+/// A chess position, created by `yukari_board_new` and freed by `yukari_board_free`.
+pub struct YukariBoard {
+    board: Board,
+    /// The board does not count moves, so the fullmove number for FENs is kept here.
+    fullmove: u32,
+}
+
+/// The Zobrist keys every board is hashed with.
+fn zobrist() -> &'static Zobrist {
+    static ZOBRIST: OnceLock<Zobrist> = OnceLock::new();
+    ZOBRIST.get_or_init(Zobrist::new)
+}
+
+/// Run `f`, reporting a panic as a status instead of unwinding into C. Under `panic = "abort"`
+/// there is nothing to catch, so `f` must report bad input through its result.
+fn guard(f: impl FnOnce() -> Result<(), YukariStatus>) -> YukariStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => YukariStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => YukariStatus::Panic,
+    }
+}
+
+/// Borrow a C string as a `&str`.
+///
+/// # Safety
+/// `s` must be null or point to a NUL-terminated string that outlives the borrow.
+unsafe fn borrow_str<'a>(s: *const c_char) -> Result<&'a str, YukariStatus> {
+    if s.is_null() {
+        return Err(YukariStatus::NullPointer);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| YukariStatus::BadString)
+}
+
+/// Copy `s` into a buffer of `len` bytes as a NUL-terminated string.
+///
+/// # Safety
+/// `buf` must be null or valid for writes of `len` bytes.
+unsafe fn write_str(s: &str, buf: *mut c_char, len: usize) -> Result<(), YukariStatus> {
+    if buf.is_null() {
+        return Err(YukariStatus::NullPointer);
+    }
+    if s.len() >= len {
+        return Err(YukariStatus::BufferTooSmall);
+    }
+    std::ptr::copy_nonoverlapping(s.as_ptr(), buf.cast::<u8>(), s.len());
+    *buf.add(s.len()) = 0;
+    Ok(())
+}
+
+/// Parse a position, reading the fullmove number the board itself ignores.
+fn parse_fen(fen: &str) -> Result<YukariBoard, YukariStatus> {
+    let board = Board::from_fen(fen, zobrist()).map_err(|_| YukariStatus::BadFen)?;
+    let fullmove = fen
+        .split_ascii_whitespace()
+        .nth(5)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1);
+    Ok(YukariBoard { board, fullmove })
+}
+
+/// The legal moves in a position.
+fn legal_moves(board: &Board) -> ArrayVec<[Move; 256]> {
+    let mut moves = ArrayVec::new();
+    board.generate(&mut moves);
+    moves
+}
+
+/// Pack a move into 16 bits.
+#[must_use]
+pub fn pack_move(m: Move) -> u16 {
+    let prom = match m.prom {
+        None => 0,
+        Some(Piece::Knight) => 1,
+        Some(Piece::Bishop) => 2,
+        Some(Piece::Rook) => 3,
+        Some(_) => 4,
+    };
+    u16::from(m.from.into_inner()) | u16::from(m.dest.into_inner()) << 6 | prom << 12
+}
+
+/// Find the legal move a packed move stands for.
+#[must_use]
+pub fn unpack_move(board: &Board, packed: u16) -> Option<Move> {
+    legal_moves(board)
+        .into_iter()
+        .find(|&m| pack_move(m) == packed)
+}
+
+/// Create a board from a FEN, storing it in `*out`.
+///
+/// # Safety
+/// `fen` must be a NUL-terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_board_new(
+    fen: *const c_char,
+    out: *mut *mut YukariBoard,
+) -> YukariStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(YukariStatus::NullPointer);
+        }
+        let board = parse_fen(borrow_str(fen)?)?;
+        *out = Box::into_raw(Box::new(board));
+        Ok(())
+    })
+}
+
+/// Free a board created by `yukari_board_new`. Freeing null does nothing.
+///
+/// # Safety
+/// `board` must be null or a board from `yukari_board_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_board_free(board: *mut YukariBoard) {
+    if !board.is_null() {
+        drop(Box::from_raw(board));
+    }
+}
+
+/// Write the legal moves of a position into `moves`, which holds `capacity` moves, and
+/// their number into `*count`.
+///
+/// A position has at most 218 legal moves, so a buffer of 256 is always enough. If `moves`
+/// is too small, `*count` still receives the number of moves.
+///
+/// # Safety
+/// `board` must be a live board, `moves` must be valid for writes of `capacity` moves and
+/// `count` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_board_legal_moves(
+    board: *const YukariBoard,
+    moves: *mut u16,
+    capacity: usize,
+    count: *mut usize,
+) -> YukariStatus {
+    guard(|| {
+        let board = board.as_ref().ok_or(YukariStatus::NullPointer)?;
+        if moves.is_null() || count.is_null() {
+            return Err(YukariStatus::NullPointer);
+        }
+        let legal = legal_moves(&board.board);
+        *count = legal.len();
+        if legal.len() > capacity {
+            return Err(YukariStatus::BufferTooSmall);
+        }
+        for (index, &m) in legal.iter().enumerate() {
+            *moves.add(index) = pack_move(m);
+        }
+        Ok(())
+    })
+}
+
+/// Play a packed move on a board.
+///
+/// # Safety
+/// `board` must be a live board.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_board_make_move(board: *mut YukariBoard, m: u16) -> YukariStatus {
+    guard(|| {
+        let board = board.as_mut().ok_or(YukariStatus::NullPointer)?;
+        let m = unpack_move(&board.board, m).ok_or(YukariStatus::IllegalMove)?;
+        board.board = board.board.make(m, zobrist());
+        if board.board.side() == Colour::White {
+            board.fullmove += 1;
+        }
+        Ok(())
+    })
+}
+
+/// Write the FEN of a position into `buf`, which holds `len` bytes. 90 bytes are always
+/// enough.
+///
+/// # Safety
+/// `board` must be a live board and `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_board_fen(
+    board: *const YukariBoard,
+    buf: *mut c_char,
+    len: usize,
+) -> YukariStatus {
+    guard(|| {
+        let board = board.as_ref().ok_or(YukariStatus::NullPointer)?;
+        write_str(&board.board.to_fen(board.fullmove), buf, len)
+    })
+}
+
+/// Write the Zobrist hash of a position into `*hash`.
+///
+/// # Safety
+/// `board` must be a live board and `hash` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_board_hash(
+    board: *const YukariBoard,
+    hash: *mut u64,
+) -> YukariStatus {
+    guard(|| {
+        let board = board.as_ref().ok_or(YukariStatus::NullPointer)?;
+        if hash.is_null() {
+            return Err(YukariStatus::NullPointer);
+        }
+        *hash = board.board.hash();
+        Ok(())
+    })
+}
+
+/// Write a packed move in coordinate notation (such as `e7e8q`) into `buf`, which holds
+/// `len` bytes. 6 bytes are always enough.
+///
+/// # Safety
+/// `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_move_text(m: u16, buf: *mut c_char, len: usize) -> YukariStatus {
+    guard(|| {
+        let square =
+            |bits: u16| Square::try_from((bits & 63) as u8).map_err(|()| YukariStatus::IllegalMove);
+        let mut text = format!("{}{}", square(m)?, square(m >> 6)?);
+        match m >> 12 {
+            0 => {}
+            prom @ 1..=4 => {
+                text.push(Piece::ALL[usize::from(prom)].to_coloured_char(Colour::Black))
+            }
+            _ => return Err(YukariStatus::IllegalMove),
+        }
+        write_str(&text, buf, len)
+    })
+}
+
+/// Search a position for `movetime_ms` milliseconds and write the best move, in coordinate
+/// notation, into `buf`, which holds `len` bytes. This blocks until the search finishes.
+///
+/// # Safety
+/// `fen` must be a NUL-terminated string and `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn yukari_search(
+    fen: *const c_char,
+    movetime_ms: u32,
+    buf: *mut c_char,
+    len: usize,
+) -> YukariStatus {
+    guard(|| {
+        let board = parse_fen(borrow_str(fen)?)?.board;
+        let Some(&fallback) = legal_moves(&board).first() else {
+            return Err(YukariStatus::NoLegalMoves);
+        };
+
+        let stop_after = Instant::now() + Duration::from_millis(movetime_ms.into());
+        let mut tt = TranspositionTable::default();
+        let mut s = yukari::Search::new(Some(stop_after), zobrist(), &mut tt);
+        let mut best = None;
+        let mut pv = ArrayVec::new();
+        for depth in 1..SearchParams::DEFAULT.depth_limit {
+            pv.set_len(0);
+            s.search_root(&board, depth, &mut pv, &mut Vec::new());
+            // An interrupted first iteration still leaves a move to play.
+            if best.is_none() || !s.stopped() {
+                best = pv.first().copied().or(best);
+            }
+            if s.stopped() || Instant::now() >= stop_after {
+                break;
+            }
+        }
+        write_str(&best.unwrap_or(fallback).to_string(), buf, len)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_char, CStr, CString};
+    use std::ptr;
+
+    use super::{
+        yukari_board_fen, yukari_board_free, yukari_board_hash, yukari_board_legal_moves,
+        yukari_board_make_move, yukari_board_new, yukari_move_text, yukari_search, YukariBoard,
+        YukariStatus,
+    };
+
+    const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn new_board(fen: &str) -> Result<*mut YukariBoard, YukariStatus> {
+        let fen = CString::new(fen).unwrap();
+        let mut board = ptr::null_mut();
+        match unsafe { yukari_board_new(fen.as_ptr(), &mut board) } {
+            YukariStatus::Ok => Ok(board),
+            status => Err(status),
+        }
+    }
+
+    fn text(buf: &[c_char]) -> String {
+        unsafe { CStr::from_ptr(buf.as_ptr()) }
+            .to_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    #[test]
+    fn play_through_the_interface() {
+        let board = new_board(STARTPOS).unwrap();
+        let mut moves = [0_u16; 256];
+        let mut count = 0;
+        let status =
+            unsafe { yukari_board_legal_moves(board, moves.as_mut_ptr(), moves.len(), &mut count) };
+        assert_eq!(status, YukariStatus::Ok);
+        assert_eq!(count, 20);
+
+        let mut names = Vec::new();
+        for &m in &moves[..count] {
+            let mut buf = [0; 6];
+            let status = unsafe { yukari_move_text(m, buf.as_mut_ptr(), buf.len()) };
+            assert_eq!(status, YukariStatus::Ok);
+            names.push(text(&buf));
+        }
+        let e4 = moves[names.iter().position(|name| name == "e2e4").unwrap()];
+
+        let mut before = 0;
+        let mut after = 0;
+        unsafe {
+            assert_eq!(yukari_board_hash(board, &mut before), YukariStatus::Ok);
+            assert_eq!(yukari_board_make_move(board, e4), YukariStatus::Ok);
+            assert_eq!(yukari_board_hash(board, &mut after), YukariStatus::Ok);
+            // White cannot play again.
+            assert_eq!(yukari_board_make_move(board, e4), YukariStatus::IllegalMove);
+        }
+        assert_ne!(before, after);
+
+        let mut buf = [0; 90];
+        let status = unsafe { yukari_board_fen(board, buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(status, YukariStatus::Ok);
+        assert_eq!(
+            text(&buf),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        let status = unsafe { yukari_board_fen(board, buf.as_mut_ptr(), 10) };
+        assert_eq!(status, YukariStatus::BufferTooSmall);
+
+        unsafe { yukari_board_free(board) };
+    }
+
+    #[test]
+    fn promotions_pack_the_piece() {
+        let board = new_board("7k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let mut moves = [0_u16; 256];
+        let mut count = 0;
+        unsafe { yukari_board_legal_moves(board, moves.as_mut_ptr(), moves.len(), &mut count) };
+        let mut names = Vec::new();
+        for &m in &moves[..count] {
+            let mut buf = [0; 6];
+            unsafe { yukari_move_text(m, buf.as_mut_ptr(), buf.len()) };
+            names.push(text(&buf));
+        }
+        for name in ["a7a8q", "a7a8r", "a7a8b", "a7a8n"] {
+            assert!(names.iter().any(|m| m == name), "{name} missing");
+        }
+        unsafe { yukari_board_free(board) };
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(new_board("not a fen").err(), Some(YukariStatus::BadFen));
+        let mut board = ptr::null_mut();
+        let status = unsafe { yukari_board_new(ptr::null(), &mut board) };
+        assert_eq!(status, YukariStatus::NullPointer);
+        let status = unsafe { yukari_board_make_move(ptr::null_mut(), 0) };
+        assert_eq!(status, YukariStatus::NullPointer);
+
+        let mut moves = [0_u16; 4];
+        let mut count = 0;
+        let board = new_board(STARTPOS).unwrap();
+        let status =
+            unsafe { yukari_board_legal_moves(board, moves.as_mut_ptr(), moves.len(), &mut count) };
+        assert_eq!(status, YukariStatus::BufferTooSmall);
+        assert_eq!(count, 20);
+        unsafe { yukari_board_free(board) };
+
+        // Promotion bits past the queen stand for no move.
+        let mut buf = [0; 6];
+        let status = unsafe { yukari_move_text(0xffff, buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(status, YukariStatus::IllegalMove);
+    }
+
+    #[test]
+    fn search_finds_mate() {
+        let fen = CString::new("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut buf = [0; 6];
+        let status = unsafe { yukari_search(fen.as_ptr(), 200, buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(status, YukariStatus::Ok);
+        assert_eq!(text(&buf), "a1a8");
+
+        let mate = CString::new("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let status = unsafe { yukari_search(mate.as_ptr(), 200, buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(status, YukariStatus::NoLegalMoves);
+    }
+}
@@ -0,0 +1,45 @@
+//! Build the C example against the library and run it. Needs a C compiler, found through
+//! `CC` or as `cc`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_example_lists_moves_and_searches() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Cargo builds the library for tests into `deps`, next to the test binary.
+    let exe = std::env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap();
+    let example = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("legal_moves");
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(cc)
+        .arg(manifest.join("c/legal_moves.c"))
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg("-L")
+        .arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lyukari_ffi")
+        .arg("-o")
+        .arg(&example)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success());
+
+    let output = Command::new(&example).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+
+    let moves = lines.next().unwrap();
+    assert!(moves.starts_with("20 legal moves:"), "{moves}");
+    assert_eq!(moves.split_whitespace().count(), 23);
+    for m in ["a2a3", "e2e4", "g1f3", "b1c3"] {
+        assert!(moves.contains(m), "{m} missing from {moves}");
+    }
+
+    let best = lines.next().unwrap();
+    let best = best.strip_prefix("bestmove ").unwrap();
+    assert!(moves.split_whitespace().any(|m| m == best), "{best}");
+}
@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use yukari_movegen::{Bitlist, Board, Colour, Zobrist, BENCH_FENS};
+
+pub fn bitlist_suite(c: &mut Criterion) {
+    let zobrist = Zobrist::new();
+    let boards: Vec<Board> = BENCH_FENS
+        .iter()
+        .map(|fen| Board::from_fen(fen, &zobrist).unwrap())
+        .collect();
+    let bitlists: Vec<Bitlist> = boards
+        .iter()
+        .flat_map(|board| {
+            [
+                board.pieces(),
+                board.pieces_of_colour(Colour::White),
+                board.pieces_of_colour(Colour::Black),
+                board.pawns(),
+                board.knights() | board.bishops(),
+                board.rooks() | board.queens(),
+            ]
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("bitlist_suite");
+
+    group.sample_size(1_000);
+    group.significance_level(0.005);
+    group.noise_threshold(0.025);
+
+    group.throughput(Throughput::Elements(bitlists.len() as u64));
+    group.bench_with_input("iterate", &bitlists, |b, bitlists| {
+        b.iter(|| {
+            for bitlist in bitlists {
+                for piece in *bitlist {
+                    criterion::black_box(piece);
+                }
+            }
+        })
+    });
+
+    group.throughput(Throughput::Elements(boards.len() as u64));
+    group.bench_with_input("iter_squares", &boards, |b, boards| {
+        b.iter(|| {
+            for board in boards {
+                for square in board.pieces().iter_squares(board) {
+                    criterion::black_box(square);
+                }
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default();
+    targets = bitlist_suite
+}
+
+criterion_main!(benches);
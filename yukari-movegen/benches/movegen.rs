@@ -0,0 +1,58 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use yukari_movegen::{Board, MoveList, Zobrist, BENCH_FENS};
+
+pub fn movegen_suite(c: &mut Criterion) {
+    let zobrist = Zobrist::new();
+    let boards: Vec<Board> = BENCH_FENS
+        .iter()
+        .map(|fen| Board::from_fen(fen, &zobrist).unwrap())
+        .collect();
+
+    let mut moves = MoveList::new();
+
+    let mut group = c.benchmark_group("movegen_suite");
+
+    group.sample_size(1_000);
+    group.significance_level(0.005);
+    group.noise_threshold(0.025);
+
+    // Throughput is in positions, so the numbers are comparable between functions.
+    group.throughput(Throughput::Elements(boards.len() as u64));
+    group.bench_with_input("generate", &boards, |b, boards| {
+        b.iter(|| {
+            for board in boards {
+                moves.set_len(0);
+                board.generate(&mut moves);
+            }
+        })
+    });
+
+    group.throughput(Throughput::Elements(boards.len() as u64));
+    group.bench_with_input("generate_captures", &boards, |b, boards| {
+        b.iter(|| {
+            for board in boards {
+                moves.set_len(0);
+                board.generate_captures(&mut moves);
+            }
+        })
+    });
+
+    group.throughput(Throughput::Elements(boards.len() as u64));
+    group.bench_with_input("discover_pinned_pieces", &boards, |b, boards| {
+        b.iter(|| {
+            for board in boards {
+                criterion::black_box(board.discover_pinned_pieces());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default();
+    targets = movegen_suite
+}
+
+criterion_main!(benches);
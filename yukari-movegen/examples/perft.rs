@@ -1,15 +1,12 @@
 use rayon::prelude::*;
-use tinyvec::ArrayVec;
-use yukari_movegen::{perft, Board, Move, Zobrist};
+use yukari_movegen::{perft, perft_parallel, perft_stats, Board, MoveList, PerftTable, Zobrist};
 
 #[must_use]
 pub fn divide(board: &Board, zobrist: &Zobrist, depth: u32) -> u64 {
     if depth == 0 {
         1
     } else {
-        let moves: [Move; 256] = [Move::default(); 256];
-        let mut moves = ArrayVec::from(moves);
-        moves.set_len(0);
+        let mut moves = MoveList::new();
         board.generate(&mut moves);
 
         moves
@@ -28,7 +25,13 @@ fn main() {
     let zobrist = Zobrist::new();
     let startpos = Board::startpos(&zobrist); //Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &zobrist).unwrap();
     let depth = 6;
+    // With --stats, break the leaves down by kind of move, for comparing against published tables
+    if std::env::args().any(|arg| arg == "--stats") {
+        let stats = perft_stats(&startpos, &zobrist, depth);
+        println!("Perft {}: {:?}", depth, stats);
+        return;
+    }
     //let nodes = divide(&startpos, &zobrist, depth);
-    let nodes = perft(&startpos, &zobrist, depth);
+    let nodes = perft_parallel(&startpos, &zobrist, depth, &PerftTable::new());
     println!("Perft {}: {}", depth, nodes);
 }
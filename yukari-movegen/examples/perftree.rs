@@ -0,0 +1,54 @@
+//! The command line interface perftree expects of an engine, for bisecting a move generator
+//! bug against Stockfish:
+//!
+//! ```text
+//! perftree <depth> <fen> [moves]
+//! ```
+//!
+//! The moves, separated by spaces in one argument, are played from the position first. Prints
+//! the perft count after each legal move, a blank line and the total.
+
+use std::process::exit;
+
+use yukari_movegen::{perft_divide, Board, Zobrist};
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (Some(depth), Some(fen)) = (args.first(), args.get(1)) else {
+        eprintln!("usage: perftree <depth> <fen> [moves]");
+        exit(1);
+    };
+    let Ok(depth) = depth.parse() else {
+        eprintln!("bad depth: {depth}");
+        exit(1);
+    };
+
+    let zobrist = Zobrist::new();
+    let mut board = match Board::from_fen(fen, &zobrist) {
+        Ok(board) => board,
+        Err(err) => {
+            eprintln!("bad FEN ({err}): {fen}");
+            exit(1);
+        }
+    };
+    for m in args
+        .get(2)
+        .into_iter()
+        .flat_map(|moves| moves.split_whitespace())
+    {
+        match board.parse_move(m) {
+            Ok(parsed) => board = board.make(parsed, &zobrist),
+            Err(err) => {
+                eprintln!("bad move ({err}): {m}");
+                exit(1);
+            }
+        }
+    }
+
+    let counts = perft_divide(&board, &zobrist, depth);
+    for (m, nodes) in &counts {
+        println!("{m} {nodes}");
+    }
+    println!();
+    println!("{}", counts.iter().map(|&(_, nodes)| nodes).sum::<u64>());
+}
@@ -1,9 +1,11 @@
-use super::index::PieceIndex;
+use super::{index::PieceIndex, Board};
 use crate::{colour::Colour, square::Square};
 use std::{
     fmt::Debug,
     iter::FusedIterator,
-    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Index, Not},
+    ops::{
+        BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Index, Not, Sub, SubAssign,
+    },
 };
 
 /// A set of 32 bits, each representing a piece.
@@ -19,26 +21,39 @@ impl Default for Bitlist {
 
 impl Bitlist {
     /// Create a new, empty Bitlist.
+    #[must_use]
     pub const fn new() -> Self {
         Self(0)
     }
 
     /// Create a mask of the white-piece bits.
+    #[must_use]
     pub const fn white() -> Self {
         Self(0x0000_FFFF)
     }
 
     /// Create a mask of the black-piece bits.
+    #[must_use]
     pub const fn black() -> Self {
         Self(0xFFFF_0000)
     }
 
     /// Count the number of set bits in a bitlist.
+    #[inline]
+    #[must_use]
     pub const fn count_ones(self) -> u32 {
         self.0.count_ones()
     }
 
+    /// Count the number of set bits belonging to pieces of `colour`.
+    #[inline]
+    #[must_use]
+    pub const fn count_of_colour(self, colour: Colour) -> u32 {
+        self.and(Self::mask_from_colour(colour)).count_ones()
+    }
+
     /// Create a mask corresponding to the bits of a given colour.
+    #[must_use]
     pub const fn mask_from_colour(colour: Colour) -> Self {
         match colour {
             Colour::White => Self::white(),
@@ -47,16 +62,28 @@ impl Bitlist {
     }
 
     /// Returns true if this `Bitlist` contains `other`.
+    #[must_use]
     pub const fn contains(self, other: Self) -> bool {
         (self.0 & other.0) != 0
     }
 
+    /// Returns true if this `Bitlist` and `other` have any bit in common.
+    #[inline]
+    #[must_use]
+    pub const fn intersects(self, other: Self) -> bool {
+        (self.0 & other.0) != 0
+    }
+
     /// Returns true if this `Bitlist` is empty.
+    #[inline]
+    #[must_use]
     pub const fn empty(self) -> bool {
         self.0 == 0
     }
 
     /// Return the lowest set bit of a `Bitlist` as a `PieceIndex`, if it exists.
+    #[inline]
+    #[must_use]
     pub const fn peek(self) -> Option<PieceIndex> {
         if self.0 == 0 {
             return None;
@@ -67,8 +94,18 @@ impl Bitlist {
     }
 
     /// Return the lowest set bit of a `Bitlist` as a `PieceIndex`.
+    ///
+    /// # Safety
+    /// The `Bitlist` must not be empty.
+    ///
+    /// # Panics
+    /// With the `safe-fallbacks` feature, panics if the `Bitlist` is empty.
+    #[inline]
+    #[must_use]
     pub const unsafe fn peek_nonzero(self) -> PieceIndex {
-        if self.0 == 0 {
+        if cfg!(feature = "safe-fallbacks") {
+            assert!(self.0 != 0, "peek_nonzero called on an empty Bitlist");
+        } else if self.0 == 0 {
             std::hint::unreachable_unchecked();
         }
         #[allow(clippy::cast_possible_truncation)]
@@ -77,28 +114,57 @@ impl Bitlist {
     }
 
     /// Return the lowest set bit of a `Bitlist` as a `PieceIndex`, if it exists, and clear that bit.
-    pub fn pop(&mut self) -> Option<PieceIndex> {
-        let bit = self.peek()?;
-        self.0 &= self.0.wrapping_sub(1);
-        Some(bit)
+    #[inline]
+    pub const fn pop(&mut self) -> Option<PieceIndex> {
+        let bits = self.0;
+        if bits == 0 {
+            return None;
+        }
+        // Clearing the lowest set bit compiles to a single BLSR where BMI1 is available.
+        self.0 = bits & (bits - 1);
+        #[allow(clippy::cast_possible_truncation)]
+        let bit = bits.trailing_zeros() as u8;
+        unsafe { Some(PieceIndex::new_unchecked(bit)) }
+    }
+
+    /// Return the square of the piece of the lowest set bit, if it exists.
+    #[inline]
+    #[must_use]
+    pub fn first_square(self, board: &Board) -> Option<Square> {
+        self.peek().map(|piece| board.square_of_piece(piece))
+    }
+
+    /// Iterate over the squares of the pieces in a `Bitlist`, lowest bit first.
+    #[inline]
+    #[must_use]
+    pub fn iter_squares(self, board: &Board) -> impl ExactSizeIterator<Item = Square> + '_ {
+        self.into_iter().map(|piece| board.square_of_piece(piece))
     }
 
     // TODO: remove when traits can have const impls.
+    #[inline]
+    #[must_use]
     pub const fn from_piece(index: PieceIndex) -> Self {
         Self(1_u32 << index.into_inner())
     }
 
     // TODO: remove when traits can have const impls.
+    #[inline]
+    #[must_use]
     pub const fn and(self, rhs: Self) -> Self {
         Self(self.0 & rhs.0)
     }
 
     // TODO: remove when traits can have const impls.
+    #[inline]
+    #[must_use]
     pub const fn or(self, rhs: Self) -> Self {
         Self(self.0 | rhs.0)
     }
 
     // TODO: remove when traits can have const impls.
+    #[inline]
+    #[must_use]
     pub const fn invert(self) -> Self {
         Self(!self.0)
     }
@@ -119,12 +185,14 @@ impl From<u32> for Bitlist {
 impl BitAnd for Bitlist {
     type Output = Self;
 
+    #[inline]
     fn bitand(self, rhs: Self) -> Self::Output {
         Self(self.0 & rhs.0)
     }
 }
 
 impl BitAndAssign for Bitlist {
+    #[inline]
     fn bitand_assign(&mut self, rhs: Self) {
         self.0 &= rhs.0;
     }
@@ -133,20 +201,56 @@ impl BitAndAssign for Bitlist {
 impl BitOr for Bitlist {
     type Output = Self;
 
+    #[inline]
     fn bitor(self, rhs: Self) -> Self::Output {
         Self(self.0 | rhs.0)
     }
 }
 
 impl BitOrAssign for Bitlist {
+    #[inline]
     fn bitor_assign(&mut self, rhs: Self) {
         self.0 |= rhs.0;
     }
 }
 
+impl BitXor for Bitlist {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Self(self.0 ^ rhs.0)
+    }
+}
+
+impl BitXorAssign for Bitlist {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        self.0 ^= rhs.0;
+    }
+}
+
+/// Set difference: the bits of the left side that are not in the right side.
+impl Sub for Bitlist {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl SubAssign for Bitlist {
+    #[inline]
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 &= !rhs.0;
+    }
+}
+
 impl Not for Bitlist {
     type Output = Self;
 
+    #[inline]
     fn not(self) -> Self::Output {
         Self(!self.0)
     }
@@ -156,6 +260,7 @@ impl IntoIterator for Bitlist {
     type Item = PieceIndex;
     type IntoIter = BitlistIter;
 
+    #[inline]
     fn into_iter(self) -> Self::IntoIter {
         BitlistIter(self)
     }
@@ -169,6 +274,7 @@ pub struct BitlistIter(Bitlist);
 impl Iterator for BitlistIter {
     type Item = PieceIndex;
 
+    #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         self.0.pop()
     }
@@ -248,3 +354,79 @@ impl BitlistArray {
         self.0[index] &= !piece;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Bitlist, PieceIndex};
+    use crate::{colour::Colour, Board, Zobrist};
+
+    #[test]
+    fn empty_and_full() {
+        let empty = Bitlist::new();
+        let full = Bitlist::from(0xFFFF_FFFF);
+        assert!(empty.empty());
+        assert_eq!(empty.into_iter().count(), 0);
+        assert_eq!(empty.count_of_colour(Colour::White), 0);
+        assert!(!empty.intersects(full));
+
+        assert_eq!(full.count_ones(), 32);
+        assert_eq!(full.into_iter().len(), 32);
+        assert!(full
+            .into_iter()
+            .map(|bit| u32::from(bit.into_inner()))
+            .eq(0..32));
+        assert_eq!(full.count_of_colour(Colour::White), 16);
+        assert_eq!(full.count_of_colour(Colour::Black), 16);
+        assert!(full.intersects(full));
+
+        let mut popped = full;
+        assert_eq!(popped.pop().map(PieceIndex::into_inner), Some(0));
+        assert_eq!(popped.count_ones(), 31);
+        let mut popped = empty;
+        assert!(popped.pop().is_none());
+        assert!(popped.empty());
+    }
+
+    #[test]
+    fn colour_masks() {
+        let white = Bitlist::white();
+        let black = Bitlist::black();
+        assert_eq!(white | black, Bitlist::from(0xFFFF_FFFF));
+        assert_eq!(white ^ black, white | black);
+        assert_eq!(!white, black);
+        assert!(!white.intersects(black));
+        assert_eq!((white | black) - white, black);
+        assert!((white - white).empty());
+        assert_eq!(Bitlist::mask_from_colour(Colour::White), white);
+        assert_eq!(Bitlist::mask_from_colour(Colour::Black), black);
+
+        let mixed = Bitlist::from(0x0003_0007);
+        assert_eq!(mixed.count_of_colour(Colour::White), 3);
+        assert_eq!(mixed.count_of_colour(Colour::Black), 2);
+        assert_eq!(mixed ^ white, Bitlist::from(0x0003_FFF8));
+
+        let mut assigned = mixed;
+        assigned ^= Bitlist::from(1);
+        assert_eq!(assigned, Bitlist::from(0x0003_0006));
+        assigned -= black;
+        assert_eq!(assigned, Bitlist::from(0x0000_0006));
+    }
+
+    #[test]
+    fn squares() {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", &zobrist).unwrap();
+        let rooks = board.rooks();
+        let mut squares: Vec<String> = rooks.iter_squares(&board).map(|s| s.to_string()).collect();
+        squares.sort();
+        assert_eq!(squares, ["a1", "h1"]);
+        assert_eq!(rooks.iter_squares(&board).len(), 2);
+
+        let king = board.kings() & Bitlist::black();
+        assert_eq!(
+            king.first_square(&board).map(|s| s.to_string()).as_deref(),
+            Some("e8")
+        );
+        assert!(Bitlist::new().first_square(&board).is_none());
+    }
+}
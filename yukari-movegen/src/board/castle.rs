@@ -0,0 +1,134 @@
+use std::fmt::Display;
+
+use super::Zobrist;
+use crate::colour::Colour;
+
+/// The side of the board a king castles towards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CastleSide {
+    /// Towards the h-file.
+    Kingside,
+    /// Towards the a-file.
+    Queenside,
+}
+
+/// Which of the four castling rights remain, one bit each in the order of their Zobrist keys:
+/// white kingside, white queenside, black kingside, black queenside.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CastleRights(u8);
+
+impl CastleRights {
+    /// No castling rights at all.
+    pub const NONE: Self = Self(0);
+
+    /// The bit of one right, which is also the index of its Zobrist key.
+    const fn index(colour: Colour, side: CastleSide) -> usize {
+        let colour = match colour {
+            Colour::White => 0,
+            Colour::Black => 2,
+        };
+        let side = match side {
+            CastleSide::Kingside => 0,
+            CastleSide::Queenside => 1,
+        };
+        colour + side
+    }
+
+    /// Whether `colour` may still castle towards `side`.
+    #[must_use]
+    pub const fn has(self, colour: Colour, side: CastleSide) -> bool {
+        self.0 & (1 << Self::index(colour, side)) != 0
+    }
+
+    /// Whether no castling rights remain.
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Grant `colour` the right to castle towards `side`, returning whether it was new.
+    pub const fn add(&mut self, colour: Colour, side: CastleSide) -> bool {
+        let before = self.0;
+        self.0 |= 1 << Self::index(colour, side);
+        self.0 != before
+    }
+
+    /// Take away the right of `colour` to castle towards `side`, returning whether it had it.
+    pub const fn remove(&mut self, colour: Colour, side: CastleSide) -> bool {
+        let before = self.0;
+        self.0 &= !(1 << Self::index(colour, side));
+        self.0 != before
+    }
+
+    /// Take away both rights of `colour`, returning whether it had either.
+    pub const fn remove_colour(&mut self, colour: Colour) -> bool {
+        let kingside = self.remove(colour, CastleSide::Kingside);
+        let queenside = self.remove(colour, CastleSide::Queenside);
+        kingside || queenside
+    }
+
+    /// The Zobrist keys of the rights held, combined; XOR the deltas of the rights before and
+    /// after a move to update a hash.
+    #[must_use]
+    pub fn hash_delta(self, zobrist: &Zobrist) -> u64 {
+        zobrist
+            .castling
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| self.0 & (1 << i) != 0)
+            .fold(0, |hash, (_, key)| hash ^ key)
+    }
+}
+
+impl Display for CastleRights {
+    /// The rights as they appear in FEN: some of `KQkq`, or `-` for none.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "-");
+        }
+        for (i, c) in ['K', 'Q', 'k', 'q'].into_iter().enumerate() {
+            if self.0 & (1 << i) != 0 {
+                write!(f, "{c}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CastleRights, CastleSide};
+    use crate::{Colour, Zobrist};
+
+    #[test]
+    fn add_and_remove() {
+        let mut rights = CastleRights::NONE;
+        assert_eq!(rights.to_string(), "-");
+        assert!(rights.add(Colour::White, CastleSide::Kingside));
+        assert!(!rights.add(Colour::White, CastleSide::Kingside));
+        assert!(rights.add(Colour::Black, CastleSide::Queenside));
+        assert_eq!(rights.to_string(), "Kq");
+        assert!(rights.has(Colour::Black, CastleSide::Queenside));
+        assert!(!rights.has(Colour::Black, CastleSide::Kingside));
+
+        assert!(!rights.remove(Colour::White, CastleSide::Queenside));
+        assert!(rights.remove_colour(Colour::White));
+        assert!(!rights.remove_colour(Colour::White));
+        assert_eq!(rights.to_string(), "q");
+        assert!(rights.remove(Colour::Black, CastleSide::Queenside));
+        assert!(rights.is_empty());
+    }
+
+    #[test]
+    fn hash_delta() {
+        let zobrist = Zobrist::new();
+        let mut rights = CastleRights::NONE;
+        assert_eq!(rights.hash_delta(&zobrist), 0);
+        rights.add(Colour::White, CastleSide::Queenside);
+        rights.add(Colour::Black, CastleSide::Kingside);
+        assert_eq!(
+            rights.hash_delta(&zobrist),
+            zobrist.castling[1] ^ zobrist.castling[2]
+        );
+    }
+}
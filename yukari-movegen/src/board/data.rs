@@ -10,6 +10,9 @@ use crate::{
     square::{Direction, Square, Square16x8},
 };
 
+/// Game phase weight of each piece type, pawn to king.
+const PHASE: [u8; 6] = [0, 1, 1, 2, 4, 0];
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone)]
 pub struct BoardData {
@@ -17,6 +20,8 @@ pub struct BoardData {
     piecelist: Piecelist,
     index: PieceIndexArray,
     piecemask: Piecemask,
+    /// Sum of the phase weights of every piece on the board.
+    phase: u8,
 }
 
 impl BoardData {
@@ -27,9 +32,15 @@ impl BoardData {
             piecelist: Piecelist::new(),
             index: PieceIndexArray::new(),
             piecemask: Piecemask::new(),
+            phase: 0,
         }
     }
 
+    /// Return the sum of the phase weights of every piece on the board.
+    pub const fn phase(&self) -> u8 {
+        self.phase
+    }
+
     /// Return the piece index on a square, if any.
     pub fn piece_index(&self, square: Square) -> Option<PieceIndex> {
         self.index[square]
@@ -40,6 +51,23 @@ impl BoardData {
         self.bitlist[square] & Bitlist::mask_from_colour(colour)
     }
 
+    /// Return the squares the piece on a square attacks, one bit per square with a1 lowest.
+    pub fn attacks_from(&self, square: Square) -> u64 {
+        let Some(piece) = self.index[square] else {
+            return 0;
+        };
+        let piece = Bitlist::from(piece);
+        (0_u8..64).fold(0, |attacks, dest| {
+            // SAFETY: dest is always in bounds.
+            let dest = unsafe { Square::from_u8_unchecked(dest) };
+            if self.bitlist[dest].contains(piece) {
+                attacks | 1 << dest.into_inner()
+            } else {
+                attacks
+            }
+        })
+    }
+
     /// Return the square a piece resides on.
     pub fn square_of_piece(&self, bit: PieceIndex) -> Square {
         self.piecelist.get(bit)
@@ -114,11 +142,18 @@ impl BoardData {
         Some(Colour::from(self.index[square]?))
     }
 
+    /// Given a square, return the colour and type of the piece on it, if any.
+    pub fn piece_on(&self, square: Square) -> Option<(Colour, Piece)> {
+        let bit = self.index[square]?;
+        Some((Colour::from(bit), self.piecemask.piece(bit)?))
+    }
+
     /// Add a `Piece` to a `Square`.
     pub fn add_piece(&mut self, piece: Piece, colour: Colour, square: Square, update: bool) {
         let piece_index = self.piecemask.add_piece(piece, colour);
         self.piecelist.add_piece(piece_index, square);
         self.index.add_piece(piece_index, square);
+        self.phase += PHASE[piece as usize];
 
         if update {
             self.update_attacks(square, piece_index, piece, true, None);
@@ -133,6 +168,7 @@ impl BoardData {
         self.piecemask.remove_piece(piece_index);
         self.piecelist.remove_piece(piece_index, square);
         self.index.remove_piece(piece_index, square);
+        self.phase -= PHASE[piece as usize];
 
         if update {
             self.update_attacks(square, piece_index, piece, false, None);
@@ -145,13 +181,9 @@ impl BoardData {
         let piece_index =
             self.index[from_square].expect("attempted to move piece from empty square");
         let piece = self.piece_from_bit(piece_index);
-        let slide_dir = from_square.direction(to_square).and_then(|dir| {
-            if matches!(piece, Piece::Bishop | Piece::Rook | Piece::Queen) {
-                Some(dir)
-            } else {
-                None
-            }
-        });
+        let slide_dir = from_square
+            .direction(to_square)
+            .filter(|_| matches!(piece, Piece::Bishop | Piece::Rook | Piece::Queen));
 
         self.update_attacks(from_square, piece_index, piece, false, slide_dir);
         self.update_sliders(from_square, true);
@@ -203,7 +235,7 @@ impl BoardData {
     ) {
         let update = |bitlist: &mut BitlistArray, dest: Square| {
             if add {
-                debug_assert!(dest != square);
+                debug_assert_ne!(dest, square);
                 bitlist.add_piece(dest, bit);
             } else {
                 bitlist.remove_piece(dest, bit);
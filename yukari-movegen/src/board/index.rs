@@ -13,8 +13,14 @@ pub struct PieceIndex(NonZeroU8);
 impl PieceIndex {
     /// # Safety
     /// `x` must be in the range 0-31.
+    ///
+    /// # Panics
+    /// With the `safe-fallbacks` feature, panics if `x` is out of range.
     #[must_use]
     pub const unsafe fn new_unchecked(x: u8) -> Self {
+        if cfg!(feature = "safe-fallbacks") {
+            assert!(x < 32, "piece index out of range");
+        }
         Self(NonZeroU8::new_unchecked(x + 1))
     }
 
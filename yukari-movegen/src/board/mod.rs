@@ -1,27 +1,32 @@
 use crate::{
-    chessmove::{Move, MoveType},
+    chessmove::{Move, MoveList, MoveType},
     colour::Colour,
     piece::Piece,
     square::{Direction, File, Rank, Square, Square16x8},
 };
 use std::{
     convert::{TryFrom, TryInto},
-    ffi::CString,
     fmt::Display,
+    str::FromStr,
 };
 
 use rand::{prelude::StdRng, Rng, SeedableRng};
 use tinyvec::ArrayVec;
 
 mod bitlist;
+mod castle;
 mod data;
 mod index;
 mod piecelist;
 mod piecemask;
+mod see;
+mod snapshot;
 
-use bitlist::Bitlist;
+pub use bitlist::Bitlist;
+pub use castle::{CastleRights, CastleSide};
 use data::BoardData;
 pub use index::PieceIndex;
+pub use snapshot::BoardSnapshot;
 
 /// Pin information in a board.
 pub struct PinInfo {
@@ -44,6 +49,62 @@ impl Default for PinInfo {
     }
 }
 
+/// An error encountered while parsing a FEN string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FenError {
+    /// The FEN contains non-ASCII characters.
+    NotAscii,
+    /// A required field is missing.
+    MissingField,
+    /// The piece placement field is malformed.
+    BadPlacement,
+    /// The side to move field is malformed.
+    BadSide,
+    /// The castling rights field is malformed.
+    BadCastling,
+    /// The en-passant field is malformed.
+    BadEnPassant,
+    /// The position cannot occur in a legal game.
+    IllegalPosition,
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::NotAscii => "FEN is not ASCII",
+            Self::MissingField => "FEN is missing a field",
+            Self::BadPlacement => "malformed piece placement",
+            Self::BadSide => "malformed side to move",
+            Self::BadCastling => "malformed castling rights",
+            Self::BadEnPassant => "malformed en-passant square",
+            Self::IllegalPosition => "illegal position",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// An error encountered while parsing a move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    /// The move is in neither coordinate notation nor SAN.
+    BadNotation,
+    /// No legal move matches.
+    Illegal,
+    /// More than one legal move matches.
+    Ambiguous,
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::BadNotation => "malformed move",
+            Self::Illegal => "illegal move",
+            Self::Ambiguous => "ambiguous move",
+        };
+        write!(f, "{s}")
+    }
+}
+
 #[derive(Clone)]
 pub struct Zobrist {
     pub piece: [[[u64; 64]; 6]; 2],
@@ -102,9 +163,11 @@ pub struct Board {
     /// The side to move.
     side: Colour,
     /// Castling rights, if any.
-    castle: (bool, bool, bool, bool),
-    /// En-passant square, if any.
-    ep: Option<Square>,
+    castle: CastleRights,
+    /// File of the en-passant square, if any; the rank follows from the side to move.
+    ep: Option<File>,
+    /// Plies since the last capture or pawn move, for the fifty-move rule.
+    halfmove: u32,
     /// Zobrist hash.
     hash: u64,
 }
@@ -119,29 +182,13 @@ impl Display for Board {
     #[allow(clippy::missing_inline_in_public_items)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for i in 0_u8..64_u8 {
-            let j = i ^ 56_u8;
-
-            if let (Some(piece), Some(colour)) = (
-                self.data
-                    .piece_from_square(j.try_into().expect("square somehow out of bounds")),
-                self.data
-                    .colour_from_square(j.try_into().expect("square somehow out of bounds")),
-            ) {
-                let c = match piece {
-                    Piece::Pawn => 'P',
-                    Piece::Knight => 'N',
-                    Piece::Bishop => 'B',
-                    Piece::Rook => 'R',
-                    Piece::Queen => 'Q',
-                    Piece::King => 'K',
-                };
-
-                let c = match colour {
-                    Colour::White => c.to_ascii_uppercase(),
-                    Colour::Black => c.to_ascii_lowercase(),
-                };
+            let j = i ^ 0o70_u8;
 
-                write!(f, "{c} ")?;
+            if let Some((colour, piece)) = self
+                .data
+                .piece_on(j.try_into().expect("square somehow out of bounds"))
+            {
+                write!(f, "{} ", piece.to_coloured_char(colour))?;
             } else {
                 write!(f, ". ")?;
             }
@@ -155,20 +202,11 @@ impl Display for Board {
         } else {
             writeln!(f, "Black to move.")?;
         }
-        if self.castle.0 {
-            write!(f, "K")?;
-        }
-        if self.castle.1 {
-            write!(f, "Q")?;
-        }
-        if self.castle.2 {
-            write!(f, "k")?;
-        }
-        if self.castle.3 {
-            write!(f, "q")?;
+        if !self.castle.is_empty() {
+            write!(f, "{}", self.castle)?;
         }
         writeln!(f)?;
-        if let Some(ep) = self.ep {
+        if let Some(ep) = self.ep() {
             writeln!(f, "{ep}")?;
         } else {
             writeln!(f, "-")?;
@@ -185,8 +223,9 @@ impl Board {
     pub const fn new() -> Self {
         Self {
             side: Colour::White,
-            castle: (false, false, false, false),
+            castle: CastleRights::NONE,
             ep: None,
+            halfmove: 0,
             data: BoardData::new(),
             hash: 0,
         }
@@ -204,6 +243,7 @@ impl Board {
 
     /// Check if this board is illegal by seeing if the enemy king is attacked by friendly pieces.
     /// If it is, it implies the move the enemy made left them in check, which is illegal.
+    /// This is one attack lookup, so cheap enough to assert after every move a search makes.
     #[must_use]
     #[inline]
     pub fn illegal(&self) -> bool {
@@ -227,130 +267,450 @@ impl Board {
 
     /// Parse a position in Forsyth-Edwards Notation into a board.
     ///
-    /// # Panics
-    /// Panics if `fen` is not ASCII.
-    #[must_use]
-    pub fn from_fen(fen: &str, zobrist: &Zobrist) -> Option<Self> {
-        let fen = CString::new(fen).expect("FEN is not ASCII");
-        let fen = fen.as_bytes();
-        Self::from_fen_bytes(fen, zobrist)
+    /// Only the piece placement and side to move are required, so EPD records parse too: missing
+    /// castling rights and en-passant fields mean there are none, a missing or non-numeric
+    /// halfmove clock is zero, and the fullmove number and anything after it are ignored.
+    /// Castling rights may also be given as the files of the rooks (`HAha`), as long as the
+    /// king is on its starting square.
+    ///
+    /// # Errors
+    /// Returns a `FenError` if `fen` is malformed or describes an illegal position.
+    pub fn from_fen(fen: &str, zobrist: &Zobrist) -> Result<Self, FenError> {
+        let mut fields = fen.split_ascii_whitespace();
+        let placement = fields.next().ok_or(FenError::MissingField)?;
+        let side = fields.next().ok_or(FenError::MissingField)?;
+        let castling = fields.next().unwrap_or("-");
+        let ep = fields.next().unwrap_or("-");
+
+        let mut b = Self::new();
+        b.halfmove = fields.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+
+        let mut ranks = placement.split('/');
+        let mut pieces = [0_u32; 2];
+        let mut kings = [0_u32; 2];
+        for rank in (0_u8..8).rev() {
+            let rank = Rank::try_from(rank).map_err(|()| FenError::BadPlacement)?;
+            let row = ranks.next().ok_or(FenError::BadPlacement)?;
+            let mut file = 0_u8;
+            for c in row.bytes() {
+                if (b'1'..=b'8').contains(&c) {
+                    file += c - b'0';
+                    if file > 8 {
+                        return Err(FenError::BadPlacement);
+                    }
+                    continue;
+                }
+
+                let (piece, colour) =
+                    Piece::from_coloured_char(c as char).ok_or(FenError::BadPlacement)?;
+
+                let square = Square::from_rank_file(
+                    rank,
+                    File::try_from(file).map_err(|()| FenError::BadPlacement)?,
+                );
+
+                // A side can only have 16 pieces, and exactly one king.
+                pieces[colour as usize] += 1;
+                if piece == Piece::King {
+                    kings[colour as usize] += 1;
+                }
+                if pieces[colour as usize] > 16 || kings[colour as usize] > 1 {
+                    return Err(FenError::IllegalPosition);
+                }
+
+                b.data.add_piece(piece, colour, square, false);
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenError::BadPlacement);
+            }
+        }
+        if ranks.next().is_some() {
+            return Err(FenError::BadPlacement);
+        }
+        // Everything from attack generation on assumes both kings exist.
+        if kings != [1, 1] {
+            return Err(FenError::IllegalPosition);
+        }
+
+        b.side = side
+            .parse()
+            .ok()
+            .and_then(Colour::from_char)
+            .ok_or(FenError::BadSide)?;
+
+        b.castle = b.parse_castling(castling)?;
+
+        let ep = if ep == "-" {
+            None
+        } else {
+            let square = Square::from_str(ep).map_err(|()| FenError::BadEnPassant)?;
+            // The en-passant square is behind a pawn that just double-pushed.
+            let expected = match b.side {
+                Colour::White => Rank::Six,
+                Colour::Black => Rank::Three,
+            };
+            if Rank::from(square) != expected {
+                return Err(FenError::BadEnPassant);
+            }
+            Some(square)
+        };
+
+        b.data.rebuild_attacks();
+        // Like `make`, only keep the en-passant square if it can actually be captured.
+        b.ep = b.capturable_ep(ep, b.side);
+        b.recalculate_hash(zobrist);
+
+        if b.illegal() {
+            return Err(FenError::IllegalPosition);
+        }
+
+        Ok(b)
     }
 
     /// Parse a position in Forsyth-Edwards Notation into a board.
     ///
-    /// # Panics
-    /// Panics when invalid FEN is input.
-    #[must_use]
-    pub fn from_fen_bytes(fen: &[u8], zobrist: &Zobrist) -> Option<Self> {
-        let mut b = Self::new();
-
-        let mut idx = 0_usize;
-        let mut c = fen[idx];
+    /// # Errors
+    /// Returns a `FenError` if `fen` is not ASCII, is malformed, or describes an illegal position.
+    pub fn from_fen_bytes(fen: &[u8], zobrist: &Zobrist) -> Result<Self, FenError> {
+        if !fen.is_ascii() {
+            return Err(FenError::NotAscii);
+        }
+        let fen = std::str::from_utf8(fen).map_err(|_| FenError::NotAscii)?;
+        Self::from_fen(fen, zobrist)
+    }
 
-        for rank in (0..=7).rev() {
-            let mut file = 0;
-            while file <= 7 {
-                if (b'1'..=b'8').contains(&c) {
-                    let length = c - b'0';
-                    let mut i = 0;
-                    while i < length {
-                        file += 1;
-                        i += 1;
+    /// Write the position in Forsyth-Edwards Notation.
+    ///
+    /// The board does not count moves, so the caller supplies the fullmove number. The
+    /// en-passant square is only written when a capture there is possible.
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn to_fen(&self, fullmove: u32) -> String {
+        let mut fen = String::new();
+        for rank in (0_u8..8).rev() {
+            let mut empty = 0;
+            for file in 0_u8..8 {
+                let square =
+                    Square::try_from(rank * 8 + file).expect("square somehow out of bounds");
+                if let Some((colour, piece)) = self.data.piece_on(square) {
+                    if empty > 0 {
+                        fen.push(char::from(b'0' + empty));
+                        empty = 0;
                     }
+                    fen.push(piece.to_coloured_char(colour));
                 } else {
-                    let piece = match c.to_ascii_lowercase() {
-                        b'k' => Piece::King,
-                        b'q' => Piece::Queen,
-                        b'r' => Piece::Rook,
-                        b'b' => Piece::Bishop,
-                        b'n' => Piece::Knight,
-                        b'p' => Piece::Pawn,
-                        _ => return None,
-                    };
-
-                    let colour = if c.is_ascii_uppercase() {
-                        Colour::White
-                    } else {
-                        Colour::Black
-                    };
+                    empty += 1;
+                }
+            }
+            if empty > 0 {
+                fen.push(char::from(b'0' + empty));
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(if self.side == Colour::White { 'w' } else { 'b' });
+        fen.push(' ');
+        fen.push_str(&self.castle.to_string());
+        let ep = self
+            .ep()
+            .map_or_else(|| "-".to_owned(), |ep| ep.to_string());
+        format!("{fen} {ep} {} {fullmove}", self.halfmove)
+    }
+
+    /// Play a line of moves from the starting position, in coordinate notation or SAN.
+    /// Returns the final board and the hashes of every position in the line, starting position included.
+    ///
+    /// # Errors
+    /// Returns a `MoveError` for the first move that cannot be played.
+    pub fn from_moves(zobrist: &Zobrist, moves: &[&str]) -> Result<(Self, Vec<u64>), MoveError> {
+        let mut board = Self::startpos(zobrist);
+        let mut keystack = vec![board.hash];
+        for s in moves {
+            board = board.make(board.parse_move(s)?, zobrist);
+            keystack.push(board.hash);
+        }
+        Ok((board, keystack))
+    }
 
-                    let square =
-                        Square::from_rank_file(rank.try_into().unwrap(), file.try_into().unwrap());
+    /// Play the legal move described by `s`, in coordinate notation or SAN, returning the
+    /// board after it, or `None` if `s` does not describe a legal move.
+    #[must_use]
+    pub fn make_move_str(&self, zobrist: &Zobrist, s: &str) -> Option<Self> {
+        let m = self.parse_move(s).ok()?;
+        Some(self.make(m, zobrist))
+    }
 
-                    b.data.add_piece(piece, colour, square, false);
+    /// Return whether `m` is one of the legal moves of this position.
+    #[must_use]
+    pub fn is_legal(&self, m: Move) -> bool {
+        let mut moves = MoveList::new();
+        self.generate(&mut moves);
+        moves.contains(&m)
+    }
 
-                    file += 1;
-                }
-                idx += 1;
-                c = fen[idx];
+    /// Play `m` if it is legal in this position, returning the board after it, or `None` for
+    /// a move that belongs to another position, such as one from a stale hash table entry.
+    ///
+    /// [`Board::make`] trusts its move; use this for moves that did not come from this
+    /// board's own move generator.
+    #[must_use]
+    pub fn make_checked(&self, m: Move, zobrist: &Zobrist) -> Option<Self> {
+        self.is_legal(m).then(|| self.make(m, zobrist))
+    }
+
+    /// Return whether `m` could be a move of this position: a piece of the side to move stands
+    /// on `m.from` and can reach `m.dest` the way `m.kind` says, with nothing in a slider's way.
+    ///
+    /// Unlike [`Board::is_legal`] this does not generate moves, and so does not look at pins or
+    /// whether the king is left in check; making the move and testing [`Board::illegal`] does
+    /// that. It is the cheap guard for a move from a hash table entry, which a collision may
+    /// have filled from another position.
+    #[must_use]
+    pub fn is_pseudo_legal(&self, m: Move) -> bool {
+        if m.from == m.dest || self.data.colour_from_square(m.from) != Some(self.side) {
+            return false;
+        }
+        let Some(piece) = self.data.piece_from_square(m.from) else {
+            return false;
+        };
+
+        // Captures take an enemy piece other than the king; everything else needs an empty
+        // destination, en passant included.
+        let target = self.data.colour_from_square(m.dest);
+        let capture = matches!(m.kind, MoveType::Capture | MoveType::CapturePromotion);
+        if capture {
+            if target != Some(!self.side)
+                || self.data.piece_from_square(m.dest) == Some(Piece::King)
+            {
+                return false;
             }
-            if rank > 0 {
-                idx += 1;
-                c = fen[idx];
+        } else if target.is_some() {
+            return false;
+        }
+
+        // Only pawns reaching the last rank promote, and they must.
+        let promotion = matches!(m.kind, MoveType::Promotion | MoveType::CapturePromotion);
+        let last_rank = Rank::from(m.dest).is_relative_eighth(self.side);
+        if promotion != (piece == Piece::Pawn && last_rank) {
+            return false;
+        }
+        match m.prom {
+            None if promotion => return false,
+            Some(Piece::Knight | Piece::Bishop | Piece::Rook | Piece::Queen) if promotion => {}
+            Some(_) => return false,
+            None => {}
+        }
+
+        let pawn_capture = || m.from.pawn_attacks(self.side).any(|sq| sq == m.dest);
+        match (piece, m.kind) {
+            (Piece::Pawn, MoveType::Normal | MoveType::Promotion) => {
+                m.from.relative_north(self.side) == Some(m.dest)
+            }
+            (Piece::Pawn, MoveType::DoublePush) => {
+                let Some(middle) = m.from.relative_north(self.side) else {
+                    return false;
+                };
+                Rank::from(m.dest).is_relative_fourth(self.side)
+                    && middle.relative_north(self.side) == Some(m.dest)
+                    && !self.data.has_piece(middle)
+            }
+            (Piece::Pawn, MoveType::Capture | MoveType::CapturePromotion) => pawn_capture(),
+            (Piece::Pawn, MoveType::EnPassant) => self.ep() == Some(m.dest) && pawn_capture(),
+            (Piece::Knight, MoveType::Normal | MoveType::Capture) => {
+                m.from.knight_attacks().any(|square| square == m.dest)
+            }
+            (Piece::King, MoveType::Normal | MoveType::Capture) => {
+                m.from.king_attacks().any(|square| square == m.dest)
             }
+            (Piece::Bishop | Piece::Rook | Piece::Queen, MoveType::Normal | MoveType::Capture) => {
+                self.slider_sees(piece, m.from, m.dest, [m.from; 2], m.from)
+            }
+            (Piece::King, MoveType::Castle) => self.is_pseudo_legal_castle(m),
+            _ => false,
         }
-        idx += 1;
-        c = fen[idx];
-        b.side = match c {
-            b'w' => Colour::White,
-            b'b' => Colour::Black,
-            _ => return None,
+    }
+
+    /// The castling half of [`Board::is_pseudo_legal`]. Castling out of or through check is
+    /// ruled out here too, since testing the board after the move only catches castling into
+    /// check.
+    fn is_pseudo_legal_castle(&self, m: Move) -> bool {
+        let rank = match self.side {
+            Colour::White => Rank::One,
+            Colour::Black => Rank::Eight,
         };
-        idx += 2;
-        c = fen[idx];
-        b.castle = (false, false, false, false);
-        if c == b'-' {
-            idx += 1;
-        } else {
-            if c == b'K' {
-                b.castle.0 = true;
-                idx += 1;
-                c = fen[idx];
-            }
-            if c == b'Q' {
-                b.castle.1 = true;
-                idx += 1;
-                c = fen[idx];
-            }
-            if c == b'k' {
-                b.castle.2 = true;
-                idx += 1;
-                c = fen[idx];
-            }
-            if c == b'q' {
-                b.castle.3 = true;
-                idx += 1;
-            }
-        }
-        idx += 1;
-        c = fen[idx];
-        if c == b'-' {
-            b.ep = None;
-        } else {
-            let file = File::try_from(c - b'a').unwrap();
-            idx += 1;
-            c = fen[idx];
-            let rank = Rank::try_from(c - b'1').unwrap();
-            b.ep = Some(Square::from_rank_file(rank, file));
+        let kingside = self.castle.has(self.side, CastleSide::Kingside);
+        let queenside = self.castle.has(self.side, CastleSide::Queenside);
+        let square = |file| Square::from_rank_file(rank, file);
+        if m.from != square(File::E) || Rank::from(m.dest) != rank || !self.checkers().empty() {
+            return false;
         }
+        // The king crosses two squares, which must be empty and safe.
+        let (right, rook_file, crossed) = match File::from(m.dest) {
+            File::G => (kingside, File::H, [File::F, File::G]),
+            File::C => (queenside, File::A, [File::D, File::C]),
+            _ => return false,
+        };
+        let rook = square(rook_file);
+        right
+            && self.data.piece_from_square(rook) == Some(Piece::Rook)
+            && self.data.colour_from_square(rook) == Some(self.side)
+            && crossed.iter().all(|&file| {
+                let square = square(file);
+                !self.data.has_piece(square) && self.data.attacks_to(square, !self.side).empty()
+            })
+            // Castling long, the rook crosses the b-file as well.
+            && (rook_file == File::H || !self.data.has_piece(square(File::B)))
+    }
 
-        b.recalculate_hash(zobrist);
-        b.data.rebuild_attacks();
+    /// Find the legal move described by `s`, in coordinate notation (`e2e4`, `e7e8q`) or SAN (`Nf3`, `exd5`, `O-O`).
+    ///
+    /// # Errors
+    /// Returns `MoveError::BadNotation` if `s` cannot be parsed, `MoveError::Illegal` if no legal move matches,
+    /// and `MoveError::Ambiguous` if a SAN move matches more than one legal move.
+    pub fn parse_move(&self, s: &str) -> Result<Move, MoveError> {
+        let mut moves = MoveList::new();
+        self.generate(&mut moves);
+
+        let square = |s: Option<&str>| s.and_then(|s| Square::from_str(s).ok());
+        if let (Some(from), Some(dest)) = (square(s.get(..2)), square(s.get(2..4))) {
+            let prom = match s.get(4..).map(str::as_bytes) {
+                Some([]) => None,
+                Some(&[c]) => match Piece::from_coloured_char(c as char) {
+                    Some((
+                        piece @ (Piece::Knight | Piece::Bishop | Piece::Rook | Piece::Queen),
+                        Colour::Black,
+                    )) => Some(piece),
+                    _ => return Err(MoveError::BadNotation),
+                },
+                _ => return Err(MoveError::BadNotation),
+            };
+            return moves
+                .into_iter()
+                .find(|m| m.from == from && m.dest == dest && m.prom == prom)
+                .ok_or(MoveError::Illegal);
+        }
 
-        if b.illegal() {
-            return None;
+        let san = s.trim_end_matches(['+', '#', '!', '?']);
+        let castle_file = match san {
+            // ICS servers send castling in lowercase.
+            "O-O" | "o-o" | "0-0" => Some(File::G),
+            "O-O-O" | "o-o-o" | "0-0-0" => Some(File::C),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            return moves
+                .into_iter()
+                .find(|m| m.kind == MoveType::Castle && File::from(m.dest) == file)
+                .ok_or(MoveError::Illegal);
+        }
+
+        // Pawn moves are written without a letter.
+        let piece_of = |c: u8| Piece::from_char(c as char).filter(|&piece| piece != Piece::Pawn);
+        let mut san = san.as_bytes();
+        let piece = match san.split_first() {
+            Some((&c, rest)) if piece_of(c).is_some() => {
+                san = rest;
+                piece_of(c)
+            }
+            _ => Some(Piece::Pawn),
+        };
+        let prom = match san {
+            [rest @ .., b'=', c] | [rest @ .., c @ (b'N' | b'B' | b'R' | b'Q')] => {
+                san = rest;
+                Some(piece_of(*c).ok_or(MoveError::BadNotation)?)
+            }
+            _ => None,
+        };
+        let [disambiguation @ .., file, rank] = san else {
+            return Err(MoveError::BadNotation);
+        };
+        let dest = std::str::from_utf8(&[*file, *rank])
+            .ok()
+            .and_then(|dest| Square::from_str(dest).ok())
+            .ok_or(MoveError::BadNotation)?;
+        let disambiguation = disambiguation.strip_suffix(b"x").unwrap_or(disambiguation);
+        let (from_file, from_rank) = match disambiguation {
+            [] => (None, None),
+            [f @ b'a'..=b'h'] => (Some(f - b'a'), None),
+            [r @ b'1'..=b'8'] => (None, Some(r - b'1')),
+            [f @ b'a'..=b'h', r @ b'1'..=b'8'] => (Some(f - b'a'), Some(r - b'1')),
+            _ => return Err(MoveError::BadNotation),
+        };
+
+        let mut candidates = moves.into_iter().filter(|m| {
+            m.dest == dest
+                && m.prom == prom
+                && self.piece_from_square(m.from) == piece
+                && from_file.is_none_or(|f| u8::from(File::from(m.from)) == f)
+                && from_rank.is_none_or(|r| u8::from(Rank::from(m.from)) == r)
+        });
+        let m = candidates.next().ok_or(MoveError::Illegal)?;
+        if candidates.next().is_some() {
+            return Err(MoveError::Ambiguous);
+        }
+        Ok(m)
+    }
+
+    /// Check if a pawn of `colour` attacks the en-passant square `ep`.
+    fn has_ep_capturer(&self, ep: Square, colour: Colour) -> bool {
+        !(self.data.attacks_to(ep, colour) & self.data.pawns()).empty()
+    }
+
+    /// Parse the castling field of a FEN, given the pieces already placed.
+    ///
+    /// Every right needs its king and rook still on their starting squares, since move
+    /// generation and `make` assume they are there.
+    fn parse_castling(&self, castling: &str) -> Result<CastleRights, FenError> {
+        let mut rights = CastleRights::NONE;
+        if castling == "-" {
+            return Ok(rights);
+        }
+        for c in castling.bytes() {
+            // Shredder-FEN rook files name the same rights as the usual letters.
+            let (colour, side) = match c {
+                b'K' | b'H' => (Colour::White, CastleSide::Kingside),
+                b'Q' | b'A' => (Colour::White, CastleSide::Queenside),
+                b'k' | b'h' => (Colour::Black, CastleSide::Kingside),
+                b'q' | b'a' => (Colour::Black, CastleSide::Queenside),
+                _ => return Err(FenError::BadCastling),
+            };
+            let rank = if colour == Colour::White {
+                Rank::One
+            } else {
+                Rank::Eight
+            };
+            let rook_file = match side {
+                CastleSide::Kingside => File::H,
+                CastleSide::Queenside => File::A,
+            };
+            let rook = Square::from_rank_file(rank, rook_file);
+            if self.data.king_square(colour) != Square::from_rank_file(rank, File::E)
+                || self.data.piece_on(rook) != Some((colour, Piece::Rook))
+                || !rights.add(colour, side)
+            {
+                return Err(FenError::BadCastling);
+            }
         }
+        Ok(rights)
+    }
 
-        Some(b)
+    /// The file of the en-passant square `ep`, if a pawn of `colour` can capture on it.
+    fn capturable_ep(&self, ep: Option<Square>, colour: Colour) -> Option<File> {
+        ep.filter(|&ep| self.has_ep_capturer(ep, colour))
+            .map(File::from)
     }
 
-    fn set_ep(&mut self, zobrist: &Zobrist, ep: Option<Square>) {
-        if let Some(ep) = self.ep {
-            self.hash ^= zobrist.ep[File::from(ep) as usize];
+    const fn set_ep(&mut self, zobrist: &Zobrist, ep: Option<File>) {
+        if let Some(file) = self.ep {
+            self.hash ^= zobrist.ep[file as usize];
         }
         self.ep = ep;
-        if let Some(ep) = self.ep {
-            self.hash ^= zobrist.ep[File::from(ep) as usize];
+        if let Some(file) = self.ep {
+            self.hash ^= zobrist.ep[file as usize];
         }
     }
 
@@ -363,6 +723,10 @@ impl Board {
     #[allow(clippy::too_many_lines)]
     pub fn make(&self, m: Move, zobrist: &Zobrist) -> Self {
         let mut b = self.clone();
+        // Pawn moves and captures cannot be undone, so they restart the fifty-move count.
+        let zeroing = !matches!(m.kind, MoveType::Normal | MoveType::Castle)
+            || self.piece_from_square(m.from) == Some(Piece::Pawn);
+        b.halfmove = if zeroing { 0 } else { self.halfmove + 1 };
         match m.kind {
             MoveType::Normal => {
                 let piece = b.piece_from_square(m.from).unwrap() as usize;
@@ -376,7 +740,10 @@ impl Board {
                 b.data.move_piece(m.from, m.dest);
                 b.hash ^= zobrist.piece[b.side as usize][piece][m.from.into_inner() as usize]
                     ^ zobrist.piece[b.side as usize][piece][m.dest.into_inner() as usize];
-                b.set_ep(zobrist, m.from.relative_north(b.side));
+                // Only set the en-passant square if an enemy pawn can capture on it, so that
+                // transpositions without a double push hash the same.
+                let ep = m.from.relative_north(b.side);
+                b.set_ep(zobrist, b.capturable_ep(ep, !b.side));
             }
             MoveType::Capture => {
                 let piece_index = b
@@ -394,23 +761,27 @@ impl Board {
                 b.set_ep(zobrist, None);
             }
             MoveType::Castle => {
-                if m.dest > m.from {
-                    let rook_from = m.dest.east().unwrap();
-                    let rook_to = m.dest.west().unwrap();
-                    b.data.move_piece(rook_from, rook_to);
-                    b.hash ^= zobrist.piece[b.side as usize][Piece::Rook as usize]
-                        [rook_from.into_inner() as usize]
-                        ^ zobrist.piece[b.side as usize][Piece::Rook as usize]
-                            [rook_to.into_inner() as usize];
-                } else {
-                    let rook_from = m.dest.west().unwrap().west().unwrap();
-                    let rook_to = m.dest.east().unwrap();
-                    b.data.move_piece(rook_from, rook_to);
-                    b.hash ^= zobrist.piece[b.side as usize][Piece::Rook as usize]
-                        [rook_from.into_inner() as usize]
-                        ^ zobrist.piece[b.side as usize][Piece::Rook as usize]
-                            [rook_to.into_inner() as usize];
-                }
+                // Castling is only generated for a king on its home square with its rook in
+                // the corner; anything else is a move from another position.
+                let home = if b.side == Colour::White { 4 } else { 60 };
+                debug_assert!(
+                    m.from.into_inner() == home
+                        && self.piece_from_square(m.from) == Some(Piece::King),
+                    "castling {m} without a king on its home square"
+                );
+                let (rook_from, rook_to) = Self::castling_rook(m);
+                debug_assert!(
+                    self.data
+                        .piece_index(rook_from)
+                        .is_some_and(|rook| rook.colour() == b.side
+                            && self.data.piece_from_bit(rook) == Piece::Rook),
+                    "castling {m} without a rook on {rook_from}"
+                );
+                b.data.move_piece(rook_from, rook_to);
+                b.hash ^= zobrist.piece[b.side as usize][Piece::Rook as usize]
+                    [rook_from.into_inner() as usize]
+                    ^ zobrist.piece[b.side as usize][Piece::Rook as usize]
+                        [rook_to.into_inner() as usize];
                 b.data.move_piece(m.from, m.dest);
                 b.hash ^= zobrist.piece[b.side as usize][Piece::King as usize]
                     [m.from.into_inner() as usize]
@@ -419,7 +790,7 @@ impl Board {
                 b.set_ep(zobrist, None);
             }
             MoveType::EnPassant => {
-                let target_square = b.ep.unwrap().relative_south(b.side).unwrap();
+                let target_square = b.ep().unwrap().relative_south(b.side).unwrap();
                 let target_piece = b.data.piece_index(target_square).unwrap();
                 b.data.remove_piece(target_piece, true);
                 b.data.move_piece(m.from, m.dest);
@@ -457,58 +828,116 @@ impl Board {
             }
         }
 
-        let a1 = Square::from_rank_file(Rank::One, File::A);
-        let a8 = Square::from_rank_file(Rank::Eight, File::A);
-        let e1 = Square::from_rank_file(Rank::One, File::E);
-        let e8 = Square::from_rank_file(Rank::Eight, File::E);
-        let h1 = Square::from_rank_file(Rank::One, File::H);
-        let h8 = Square::from_rank_file(Rank::Eight, File::H);
-
-        if m.from == e1 {
-            if b.castle.0 {
-                b.castle.0 = false;
-                b.hash ^= zobrist.castling[0];
-            }
-            if b.castle.1 {
-                b.castle.1 = false;
-                b.hash ^= zobrist.castling[1];
-            }
+        let castle = self.castle_after(m);
+        if castle != b.castle {
+            b.hash ^= b.castle.hash_delta(zobrist) ^ castle.hash_delta(zobrist);
+            b.castle = castle;
         }
 
-        if m.from == e8 {
-            if b.castle.2 {
-                b.castle.2 = false;
-                b.hash ^= zobrist.castling[2];
+        b.side = !b.side;
+        b.hash ^= zobrist.side;
+        b
+    }
+
+    /// The squares the rook moves from and to when castling with the king move `m`.
+    fn castling_rook(m: Move) -> (Square, Square) {
+        let (rook_from, rook_to) = if m.dest > m.from {
+            (m.dest.east(), m.dest.west())
+        } else {
+            (m.dest.west().and_then(Square::west), m.dest.east())
+        };
+        (
+            rook_from.expect("castling rook off the board"),
+            rook_to.expect("castling rook off the board"),
+        )
+    }
+
+    /// The castling rights left after `m`.
+    fn castle_after(&self, m: Move) -> CastleRights {
+        // A king leaving its home square loses both rights; anything leaving or landing on a
+        // rook's corner loses that rook's right.
+        let mut castle = self.castle;
+        if castle.is_empty() {
+            return castle;
+        }
+        let corners = [
+            (CastleSide::Kingside, File::H),
+            (CastleSide::Queenside, File::A),
+        ];
+        for (colour, rank) in [(Colour::White, Rank::One), (Colour::Black, Rank::Eight)] {
+            if m.from == Square::from_rank_file(rank, File::E) {
+                castle.remove_colour(colour);
             }
-            if b.castle.3 {
-                b.castle.3 = false;
-                b.hash ^= zobrist.castling[3];
+            for (side, file) in corners {
+                let corner = Square::from_rank_file(rank, file);
+                if m.from == corner || m.dest == corner {
+                    castle.remove(colour, side);
+                }
             }
         }
+        castle
+    }
 
-        if (m.from == h1 || m.dest == h1) && b.castle.0 {
-            b.castle.0 = false;
-            b.hash ^= zobrist.castling[0];
-        }
-
-        if (m.from == a1 || m.dest == a1) && b.castle.1 {
-            b.castle.1 = false;
-            b.hash ^= zobrist.castling[1];
+    /// The hash of the position after the legal move `m`, as `make` would compute it, without
+    /// making the move.
+    ///
+    /// # Panics
+    /// Panics if there is no piece on the source square of `m`, or no piece to capture.
+    #[must_use]
+    pub fn zobrist_after(&self, m: Move, zobrist: &Zobrist) -> u64 {
+        let key = |colour: Colour, piece: Piece, square: Square| {
+            zobrist.piece[colour as usize][piece as usize][square.into_inner() as usize]
+        };
+        let (us, them) = (self.side, !self.side);
+        let piece = self.piece_from_square(m.from).unwrap();
+        let mut hash = self.hash ^ zobrist.side;
+        if let Some(file) = self.ep {
+            hash ^= zobrist.ep[file as usize];
         }
-
-        if (m.from == h8 || m.dest == h8) && b.castle.2 {
-            b.castle.2 = false;
-            b.hash ^= zobrist.castling[2];
+        match m.kind {
+            MoveType::Normal => {
+                hash ^= key(us, piece, m.from) ^ key(us, piece, m.dest);
+            }
+            MoveType::DoublePush => {
+                hash ^= key(us, piece, m.from) ^ key(us, piece, m.dest);
+                // Pawns attack the same squares whether or not this pawn has moved yet.
+                if let Some(file) = self.capturable_ep(m.from.relative_north(us), them) {
+                    hash ^= zobrist.ep[file as usize];
+                }
+            }
+            MoveType::Capture => {
+                let captured = self.piece_from_square(m.dest).unwrap();
+                hash ^=
+                    key(us, piece, m.from) ^ key(us, piece, m.dest) ^ key(them, captured, m.dest);
+            }
+            MoveType::Castle => {
+                let (rook_from, rook_to) = Self::castling_rook(m);
+                hash ^= key(us, Piece::King, m.from)
+                    ^ key(us, Piece::King, m.dest)
+                    ^ key(us, Piece::Rook, rook_from)
+                    ^ key(us, Piece::Rook, rook_to);
+            }
+            MoveType::EnPassant => {
+                let target_square = m.dest.relative_south(us).unwrap();
+                hash ^= key(us, Piece::Pawn, m.from)
+                    ^ key(us, Piece::Pawn, m.dest)
+                    ^ key(them, Piece::Pawn, target_square);
+            }
+            MoveType::Promotion => {
+                hash ^= key(us, Piece::Pawn, m.from) ^ key(us, m.prom.unwrap(), m.dest);
+            }
+            MoveType::CapturePromotion => {
+                let captured = self.piece_from_square(m.dest).unwrap();
+                hash ^= key(us, Piece::Pawn, m.from)
+                    ^ key(us, m.prom.unwrap(), m.dest)
+                    ^ key(them, captured, m.dest);
+            }
         }
-
-        if (m.from == a8 || m.dest == a8) && b.castle.3 {
-            b.castle.3 = false;
-            b.hash ^= zobrist.castling[3];
+        let castle = self.castle_after(m);
+        if castle != self.castle {
+            hash ^= self.castle.hash_delta(zobrist) ^ castle.hash_delta(zobrist);
         }
-
-        b.side = !b.side;
-        b.hash ^= zobrist.side;
-        b
+        hash
     }
 
     fn try_push_move(
@@ -531,6 +960,8 @@ impl Board {
                 return;
             }
         }
+        // No legal position has more than 218 moves, so this would be a movegen bug.
+        debug_assert!(v.len() < v.capacity(), "move list overflow");
         v.push(Move::new(from, dest, kind, promotion_piece));
     }
 
@@ -594,7 +1025,7 @@ impl Board {
 
             match (friendly_blocker, enemy_blocker) {
                 // There are no friendly blockers: skip.
-                (None, _) => continue,
+                (None, _) => {}
                 // There is one friendly blocker: it is pinned.
                 (Some(blocker), None) => {
                     info.pins[blocker.into_inner() as usize] = Some(pinner_king_dir);
@@ -621,7 +1052,9 @@ impl Board {
 
     /// Generate en-passant pawn moves.
     fn generate_pawn_enpassant(&self, v: &mut ArrayVec<[Move; 256]>, pininfo: &PinInfo) {
-        let Some(ep) = self.ep else { return; };
+        let Some(ep) = self.ep() else {
+            return;
+        };
         for capturer in self
             .data
             .attacks_to(ep, self.side)
@@ -667,11 +1100,10 @@ impl Board {
 
     /// Generate moves when in check by a single piece.
     #[allow(clippy::too_many_lines)]
-    fn generate_single_check(&self, v: &mut ArrayVec<[Move; 256]>) {
+    fn generate_single_check(&self, v: &mut ArrayVec<[Move; 256]>, checkers: Bitlist) {
         let king_square = self.data.king_square(self.side);
         let king_square_16x8 = Square16x8::from_square(king_square);
-        let attacker_bit = self.data.attacks_to(king_square, !self.side);
-        let attacker_index = unsafe { attacker_bit.peek_nonzero() };
+        let attacker_index = unsafe { checkers.peek_nonzero() };
         let attacker_piece = self.data.piece_from_bit(attacker_index);
         let attacker_square = self.data.square_of_piece(attacker_index);
         let attacker_direction = attacker_square.direction(king_square);
@@ -728,7 +1160,7 @@ impl Board {
             }
         }
 
-        if let Some(ep) = self.ep {
+        if let Some(ep) = self.ep() {
             if let Some(ep_south) = ep.relative_south(self.side) {
                 if ep_south == attacker_square && attacker_piece == Piece::Pawn {
                     for capturer in self.data.attacks_to(ep, self.side)
@@ -807,13 +1239,14 @@ impl Board {
                 }
             }
 
+            debug_assert!(v.len() < v.capacity(), "move list overflow");
             v.push(Move::new(king_square, square, kind, None));
         }
     }
 
-    fn generate_double_check(&self, v: &mut ArrayVec<[Move; 256]>) {
+    fn generate_double_check(&self, v: &mut ArrayVec<[Move; 256]>, checkers: Bitlist) {
         let king_square = self.data.king_square(self.side);
-        let mut attacker_bits = self.data.attacks_to(king_square, !self.side);
+        let mut attacker_bits = checkers;
         let attacker1_index = attacker_bits.pop().unwrap();
         let attacker1_piece = self.data.piece_from_bit(attacker1_index);
         let attacker1_square = self.data.square_of_piece(attacker1_index);
@@ -861,10 +1294,24 @@ impl Board {
                 }
             }
 
+            debug_assert!(v.len() < v.capacity(), "move list overflow");
             v.push(Move::new(king_square, square, kind, None));
         }
     }
 
+    /// Generate the legal moves out of check, or nothing if the side to move is not in check.
+    pub fn generate_evasions(&self, v: &mut ArrayVec<[Move; 256]>) {
+        self.generate_evasions_from(v, self.checkers());
+    }
+
+    fn generate_evasions_from(&self, v: &mut ArrayVec<[Move; 256]>, checkers: Bitlist) {
+        match checkers.count_ones() {
+            0 => {}
+            1 => self.generate_single_check(v, checkers),
+            _ => self.generate_double_check(v, checkers),
+        }
+    }
+
     pub fn generate_captures(&self, v: &mut ArrayVec<[Move; 256]>) {
         let pininfo = self.discover_pinned_pieces();
 
@@ -924,17 +1371,15 @@ impl Board {
 
     #[allow(clippy::missing_panics_doc, clippy::too_many_lines)]
     pub fn generate_captures_incremental<F: FnMut(Move) -> bool>(&self, mut f: F) {
-        let king_square = self.data.king_square(self.side);
-        let checks = self.data.attacks_to(king_square, !self.side);
+        let checkers = self.checkers();
 
         // special case: being in check.
-        if checks.count_ones() != 0 {
+        if !checkers.empty() {
             let mut v = ArrayVec::new();
-            v.set_len(0);
-            if checks.count_ones() == 1 {
-                self.generate_single_check(&mut v);
-            } else if checks.count_ones() == 2 {
-                self.generate_double_check(&mut v);
+            if checkers.count_ones() == 1 {
+                self.generate_single_check(&mut v, checkers);
+            } else if checkers.count_ones() == 2 {
+                self.generate_double_check(&mut v, checkers);
             }
 
             for m in v {
@@ -1109,6 +1554,102 @@ impl Board {
         }
     }
 
+    /// Generate the legal moves that neither capture nor promote.
+    pub fn generate_quiets(&self, v: &mut ArrayVec<[Move; 256]>) {
+        self.generate(v);
+        v.retain(|m| !m.is_capture() && m.prom.is_none());
+    }
+
+    /// Check if a slider of type `piece` on `from` would attack `target`, with the squares in
+    /// `vacated` treated as empty and `filled` treated as occupied.
+    fn slider_sees(
+        &self,
+        piece: Piece,
+        from: Square,
+        target: Square,
+        vacated: [Square; 2],
+        filled: Square,
+    ) -> bool {
+        let Some(dir) = from.direction(target) else {
+            return false;
+        };
+        if !dir.valid_for_slider(piece) {
+            return false;
+        }
+        let mut square = from;
+        while let Some(next) = square.travel(dir) {
+            if next == target {
+                return true;
+            }
+            if next == filled || (self.data.has_piece(next) && !vacated.contains(&next)) {
+                return false;
+            }
+            square = next;
+        }
+        false
+    }
+
+    /// Check if the legal move `m` would put the opponent in check, without making it.
+    #[must_use]
+    pub fn gives_check(&self, m: Move) -> bool {
+        let king = self.data.king_square(!self.side);
+        let Some(piece) = self.data.piece_from_square(m.from) else {
+            return false;
+        };
+
+        // Squares the move empties, besides the one the piece leaves.
+        let also_vacated = match m.kind {
+            MoveType::EnPassant => Square::from_rank_file(Rank::from(m.from), File::from(m.dest)),
+            MoveType::Castle if File::from(m.dest) == File::G => {
+                Square::from_rank_file(Rank::from(m.from), File::H)
+            }
+            MoveType::Castle => Square::from_rank_file(Rank::from(m.from), File::A),
+            _ => m.from,
+        };
+        let vacated = [m.from, also_vacated];
+
+        // Direct check by the moved piece, or by the rook when castling.
+        let (checker, square) = match m.kind {
+            MoveType::Castle if File::from(m.dest) == File::G => (
+                Piece::Rook,
+                Square::from_rank_file(Rank::from(m.from), File::F),
+            ),
+            MoveType::Castle => (
+                Piece::Rook,
+                Square::from_rank_file(Rank::from(m.from), File::D),
+            ),
+            _ => (m.prom.unwrap_or(piece), m.dest),
+        };
+        let direct = match checker {
+            Piece::Pawn => square.pawn_attacks(self.side).any(|sq| sq == king),
+            Piece::Knight => square.knight_attacks().any(|sq| sq == king),
+            Piece::King => false,
+            Piece::Bishop | Piece::Rook | Piece::Queen => {
+                self.slider_sees(checker, square, king, vacated, m.dest)
+            }
+        };
+        if direct {
+            return true;
+        }
+
+        // Discovered check by a slider that could see one of the vacated squares.
+        let sliders = self.data.bishops() | self.data.rooks() | self.data.queens();
+        let behind =
+            self.data.attacks_to(m.from, self.side) | self.data.attacks_to(also_vacated, self.side);
+        (behind & sliders).into_iter().any(|slider| {
+            let from = self.data.square_of_piece(slider);
+            let piece = self.data.piece_from_bit(slider);
+            from != m.from && self.slider_sees(piece, from, king, vacated, m.dest)
+        })
+    }
+
+    /// Generate the legal quiet moves that give check: the moves of `generate_quiets` that
+    /// `gives_check` accepts, which quiescence searches besides captures.
+    pub fn generate_checks(&self, v: &mut ArrayVec<[Move; 256]>) {
+        self.generate_quiets(v);
+        v.retain(|m| self.gives_check(*m));
+    }
+
     /// Generate a vector of moves on the board.
     ///
     /// # Panics
@@ -1117,13 +1658,10 @@ impl Board {
     pub fn generate(&self, v: &mut ArrayVec<[Move; 256]>) {
         // Unless something has gone very badly wrong we have to have a king.
         let king_square = self.data.king_square(self.side);
-        let checks = self.data.attacks_to(king_square, !self.side);
+        let checkers = self.checkers();
 
-        if checks.count_ones() == 1 {
-            return self.generate_single_check(v);
-        }
-        if checks.count_ones() == 2 {
-            return self.generate_double_check(v);
+        if !checkers.empty() {
+            return self.generate_evasions_from(v, checkers);
         }
 
         let pininfo = self.discover_pinned_pieces();
@@ -1165,12 +1703,10 @@ impl Board {
         }
 
         // Kingside castling.
-        if (self.side == Colour::White && self.castle.0)
-            || (self.side == Colour::Black && self.castle.2)
-        {
+        if self.castle.has(self.side, CastleSide::Kingside) {
             let east1 = king_square.east().unwrap();
             let east2 = east1.east().unwrap();
-            if self.data.attacks_to(king_square, !self.side).empty()
+            if checkers.empty()
                 && !self.data.has_piece(east1)
                 && self.data.attacks_to(east1, !self.side).empty()
                 && !self.data.has_piece(east2)
@@ -1181,13 +1717,11 @@ impl Board {
         }
 
         // Queenside castling.
-        if (self.side == Colour::White && self.castle.1)
-            || (self.side == Colour::Black && self.castle.3)
-        {
+        if self.castle.has(self.side, CastleSide::Queenside) {
             let west1 = king_square.west().unwrap();
             let west2 = west1.west().unwrap();
             let west3 = west2.west().unwrap();
-            if self.data.attacks_to(king_square, !self.side).empty()
+            if checkers.empty()
                 && !self.data.has_piece(west1)
                 && self.data.attacks_to(west1, !self.side).empty()
                 && !self.data.has_piece(west2)
@@ -1199,46 +1733,181 @@ impl Board {
         }
     }
 
+    /// The castling rights that remain.
     #[must_use]
-    pub const fn kings(&self) -> Bitlist {
-        self.data.kings()
+    pub const fn castle_rights(&self) -> CastleRights {
+        self.castle
     }
 
-    /// Return a bitlist of all pieces.
+    /// Return a bitlist of all pawns.
     #[must_use]
-    pub const fn pieces(&self) -> Bitlist {
-        self.data.pieces()
+    pub const fn pawns(&self) -> Bitlist {
+        self.data.pawns()
     }
 
-    /// Given a piece index, return its piece type.
+    /// Return a bitlist of all knights.
     #[must_use]
-    pub const fn piece_from_bit(&self, bit: PieceIndex) -> Piece {
-        self.data.piece_from_bit(bit)
+    pub const fn knights(&self) -> Bitlist {
+        self.data.knights()
     }
 
+    /// Return a bitlist of all bishops.
     #[must_use]
-    pub fn piece_from_square(&self, square: Square) -> Option<Piece> {
-        self.data.piece_from_square(square)
+    pub const fn bishops(&self) -> Bitlist {
+        self.data.bishops()
     }
 
+    /// Return a bitlist of all rooks.
     #[must_use]
-    pub fn square_of_piece(&self, bit: PieceIndex) -> Square {
-        self.data.square_of_piece(bit)
+    pub const fn rooks(&self) -> Bitlist {
+        self.data.rooks()
     }
 
+    /// Return a bitlist of all queens.
     #[must_use]
-    pub const fn ep(&self) -> Option<Square> {
-        self.ep
+    pub const fn queens(&self) -> Bitlist {
+        self.data.queens()
     }
 
     #[must_use]
-    pub const fn side(&self) -> Colour {
-        self.side
+    pub const fn kings(&self) -> Bitlist {
+        self.data.kings()
     }
 
+    /// Return the square of the king of a given colour.
     #[must_use]
-    pub const fn hash(&self) -> u64 {
-        self.hash
+    pub fn king_square(&self, colour: Colour) -> Square {
+        self.data.king_square(colour)
+    }
+
+    /// Return a bitlist of all pieces of a given colour.
+    #[must_use]
+    pub const fn pieces_of_colour(&self, colour: Colour) -> Bitlist {
+        self.data.pieces_of_colour(colour)
+    }
+
+    /// Return a bitlist of all pieces.
+    #[must_use]
+    pub const fn pieces(&self) -> Bitlist {
+        self.data.pieces()
+    }
+
+    /// Given a piece index, return its piece type.
+    #[must_use]
+    pub const fn piece_from_bit(&self, bit: PieceIndex) -> Piece {
+        self.data.piece_from_bit(bit)
+    }
+
+    #[must_use]
+    pub fn piece_from_square(&self, square: Square) -> Option<Piece> {
+        self.data.piece_from_square(square)
+    }
+
+    /// Given a square, return the colour and type of the piece on it, if any.
+    #[must_use]
+    pub fn piece_on(&self, square: Square) -> Option<(Colour, Piece)> {
+        self.data.piece_on(square)
+    }
+
+    #[must_use]
+    pub fn square_of_piece(&self, bit: PieceIndex) -> Square {
+        self.data.square_of_piece(bit)
+    }
+
+    /// The pieces on the board as an array indexed by rank and then file, so `[0][0]` is a1
+    /// and `[7][0]` is a8.
+    #[must_use]
+    pub fn to_array(&self) -> [[Option<(Colour, Piece)>; 8]; 8] {
+        let mut array = [[None; 8]; 8];
+        for (square, slot) in (0_u8..64).zip(array.iter_mut().flatten()) {
+            // SAFETY: square is always in bounds.
+            let square = unsafe { Square::from_u8_unchecked(square) };
+            *slot = self.data.piece_on(square);
+        }
+        array
+    }
+
+    /// Copy the position and the features derived from it into plain arrays.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn snapshot(&self) -> BoardSnapshot {
+        let pininfo = self.discover_pinned_pieces();
+        let mut pieces = [None; 64];
+        let mut attacks = [[0; 64]; 2];
+        let mut pinned = 0;
+        for square in 0_u8..64 {
+            let index = usize::from(square);
+            // SAFETY: square is always in bounds.
+            let square = unsafe { Square::from_u8_unchecked(square) };
+            pieces[index] = self.data.piece_on(square);
+            for colour in Colour::BOTH {
+                // At most 16 pieces can attack a square, so the counts fit.
+                attacks[usize::from(colour)][index] =
+                    self.data.attacks_to(square, colour).count_ones() as u8;
+            }
+            if let Some(piece) = self.data.piece_index(square) {
+                if pininfo.pins[piece.into_inner() as usize].is_some() {
+                    pinned |= 1 << index;
+                }
+            }
+        }
+        BoardSnapshot {
+            pieces,
+            side: self.side,
+            attacks,
+            pinned,
+            checkers: self.check_count() as u8,
+            in_check: self.in_check(),
+            hash: self.hash,
+        }
+    }
+
+    #[must_use]
+    pub fn ep(&self) -> Option<Square> {
+        let rank = match self.side {
+            Colour::White => Rank::Six,
+            Colour::Black => Rank::Three,
+        };
+        self.ep.map(|file| Square::from_rank_file(rank, file))
+    }
+
+    /// Plies since the last capture or pawn move.
+    #[must_use]
+    pub const fn halfmove_clock(&self) -> u32 {
+        self.halfmove
+    }
+
+    /// The game phase, from 24 with all pieces on the board down to 0 with only kings and pawns.
+    ///
+    /// Knights and bishops count 1, rooks 2 and queens 4. Promotions can take the sum past the
+    /// starting material, so it is capped at 24.
+    #[must_use]
+    pub fn phase(&self) -> u8 {
+        self.data.phase().min(24)
+    }
+
+    #[must_use]
+    pub const fn side(&self) -> Colour {
+        self.side
+    }
+
+    #[must_use]
+    pub const fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Check if two boards hold the same position: piece placement, side to move, castling rights and en-passant square.
+    /// Unlike comparing hashes, this cannot be fooled by a hash collision.
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn same_position(&self, other: &Self) -> bool {
+        if self.side != other.side || self.castle != other.castle || self.ep != other.ep {
+            return false;
+        }
+        (0_u8..64).all(|square| {
+            let square = Square::try_from(square).expect("square somehow out of bounds");
+            self.data.piece_on(square) == other.data.piece_on(square)
+        })
     }
 
     pub fn recalculate_hash(&mut self, zobrist: &Zobrist) {
@@ -1250,41 +1919,111 @@ impl Board {
             hash ^= zobrist.piece[side][piece][square];
         }
 
-        if let Some(ep) = self.ep {
-            hash ^= zobrist.ep[Rank::from(ep) as usize];
+        // An en-passant square nobody can capture on does not change the position.
+        if let Some(file) = self.capturable_ep(self.ep(), self.side) {
+            hash ^= zobrist.ep[file as usize];
         }
 
-        if self.castle.0 {
-            hash ^= zobrist.castling[0];
-        }
-        if self.castle.1 {
-            hash ^= zobrist.castling[1];
-        }
-        if self.castle.2 {
-            hash ^= zobrist.castling[2];
-        }
-        if self.castle.3 {
-            hash ^= zobrist.castling[3];
-        }
+        hash ^= self.castle.hash_delta(zobrist);
         if self.side == Colour::Black {
             hash ^= zobrist.side;
         }
         self.hash = hash;
     }
 
+    /// Count the squares each piece type of `colour` attacks, indexed by `Piece`.
+    /// Squares occupied by `colour`'s own pieces do not count, and each piece counts separately,
+    /// so two knights attacking the same square count it twice. Pawns count their captures, not pushes.
+    #[must_use]
+    pub fn mobility(&self, colour: Colour) -> [u32; 6] {
+        let kinds = [
+            self.data.pawns(),
+            self.data.knights(),
+            self.data.bishops(),
+            self.data.rooks(),
+            self.data.queens(),
+            self.data.kings(),
+        ];
+        let mut mobility = [0; 6];
+        for square in 0_u8..64 {
+            // SAFETY: square is always in bounds.
+            let square = unsafe { Square::from_u8_unchecked(square) };
+            if self.data.colour_from_square(square) == Some(colour) {
+                continue;
+            }
+            let attackers = self.data.attacks_to(square, colour);
+            for (count, kind) in mobility.iter_mut().zip(kinds) {
+                *count += (attackers & kind).count_ones();
+            }
+        }
+        mobility
+    }
+
+    /// Return the squares the piece on `square` attacks, one bit per square with a1 lowest,
+    /// or none for an empty square. Squares of either colour's pieces count, as for attacks to a
+    /// square; a pawn attacks where it captures, not where it pushes.
+    #[must_use]
+    pub fn attacks_from(&self, square: Square) -> u64 {
+        self.data.attacks_from(square)
+    }
+
     #[must_use]
     pub fn in_check(&self) -> bool {
-        !self
-            .data
+        !self.checkers().empty()
+    }
+
+    /// Check if the side to move has any legal move.
+    fn has_legal_move(&self) -> bool {
+        let mut moves = MoveList::new();
+        self.generate(&mut moves);
+        !moves.is_empty()
+    }
+
+    /// Check if the side to move is checkmated.
+    #[must_use]
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check() && !self.has_legal_move()
+    }
+
+    /// Check if the side to move is stalemated.
+    #[must_use]
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check() && !self.has_legal_move()
+    }
+
+    /// Check if neither side has the material to checkmate: bare kings, or a lone knight or
+    /// bishop against a bare king.
+    #[must_use]
+    pub fn insufficient_material(&self) -> bool {
+        let heavy = self.data.pawns() | self.data.rooks() | self.data.queens();
+        let minors = self.data.knights() | self.data.bishops();
+        heavy.empty() && minors.count_ones() <= 1
+    }
+
+    /// Check if the legal move `m` checkmates the opponent.
+    #[must_use]
+    pub fn gives_checkmate(&self, m: Move, zobrist: &Zobrist) -> bool {
+        self.gives_check(m) && self.make(m, zobrist).is_checkmate()
+    }
+
+    /// Return a bitlist of the enemy pieces giving check to the side to move.
+    #[must_use]
+    pub fn checkers(&self) -> Bitlist {
+        self.data
             .attacks_to(self.data.king_square(self.side), !self.side)
-            .empty()
+    }
+
+    /// Return the number of enemy pieces giving check: 0, 1 or 2.
+    #[must_use]
+    pub fn check_count(&self) -> u32 {
+        self.checkers().count_ones()
     }
 
     #[must_use]
     pub fn make_null(&self, zobrist: &Zobrist) -> Self {
         let mut board = self.clone();
         board.side = !board.side;
-        board.ep = None;
+        board.set_ep(zobrist, None);
         board.hash ^= zobrist.side;
         board
     }
@@ -1292,28 +2031,15 @@ impl Board {
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashMap;
     use std::str::FromStr;
 
-    use tinyvec::ArrayVec;
+    use rand::{prelude::StdRng, Rng, SeedableRng};
 
-    use crate::{Board, Move, Square, Zobrist};
-
-    // Helper mostly copied from main engine to convert notated moves into real moves
-    fn make_move(board: &Board, zobrist: &Zobrist, move_str: &str) -> Board {
-        let (from_str, dest_str) = move_str.split_at(2);
-        let from = Square::from_str(from_str).unwrap();
-        let dest = Square::from_str(dest_str).unwrap();
-        let moves: [Move; 256] = [Move::default(); 256];
-        let mut moves = ArrayVec::from(moves);
-        moves.set_len(0);
-        board.generate(&mut moves);
-        for m in moves {
-            if m.from == from && m.dest == dest {
-                return board.make(m, zobrist);
-            }
-        }
-        unreachable!("Should never hit this under testing conditions");
-    }
+    use crate::{
+        Board, CastleSide, Colour, FenError, Move, MoveError, MoveList, MoveType, Piece, Square,
+        Zobrist, BENCH_FENS,
+    };
 
     // Helper to take a board and compute the hash freshly
     fn fresh_hash(board: &Board, zobrist: &Zobrist) -> u64 {
@@ -1337,7 +2063,7 @@ mod test {
         let moves = ["a1b1", "a7a6", "b1a1", "a6b6", "a1b1", "b6a6"];
         // Make each move
         for (i, &m) in moves.iter().enumerate() {
-            board = make_move(&board, &zobrist, m);
+            board = board.make_move_str(&zobrist, m).unwrap();
             assert_eq!(
                 board.hash,
                 fresh_hash(&board, &zobrist),
@@ -1355,13 +2081,13 @@ mod test {
         // This hash will always be the same between incremental and non-incremental because it's been computed directly
         let initial_hash = board.hash;
         // Now make the test move
-        board = make_move(&board, &zobrist, "a1b1");
+        board = board.make_move_str(&zobrist, "a1b1").unwrap();
         // Allows us to flip side back without making a move
         board = board.make_null(&zobrist);
         // Option for dev to test that it's the same between both incremental and non
         //assert_eq!(board.hash, fresh_hash(&board, &zobrist), "Made move differs between incremental and fresh");
         // Unmake the move
-        board = make_move(&board, &zobrist, "b1a1");
+        board = board.make_move_str(&zobrist, "b1a1").unwrap();
         // Unmake the side swap hash break
         board = board.make_null(&zobrist);
         // Check that it's the same hash
@@ -1376,6 +2102,1056 @@ mod test {
             "Freshly computed hash differs between original and unmade"
         );
     }
+
+    // Test playing an opening line from the starting position
+    #[test]
+    fn from_moves() {
+        let zobrist = Zobrist::new();
+        let ruy_lopez = Board::from_fen(
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+            &zobrist,
+        )
+        .unwrap();
+        let (board, keystack) =
+            Board::from_moves(&zobrist, &["e4", "e5", "Nf3", "Nc6", "Bb5"]).unwrap();
+        assert!(board.same_position(&ruy_lopez));
+        assert_eq!(keystack.len(), 6);
+        assert_eq!(keystack[0], Board::startpos(&zobrist).hash());
+        assert_eq!(keystack[5], ruy_lopez.hash());
+
+        // Coordinate notation and SAN can be mixed.
+        let (board, _) =
+            Board::from_moves(&zobrist, &["e2e4", "e5", "g1f3", "Nc6", "f1b5"]).unwrap();
+        assert!(board.same_position(&ruy_lopez));
+
+        assert_eq!(
+            Board::from_moves(&zobrist, &["e4", "e4"]).err(),
+            Some(MoveError::Illegal)
+        );
+        assert_eq!(
+            Board::from_moves(&zobrist, &["e4", "pawn"]).err(),
+            Some(MoveError::BadNotation)
+        );
+    }
+
+    // Test parsing SAN moves that need castling, promotion, or disambiguation
+    #[test]
+    fn parse_san() {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen("r3k2r/1P6/8/8/8/8/8/R3K1NR w KQkq - 0 1", &zobrist).unwrap();
+        let parse = |s| board.parse_move(s).map(|m| m.to_string());
+        assert_eq!(parse("O-O-O"), Ok("e1c1".to_string()));
+        assert_eq!(parse("0-0").err(), Some(MoveError::Illegal));
+        assert_eq!(parse("b8=Q"), Ok("b7b8q".to_string()));
+        assert_eq!(parse("bxa8N+"), Ok("b7a8n".to_string()));
+        assert_eq!(parse("b7b8r"), Ok("b7b8r".to_string()));
+        assert_eq!(parse("b7b8k").err(), Some(MoveError::BadNotation));
+        assert_eq!(parse("b7b8Q").err(), Some(MoveError::BadNotation));
+        assert_eq!(parse("b8").err(), Some(MoveError::Illegal));
+        assert_eq!(parse("Rd1"), Ok("a1d1".to_string()));
+        assert_eq!(parse("Rf1").err(), Some(MoveError::Illegal));
+        assert_eq!(parse("Nf3"), Ok("g1f3".to_string()));
+
+        let board = Board::from_fen("4k3/8/8/8/R7/8/4K3/R6R w - - 0 1", &zobrist).unwrap();
+        let parse = |s| board.parse_move(s).map(|m| m.to_string());
+        assert_eq!(parse("Rd1").err(), Some(MoveError::Ambiguous));
+        assert_eq!(parse("Rhd1"), Ok("h1d1".to_string()));
+        assert_eq!(parse("Rbd1").err(), Some(MoveError::Illegal));
+        assert_eq!(parse("Ra2").err(), Some(MoveError::Ambiguous));
+        assert_eq!(parse("R4a2"), Ok("a4a2".to_string()));
+        assert_eq!(parse("Ra1d1"), Ok("a1d1".to_string()));
+        assert_eq!(parse("Rd9").err(), Some(MoveError::BadNotation));
+    }
+
+    // Test every spelling of castling, for either side to move
+    #[test]
+    fn parse_castling() {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &zobrist).unwrap();
+        let parse = |s| board.parse_move(s).map(|m| m.to_string());
+        for s in ["O-O", "o-o", "0-0", "O-O+"] {
+            assert_eq!(parse(s), Ok("e1g1".to_string()));
+        }
+        for s in ["O-O-O", "o-o-o", "0-0-0"] {
+            assert_eq!(parse(s), Ok("e1c1".to_string()));
+        }
+        assert_eq!(parse("o-o-o-o").err(), Some(MoveError::BadNotation));
+
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", &zobrist).unwrap();
+        let parse = |s| board.parse_move(s).map(|m| m.to_string());
+        assert_eq!(parse("o-o"), Ok("e8g8".to_string()));
+        assert_eq!(parse("0-0-0"), Ok("e8c8".to_string()));
+
+        // Castling is illegal without the right, through an attacked square, or out of check.
+        for fen in [
+            "r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1",
+            "r3k2r/8/8/8/8/8/3r1r2/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1",
+        ] {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            for s in ["O-O", "o-o", "0-0", "O-O-O", "o-o-o", "0-0-0"] {
+                assert_eq!(
+                    board.parse_move(s).err(),
+                    Some(MoveError::Illegal),
+                    "{fen} {s}"
+                );
+            }
+        }
+    }
+
+    // Test that a piece attacks along its rays as far as the first piece, whatever its colour
+    #[test]
+    fn attacks_from() {
+        let zobrist = Zobrist::new();
+        let squares = |names: &[&str]| {
+            names.iter().fold(0_u64, |mask, name| {
+                mask | 1 << name.parse::<Square>().unwrap().into_inner()
+            })
+        };
+        let sq = |name: &str| name.parse::<Square>().unwrap();
+
+        // A rook on an open file, blocked on its rank by its own king and a black knight.
+        let board = Board::from_fen("7k/8/8/8/1n1R2K1/8/8/8 w - - 0 1", &zobrist).unwrap();
+        let file = ["d1", "d2", "d3", "d5", "d6", "d7", "d8"];
+        let rank = ["b4", "c4", "e4", "f4", "g4"];
+        assert_eq!(
+            board.attacks_from(sq("d4")),
+            squares(&file) | squares(&rank)
+        );
+        assert_eq!(board.attacks_from(sq("d4")).count_ones(), 12);
+        assert_eq!(board.attacks_from(sq("e4")), 0);
+
+        // Pawns attack diagonally forwards only, and knights jump.
+        let board = Board::startpos(&zobrist);
+        assert_eq!(board.attacks_from(sq("e2")), squares(&["d3", "f3"]));
+        assert_eq!(board.attacks_from(sq("e7")), squares(&["d6", "f6"]));
+        assert_eq!(board.attacks_from(sq("g1")), squares(&["e2", "f3", "h3"]));
+    }
+
+    // Test mobility counts against hand-counted positions
+    #[test]
+    fn mobility() {
+        let zobrist = Zobrist::new();
+        let startpos = Board::startpos(&zobrist);
+        // Knights reach a3, c3, f3 and h3; every other piece is blocked in, and pawns attack rank 3.
+        assert_eq!(startpos.mobility(Colour::White), [14, 4, 0, 0, 0, 0]);
+        assert_eq!(startpos.mobility(Colour::Black), [14, 4, 0, 0, 0, 0]);
+
+        // A centralised knight and a rook on an open board, against a lone king.
+        let board = Board::from_fen("7k/8/8/3N4/8/8/8/R3K3 w - - 0 1", &zobrist).unwrap();
+        // The rook sees the a-file and b1-d1; the king sees d1, f1 and d2-f2.
+        assert_eq!(board.mobility(Colour::White), [0, 8, 0, 10, 0, 5]);
+        assert_eq!(board.mobility(Colour::Black), [0, 0, 0, 0, 0, 3]);
+
+        // Own pieces are not counted, but enemy pieces are.
+        let board = Board::from_fen("4k3/8/8/8/8/2p5/1P6/BQ2K3 w - - 0 1", &zobrist).unwrap();
+        // The bishop is blocked by its own pawn; the queen sees c1, d1, a2 and the c2-h7 diagonal.
+        // The pawn attacks a3 and c3.
+        assert_eq!(board.mobility(Colour::White)[..5], [2, 0, 0, 0, 9]);
+    }
+
+    // Test the position with the most legal moves known fits in a move list
+    #[test]
+    fn max_moves() {
+        let zobrist = Zobrist::new();
+        let fen = "R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        assert_eq!(moves.len(), 218);
+    }
+
+    // Compare gives_check against making each move, down to `depth` plies
+    fn check_gives_check(board: &Board, zobrist: &Zobrist, depth: u32) -> usize {
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        let mut checks = 0;
+        for m in moves {
+            let child = board.make(m, zobrist);
+            assert_eq!(board.gives_check(m), child.in_check(), "{board} {m}");
+            checks += usize::from(child.in_check());
+            if depth > 1 {
+                checks += check_gives_check(&child, zobrist, depth - 1);
+            }
+        }
+        checks
+    }
+
+    // Check zobrist_after against the hash of the made move for every move in a perft tree
+    fn check_zobrist_after(board: &Board, zobrist: &Zobrist, depth: u32) {
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        for m in moves {
+            let child = board.make(m, zobrist);
+            assert_eq!(board.zobrist_after(m, zobrist), child.hash(), "{board} {m}");
+            if depth > 1 {
+                check_zobrist_after(&child, zobrist, depth - 1);
+            }
+        }
+    }
+
+    // Test that zobrist_after agrees with make, through castling, en passant and promotions
+    #[test]
+    fn zobrist_after() {
+        let zobrist = Zobrist::new();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            check_zobrist_after(&board, &zobrist, 3);
+        }
+    }
+
+    // Test the checking pieces are found, for no, single and double check
+    #[test]
+    fn checkers() {
+        let zobrist = Zobrist::new();
+        let startpos = Board::startpos(&zobrist);
+        assert!(startpos.checkers().empty());
+        assert_eq!(startpos.check_count(), 0);
+
+        let board = Board::from_fen("4k3/8/8/8/8/3n4/8/4K3 w - - 0 1", &zobrist).unwrap();
+        assert_eq!(board.check_count(), 1);
+
+        // The bishop on a1 and the rook on h1 both give check.
+        let board = Board::from_fen("7k/8/8/8/8/8/K7/B6R b - - 0 1", &zobrist).unwrap();
+        let checkers = board.checkers();
+        assert_eq!(board.check_count(), 2);
+        let mut squares: Vec<String> = checkers
+            .into_iter()
+            .map(|bit| board.square_of_piece(bit).to_string())
+            .collect();
+        squares.sort();
+        assert_eq!(squares, ["a1", "h1"]);
+        assert!(checkers.contains(board.pieces_of_colour(Colour::White) & board.rooks()));
+    }
+
+    // Walk a perft tree, checking that evasions are exactly the legal moves in check and nothing
+    // otherwise, and count its leaves
+    fn evasion_perft(board: &Board, zobrist: &Zobrist, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        let mut evasions = MoveList::new();
+        board.generate_evasions(&mut evasions);
+        if board.in_check() {
+            assert!(evasions == moves, "{board}");
+        } else {
+            assert!(evasions.is_empty(), "{board}");
+        }
+        moves
+            .into_iter()
+            .map(|m| evasion_perft(&board.make(m, zobrist), zobrist, depth - 1))
+            .sum()
+    }
+
+    // Test that routing in-check nodes through generate_evasions keeps perft counts
+    #[test]
+    fn generate_evasions() {
+        let zobrist = Zobrist::new();
+        let positions = [
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                97862,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 2812),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                9467,
+            ),
+        ];
+        for (fen, nodes) in positions {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert_eq!(evasion_perft(&board, &zobrist, 3), nodes, "{fen}");
+        }
+    }
+
+    // Test that gives_check agrees with making the move
+    #[test]
+    fn gives_check() {
+        let zobrist = Zobrist::new();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert!(check_gives_check(&board, &zobrist, 3) > 0);
+        }
+
+        // Castling with check, and a discovered check through an en-passant capture.
+        let board = Board::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1", &zobrist).unwrap();
+        assert_eq!(check_gives_check(&board, &zobrist, 1), 3);
+        let board = Board::from_fen("8/8/8/1k1pP2R/8/8/8/4K3 w - d6 0 1", &zobrist).unwrap();
+        assert_eq!(
+            board.parse_move("e5d6").map(|m| board.gives_check(m)),
+            Ok(true)
+        );
+    }
+
+    // Check the quiet checks against making every quiet move, and count them
+    fn checks_perft(board: &Board, zobrist: &Zobrist, depth: u32) -> usize {
+        let mut quiets = MoveList::new();
+        board.generate_quiets(&mut quiets);
+        let mut checks = MoveList::new();
+        board.generate_checks(&mut checks);
+        let expected: MoveList = quiets
+            .iter()
+            .copied()
+            .filter(|&m| board.make(m, zobrist).in_check())
+            .collect();
+        assert!(checks == expected, "{board}");
+
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        let mut count = checks.len();
+        if depth > 1 {
+            for m in moves {
+                count += checks_perft(&board.make(m, zobrist), zobrist, depth - 1);
+            }
+        }
+        count
+    }
+
+    // Test that generate_checks finds exactly the quiet moves that give check
+    #[test]
+    fn generate_checks() {
+        let zobrist = Zobrist::new();
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert!(checks_perft(&board, &zobrist, 3) > 0, "{fen}");
+        }
+
+        // Castling with check is quiet; a checking capture is not.
+        let board = Board::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1", &zobrist).unwrap();
+        let mut checks = MoveList::new();
+        board.generate_checks(&mut checks);
+        assert_eq!(checks.len(), 3);
+        let board = Board::from_fen("4k3/8/8/8/8/8/4p3/4R2K w - - 0 1", &zobrist).unwrap();
+        assert_eq!(
+            board.parse_move("e1e2").map(|m| board.gives_check(m)),
+            Ok(true)
+        );
+        let mut checks = MoveList::new();
+        board.generate_checks(&mut checks);
+        assert!(checks.is_empty());
+    }
+
+    // Test that transposing move orders reach the same position
+    #[test]
+    fn same_position() {
+        let zobrist = Zobrist::new();
+        let startpos = Board::startpos(&zobrist);
+
+        let mut a = startpos.clone();
+        for m in ["g1f3", "g8f6", "b1c3"] {
+            a = a.make_move_str(&zobrist, m).unwrap();
+        }
+        let mut b = startpos.clone();
+        for m in ["b1c3", "g8f6", "g1f3"] {
+            b = b.make_move_str(&zobrist, m).unwrap();
+        }
+        assert!(a.same_position(&b));
+
+        // Knight shuffles return to the start position, but a move later.
+        let mut c = startpos.clone();
+        for m in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            c = c.make_move_str(&zobrist, m).unwrap();
+        }
+        assert!(c.same_position(&startpos));
+        let late = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4 3";
+        let late = Board::from_fen(late, &zobrist).unwrap();
+        assert!(late.same_position(&startpos));
+
+        // Same pieces, different side to move.
+        assert!(!startpos.same_position(&startpos.make_null(&zobrist)));
+        // Same pieces, different castling rights.
+        let no_castling = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1";
+        let no_castling = Board::from_fen(no_castling, &zobrist).unwrap();
+        assert!(!no_castling.same_position(&startpos));
+        // Different pieces.
+        assert!(!a.same_position(&b.make_move_str(&zobrist, "e7e5").unwrap()));
+    }
+
+    // Test that the en-passant square is hashed by file, and only when it can be captured
+    #[test]
+    fn ep_zobrist() {
+        let zobrist = Zobrist::new();
+
+        // With a black pawn on d4, the double push can be captured en passant.
+        let board = Board::from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", &zobrist).unwrap();
+        let board = board.make_move_str(&zobrist, "e2e4").unwrap();
+        assert_eq!(board.hash, fresh_hash(&board, &zobrist));
+        let fen = "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1";
+        assert_eq!(board.hash, Board::from_fen(fen, &zobrist).unwrap().hash);
+        let fen = "4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1";
+        assert_ne!(board.hash, Board::from_fen(fen, &zobrist).unwrap().hash);
+
+        // Without it, the en-passant square makes no difference.
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", &zobrist).unwrap();
+        let board = board.make_move_str(&zobrist, "e2e4").unwrap();
+        assert_eq!(board.hash, fresh_hash(&board, &zobrist));
+        let fen = "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1";
+        assert_eq!(board.hash, Board::from_fen(fen, &zobrist).unwrap().hash);
+        let fen = "4k3/8/8/8/4P3/8/8/4K3 b - - 0 1";
+        assert_eq!(board.hash, Board::from_fen(fen, &zobrist).unwrap().hash);
+    }
+
+    // Test that a double push only sets the en-passant square if it can be captured, so that
+    // transpositions through a single push repeat the same position
+    #[test]
+    fn ep_transposition() {
+        let zobrist = Zobrist::new();
+
+        // The white king loses a tempo so that the single pushes transpose.
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", &zobrist).unwrap();
+        let mut double = board.clone();
+        for m in ["e1d1", "e8d8", "d1d2", "d8e8", "d2e1", "e8d8", "e2e4"] {
+            double = double.make_move_str(&zobrist, m).unwrap();
+        }
+        let mut single = board;
+        for m in ["e2e3", "e8d8", "e3e4"] {
+            single = single.make_move_str(&zobrist, m).unwrap();
+        }
+        assert_eq!(double.ep(), None);
+        assert!(double.same_position(&single));
+        assert_eq!(double.hash(), single.hash());
+
+        // With a black pawn on d4, the double push can be captured en passant.
+        let board = Board::from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", &zobrist).unwrap();
+        let mut double = board.clone();
+        for m in ["e1d1", "e8d8", "d1d2", "d8e8", "d2e1", "e8d8", "e2e4"] {
+            double = double.make_move_str(&zobrist, m).unwrap();
+        }
+        let mut single = board;
+        for m in ["e2e3", "e8d8", "e3e4"] {
+            single = single.make_move_str(&zobrist, m).unwrap();
+        }
+        assert_eq!(double.ep(), Square::from_str("e3").ok());
+        assert!(!double.same_position(&single));
+        assert_ne!(double.hash(), single.hash());
+
+        // A FEN en-passant square that cannot be captured is dropped.
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        assert_eq!(board.ep(), None);
+        let startpos = Board::startpos(&zobrist);
+        assert!(board.same_position(&startpos.make_move_str(&zobrist, "e2e4").unwrap()));
+    }
+
+    // Test that incremental hashing agrees with recalculation through en-passant squares for both
+    // sides, on the edge files, after en-passant captures, and after the square lapses
+    #[test]
+    fn ep_hash_consistency() {
+        let zobrist = Zobrist::new();
+        let lines = [
+            (
+                "4k3/1p4p1/8/P6P/8/8/8/4K3 b - - 0 1",
+                [("b7b5", "b6"), ("a5b6", "-"), ("g7g5", "g6"), ("h5g6", "-")],
+            ),
+            (
+                "4k3/8/8/8/1p4p1/8/P6P/4K3 w - - 0 1",
+                [("a2a4", "a3"), ("e8d8", "-"), ("h2h4", "h3"), ("g4h3", "-")],
+            ),
+            (
+                "4k3/8/8/8/3p4/8/2P1P3/4K3 w - - 0 1",
+                [("c2c4", "c3"), ("e8e7", "-"), ("e2e4", "e3"), ("d4e3", "-")],
+            ),
+        ];
+        for (fen, moves) in lines {
+            let mut board = Board::from_fen(fen, &zobrist).unwrap();
+            for (m, ep) in moves {
+                board = board.make_move_str(&zobrist, m).unwrap();
+                assert_eq!(board.ep(), Square::from_str(ep).ok(), "after {m}");
+                assert_eq!(board.hash(), fresh_hash(&board, &zobrist), "after {m}");
+            }
+        }
+    }
+
+    #[test]
+    fn checkmate_and_stalemate() {
+        let zobrist = Zobrist::new();
+        let startpos = Board::startpos(&zobrist);
+        assert!(!startpos.is_checkmate() && !startpos.is_stalemate());
+
+        // The last of each are from the bench suite.
+        let mates = [
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+            "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1",
+            "7k/7P/6K1/8/3B4/8/8/8 b - -",
+        ];
+        let stalemates = [
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            "8/8/8/8/8/6k1/6p1/6K1 w - -",
+        ];
+        for fen in mates {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert!(board.is_checkmate(), "{fen}");
+            assert!(!board.is_stalemate(), "{fen}");
+        }
+        for fen in stalemates {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert!(board.is_stalemate(), "{fen}");
+            assert!(!board.is_checkmate(), "{fen}");
+        }
+        // A check is not mate while the king can escape.
+        let board = Board::from_fen("R5k1/8/8/8/8/8/8/6K1 b - - 0 1", &zobrist).unwrap();
+        assert!(board.in_check() && !board.is_checkmate());
+
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &zobrist).unwrap();
+        assert!(board.gives_checkmate(board.parse_move("a1a8").unwrap(), &zobrist));
+        assert!(!board.gives_checkmate(board.parse_move("a1a7").unwrap(), &zobrist));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let zobrist = Zobrist::new();
+        for fen in [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/5N2/8 b - - 0 1",
+            "8/8/4k3/4b3/8/3K4/8/8 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert!(board.insufficient_material(), "{fen}");
+        }
+        for fen in [
+            "8/8/4k3/8/8/3K4/4P3/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/R7/8 w - - 0 1",
+            "8/8/4k3/4b3/8/3K4/5N2/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/4NN2/8 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert!(!board.insufficient_material(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn null_move_hash() {
+        let zobrist = Zobrist::new();
+        // After e2e4 black can capture en passant, so the null move has to clear the square.
+        let board = Board::from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", &zobrist).unwrap();
+        let board = board.make_move_str(&zobrist, "e2e4").unwrap();
+        assert!(board.ep().is_some());
+        let null = board.make_null(&zobrist);
+        assert_eq!(null.ep(), None);
+        assert_eq!(null.hash(), fresh_hash(&null, &zobrist));
+        let fen = "4k3/8/8/8/3pP3/8/8/4K3 w - - 0 1";
+        assert_eq!(null.hash(), Board::from_fen(fen, &zobrist).unwrap().hash());
+        // Passing twice gives back the position, less the en-passant capture.
+        let double = null.make_null(&zobrist);
+        assert_ne!(double.hash(), board.hash());
+        assert_eq!(double.hash(), fresh_hash(&double, &zobrist));
+    }
+
+    #[test]
+    fn halfmove_clock() {
+        let zobrist = Zobrist::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 7 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        assert_eq!(board.halfmove_clock(), 7);
+        // Quiet piece moves and castling count up; pawn moves and captures reset the clock.
+        for (m, clock) in [
+            ("e1g1", 8),
+            ("f3f5", 8),
+            ("a2a3", 0),
+            ("a2a4", 0),
+            ("e5f7", 0),
+            ("g2h3", 0),
+        ] {
+            let after = board.make_move_str(&zobrist, m).unwrap();
+            assert_eq!(after.halfmove_clock(), clock, "{m}");
+        }
+        // The clock is optional.
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -";
+        assert_eq!(Board::from_fen(fen, &zobrist).unwrap().halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn no_zobrist_collisions() {
+        // Visit every position within `depth` plies, keeping one board per hash.
+        fn visit(board: &Board, zobrist: &Zobrist, depth: u32, seen: &mut HashMap<u64, Board>) {
+            assert_eq!(board.hash, fresh_hash(board, zobrist), "{board}");
+            let earlier = seen.entry(board.hash).or_insert_with(|| board.clone());
+            assert!(
+                earlier.same_position(board),
+                "{earlier}and\n{board}share hash {:#x}",
+                board.hash
+            );
+            if depth == 0 {
+                return;
+            }
+            let mut moves = MoveList::new();
+            board.generate(&mut moves);
+            for m in moves {
+                visit(&board.make(m, zobrist), zobrist, depth - 1, seen);
+            }
+        }
+
+        // The keys come from a fixed seed, so this checks the same table every run.
+        let zobrist = Zobrist::new();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+        let mut seen = HashMap::new();
+        for fen in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            visit(&board, &zobrist, 3, &mut seen);
+        }
+        assert!(seen.len() > 50_000);
+    }
+
+    #[test]
+    fn to_array() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let array = board.to_array();
+        let back_rank = [
+            Piece::Rook,
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Queen,
+            Piece::King,
+            Piece::Bishop,
+            Piece::Knight,
+            Piece::Rook,
+        ];
+        for (file, &piece) in back_rank.iter().enumerate() {
+            assert_eq!(array[0][file], Some((Colour::White, piece)));
+            assert_eq!(array[1][file], Some((Colour::White, Piece::Pawn)));
+            assert!(array[2..6].iter().all(|rank| rank[file].is_none()));
+            assert_eq!(array[6][file], Some((Colour::Black, Piece::Pawn)));
+            assert_eq!(array[7][file], Some((Colour::Black, piece)));
+        }
+
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let array = board.to_array();
+        for square in 0_u8..64 {
+            let slot = array[usize::from(square / 8)][usize::from(square % 8)];
+            let square = Square::try_from(square).unwrap();
+            assert_eq!(
+                slot.map(|(_, piece)| piece),
+                board.piece_from_square(square)
+            );
+        }
+        assert_eq!(array[7][0], Some((Colour::Black, Piece::Rook)));
+    }
+
+    #[test]
+    fn piece_on() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        for square in 0_u8..64 {
+            let square = Square::try_from(square).unwrap();
+            let colour = board.data.colour_from_square(square);
+            let piece = board.piece_from_square(square);
+            assert_eq!(board.piece_on(square), colour.zip(piece));
+            assert_eq!(colour.is_some(), piece.is_some());
+        }
+        assert_eq!(
+            board.piece_on(Square::try_from(4).unwrap()),
+            Some((Colour::White, Piece::King))
+        );
+    }
+
+    #[test]
+    fn incremental_phase() {
+        // Sum the phase weights of the pieces on the board.
+        fn fresh_phase(board: &Board) -> u8 {
+            let phase: u8 = board
+                .pieces()
+                .into_iter()
+                .map(|bit| match board.piece_from_bit(bit) {
+                    Piece::Pawn | Piece::King => 0,
+                    Piece::Knight | Piece::Bishop => 1,
+                    Piece::Rook => 2,
+                    Piece::Queen => 4,
+                })
+                .sum();
+            phase.min(24)
+        }
+
+        let zobrist = Zobrist::new();
+        assert_eq!(Board::startpos(&zobrist).phase(), 24);
+        let mut board =
+            Board::from_fen("r7/1P5k/8/8/3q4/8/5N2/R3K1R1 w - - 0 1", &zobrist).unwrap();
+        assert_eq!(board.phase(), 11);
+        // A capture-promotion, a queen taking a knight, and the king taking the queen.
+        for (m, phase) in [("b7a8q", 13), ("d4f2", 12), ("e1f2", 8)] {
+            board = board.make(board.parse_move(m).unwrap(), &zobrist);
+            assert_eq!(board.phase(), phase, "after {m}");
+            assert_eq!(board.phase(), fresh_phase(&board), "after {m}");
+        }
+    }
+
+    #[test]
+    fn truncated_fen() {
+        let zobrist = Zobrist::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        // Everything up to the side to move is required.
+        let required = fen.find(" w").unwrap() + 2;
+        for len in 0..fen.len() {
+            let result = Board::from_fen(&fen[..len], &zobrist);
+            if len < required {
+                assert!(result.is_err(), "accepted truncated FEN {:?}", &fen[..len]);
+            }
+        }
+        assert!(Board::from_fen("8/8/8", &zobrist).is_err());
+    }
+
+    #[test]
+    fn malformed_fen() {
+        let zobrist = Zobrist::new();
+        let cases = [
+            ("", FenError::MissingField),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", FenError::MissingField),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1", FenError::BadPlacement),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR/8 w KQkq - 0 1", FenError::BadPlacement),
+            ("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPlacement),
+            ("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPlacement),
+            ("rnbqkbnr/pppppppp/99999999999999999999999999999999999/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1", FenError::BadPlacement),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1", FenError::BadPlacement),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", FenError::BadSide),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1", FenError::BadCastling),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KKkq - 0 1", FenError::BadCastling),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w BGbg - 0 1", FenError::BadCastling),
+            ("r3k2r/8/8/8/8/8/8/R4K1R w HAha - 0 1", FenError::BadCastling),
+            ("4k3/8/8/8/8/8/8/7K w K - 0 1", FenError::BadCastling),
+            ("4k3/8/8/8/8/8/8/K6R w K - 0 1", FenError::BadCastling),
+            ("r3k2r/8/8/8/8/8/8/r3K2R w KQkq - 0 1", FenError::BadCastling),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KHkq - 0 1", FenError::BadCastling),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w bm Nf3;", FenError::BadCastling),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e 0 1", FenError::BadEnPassant),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1", FenError::BadEnPassant),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq i6 0 1", FenError::BadEnPassant),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq bm Nf3;", FenError::BadEnPassant),
+            ("rnbqqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::IllegalPosition),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w KQkq - 0 1", FenError::IllegalPosition),
+            ("rnbqkbnr/pppppppp/8/8/8/P7/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::IllegalPosition),
+            ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", FenError::IllegalPosition),
+            ("8/8/8/8/8/8/8/8 w - - 0 1", FenError::IllegalPosition),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 \u{e9}", FenError::NotAscii),
+        ];
+
+        for (fen, error) in cases {
+            assert_eq!(
+                Board::from_fen_bytes(fen.as_bytes(), &zobrist).err(),
+                Some(error),
+                "wrong result for {fen:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn fabricated_castling_rejected() {
+        let zobrist = Zobrist::new();
+        // The white king has left e1 and the black rooks have left their corners.
+        let board = Board::from_fen(
+            "2r2rk1/pp1bqppp/2n1pn2/3p4/3P4/2NBPN2/PP1QKPPP/R6R w - - 0 12",
+            &zobrist,
+        )
+        .unwrap();
+        let castle = |from: &str, dest: &str| {
+            Move::new(
+                from.parse().unwrap(),
+                dest.parse().unwrap(),
+                MoveType::Castle,
+                None,
+            )
+        };
+        for m in [castle("e1", "g1"), castle("e1", "c1"), castle("e2", "g2")] {
+            assert!(!board.is_legal(m), "{m}");
+            assert!(board.make_checked(m, &zobrist).is_none(), "{m}");
+        }
+        let black = board.make_move_str(&zobrist, "a2a3").unwrap();
+        for m in [castle("e8", "g8"), castle("e8", "c8"), castle("g8", "e8")] {
+            assert!(black.make_checked(m, &zobrist).is_none(), "{m}");
+        }
+
+        // Nor can a king step two squares sideways from wherever it stands in a midgame.
+        for fen in BENCH_FENS {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let king = board.king_square(board.side());
+            for dest in [
+                king.east().and_then(Square::east),
+                king.west().and_then(Square::west),
+            ] {
+                let Some(dest) = dest else { continue };
+                let m = Move::new(king, dest, MoveType::Castle, None);
+                if !board.is_legal(m) {
+                    assert!(board.make_checked(m, &zobrist).is_none(), "{fen} {m}");
+                }
+            }
+        }
+
+        // The real thing still goes through.
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &zobrist).unwrap();
+        let child = board.make_checked(castle("e1", "c1"), &zobrist).unwrap();
+        assert_eq!(child.to_fen(1), "r3k2r/8/8/8/8/8/8/2KR3R b kq - 1 1");
+    }
+
+    #[test]
+    fn make_move_str() {
+        let zobrist = Zobrist::new();
+        // A Ruy Lopez in a mix of notations, castling and a capture included.
+        let mut board = Board::startpos(&zobrist);
+        let game = [
+            "e2e4", "e5", "Nf3", "b8c6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "f6",
+        ];
+        for m in game {
+            board = board.make_move_str(&zobrist, m).unwrap();
+        }
+        assert_eq!(
+            board.to_fen(6),
+            "r1bqkbnr/1pp3pp/p1p2p2/4p3/4P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 0 6"
+        );
+
+        let board = Board::startpos(&zobrist);
+        for m in ["e2e5", "Ke2", "O-O", "e7e5", "e2", "xx"] {
+            assert!(board.make_move_str(&zobrist, m).is_none(), "{m}");
+        }
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let zobrist = Zobrist::new();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "4k3/8/8/8/8/8/8/4K2R b K - 17 42",
+        ] {
+            let fullmove = fen.rsplit(' ').next().unwrap().parse().unwrap();
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert_eq!(board.to_fen(fullmove), fen);
+        }
+
+        // An en-passant square nothing can capture on is dropped.
+        let board = Board::from_fen(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            &zobrist,
+        )
+        .unwrap();
+        assert_eq!(
+            board.to_fen(1),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn epd_fen() {
+        let zobrist = Zobrist::new();
+        let full = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let kiwipete = Board::from_fen(full, &zobrist).unwrap();
+        let placement = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R";
+        let same = [
+            "w KQkq -",
+            "w KQkq",
+            "w KQkq - ",
+            "  w   KQkq   -   0   1  ",
+            "w KQkq - 0",
+            "w HAha - 0 1",
+            "w AHah -",
+            "w KQha -",
+            "w KQkq - bm Qxf6; id \"kiwipete\";",
+            "w KQkq - c9 \"1/2-1/2\";",
+            "w KQkq - [0.5]",
+            "w KQkq - 0 1 c0 \"comment\";",
+            "w KQkq - hmvc 0; fmvn 1;",
+        ];
+        for rest in same {
+            let fen = format!("{placement} {rest}");
+            let board = Board::from_fen(&fen, &zobrist).unwrap();
+            assert!(board.same_position(&kiwipete), "{fen:?}");
+            assert_eq!(board.hash(), kiwipete.hash(), "{fen:?}");
+            assert_eq!(board.halfmove_clock(), 0, "{fen:?}");
+        }
+
+        // Missing castling rights and en-passant fields mean there are none.
+        let none = Board::from_fen(&format!("{placement} w - -"), &zobrist).unwrap();
+        let board = Board::from_fen(&format!("{placement} w"), &zobrist).unwrap();
+        assert!(board.same_position(&none));
+
+        // The halfmove clock is kept when present, even in an EPD record.
+        let fen = format!("{placement} b KQkq - 37 60 ; c9 \"0-1\";");
+        let board = Board::from_fen(&fen, &zobrist).unwrap();
+        assert_eq!(board.halfmove_clock(), 37);
+        assert_eq!(board.side(), Colour::Black);
+
+        // An en-passant square with nothing after it.
+        let fen = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        assert_eq!(board.ep(), Some(Square::from_str("e3").unwrap()));
+    }
+
+    #[test]
+    fn fuzzed_fen() {
+        let zobrist = Zobrist::new();
+        let fen = b"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let alphabet = b"pnbrqkPNBRQK12345678/ -wbKQkqabcdefgh0123456789";
+        let mut rng = StdRng::seed_from_u64(1);
+        // Miri is far too slow for the full run.
+        let iterations = if cfg!(miri) { 100 } else { 10_000 };
+
+        // Whatever parses must also be safe to generate and make moves from.
+        let play = |fen: &[u8]| {
+            if let Ok(board) = Board::from_fen_bytes(fen, &zobrist) {
+                let mut moves = MoveList::new();
+                board.generate(&mut moves);
+                for m in moves {
+                    let _ = board.make(m, &zobrist);
+                }
+            }
+        };
+        // Castling rights without the king or the rook at home used to parse.
+        play(b"4k3/8/8/8/8/8/8/7K w K - 0 1");
+        play(b"4k3/8/8/8/8/8/8/K6R w K - 0 1");
+
+        for _ in 0..iterations {
+            let mut fen = fen.to_vec();
+            for _ in 0..rng.gen_range(1..4) {
+                let index = rng.gen_range(0..fen.len());
+                match rng.gen_range(0..3) {
+                    0 => fen[index] = alphabet[rng.gen_range(0..alphabet.len())],
+                    1 => {
+                        fen.remove(index);
+                    }
+                    _ => fen.insert(index, alphabet[rng.gen_range(0..alphabet.len())]),
+                }
+            }
+            // This must never panic.
+            play(&fen);
+        }
+    }
+
+    // Test that kings, rooks and rook captures take away the rights they should, and no others
+    #[test]
+    fn castle_rights_transitions() {
+        let zobrist = Zobrist::new();
+        let rights_after = |fen: &str, m: &str| {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let child = board.make(board.parse_move(m).unwrap(), &zobrist);
+            assert_eq!(child.hash, fresh_hash(&child, &zobrist), "{m}");
+            child.castle_rights().to_string()
+        };
+        let white = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let black = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        let minors = "r3k2r/8/8/8/8/8/4P3/RN2K2R w KQkq - 0 1";
+        for (fen, m, rights) in [
+            // King moves, castling included, lose both of that side's rights.
+            (white, "e1e2", "kq"),
+            (white, "e1f1", "kq"),
+            (white, "e1g1", "kq"),
+            (black, "e8d8", "KQ"),
+            (black, "e8c8", "KQ"),
+            // Rook moves lose only that rook's right.
+            (white, "h1h5", "Qkq"),
+            (white, "a1b1", "Kkq"),
+            (white, "a1a2", "Kkq"),
+            (black, "h8g8", "KQq"),
+            (black, "a8a1", "Kk"),
+            // Capturing a rook on its corner loses both sides a right.
+            (white, "h1h8", "Qq"),
+            (white, "a1a8", "Kk"),
+            // Other moves keep them all.
+            (minors, "b1c3", "KQkq"),
+            (minors, "e2e4", "KQkq"),
+        ] {
+            assert_eq!(rights_after(fen, m), rights, "{m}");
+        }
+
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K1R1 w Qk - 0 1", &zobrist).unwrap();
+        let rights = board.castle_rights();
+        assert!(rights.has(Colour::White, CastleSide::Queenside));
+        assert!(!rights.has(Colour::White, CastleSide::Kingside));
+        assert_eq!(rights_after(&board.to_fen(1), "g1g8"), "Qk");
+    }
+
+    // Test that hash table moves from the wrong position are caught before they are played
+    #[test]
+    fn pseudo_legal() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let m = |from: &str, dest: &str, kind, prom| {
+            Move::new(from.parse().unwrap(), dest.parse().unwrap(), kind, prom)
+        };
+        assert!(board.is_pseudo_legal(m("e2", "e4", MoveType::DoublePush, None)));
+        assert!(board.is_pseudo_legal(m("g1", "f3", MoveType::Normal, None)));
+        for wrong in [
+            // Black's move, an empty square, and the wrong kinds of move.
+            m("e7", "e5", MoveType::DoublePush, None),
+            m("e4", "e5", MoveType::Normal, None),
+            m("e2", "e4", MoveType::Normal, None),
+            m("g1", "f3", MoveType::Capture, None),
+            m("e2", "e3", MoveType::Promotion, Some(Piece::Queen)),
+            m("e2", "e3", MoveType::Normal, Some(Piece::Queen)),
+            // Pieces that cannot get there, or are blocked.
+            m("e2", "e5", MoveType::Normal, None),
+            m("g1", "g3", MoveType::Normal, None),
+            m("f1", "c4", MoveType::Normal, None),
+            m("a1", "a3", MoveType::Normal, None),
+            m("d1", "d7", MoveType::Capture, None),
+            m("e1", "g1", MoveType::Castle, None),
+        ] {
+            assert!(!board.is_pseudo_legal(wrong), "{wrong}");
+        }
+
+        // A pinned piece's move is pseudo-legal; making it shows that it is not legal.
+        let board = Board::from_fen("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1", &zobrist).unwrap();
+        let pinned = m("e2", "d3", MoveType::Normal, None);
+        assert!(board.is_pseudo_legal(pinned) && !board.is_legal(pinned));
+        assert!(board.make(pinned, &zobrist).illegal());
+
+        // Castling through an attacked square is ruled out, since making it would not show it.
+        let fen = "r3k2r/8/8/8/2b5/8/8/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        assert!(!board.is_pseudo_legal(m("e1", "g1", MoveType::Castle, None)));
+        assert!(board.is_pseudo_legal(m("e1", "c1", MoveType::Castle, None)));
+
+        // Moves of every other position are either rejected, or legal exactly when the board
+        // after them is.
+        let boards: Vec<_> = BENCH_FENS
+            .iter()
+            .map(|fen| Board::from_fen(fen, &zobrist).unwrap())
+            .collect();
+        for board in &boards {
+            for other in &boards {
+                let mut moves = MoveList::new();
+                other.generate(&mut moves);
+                for m in moves {
+                    let legal = board.is_legal(m);
+                    if board.is_pseudo_legal(m) {
+                        assert_eq!(legal, !board.make(m, &zobrist).illegal(), "{m}");
+                    } else {
+                        assert!(!legal, "{m}");
+                    }
+                }
+            }
+        }
+    }
 }
 /* impl Drop for Board {
     fn drop(&mut self) {
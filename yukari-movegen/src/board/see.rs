@@ -0,0 +1,213 @@
+use super::{Bitlist, Board};
+use crate::{
+    chessmove::{Move, MoveType},
+    piece::Piece,
+    square::Square,
+};
+
+/// Exchange values of each piece type, pawn to king.
+const SEE_VALUE: [i32; 6] = [100, 300, 300, 500, 900, 20000];
+
+/// Piece types in the order attackers join an exchange.
+const SEE_ORDER: [Piece; 6] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+    Piece::King,
+];
+
+impl Board {
+    /// Return whether the exchange started by `m` on its destination square wins at least
+    /// `threshold` centipawns for the side to move.
+    ///
+    /// Both sides recapture with their least valuable attacker and may stop whenever doing so
+    /// is better for them; sliders behind the pieces that have been traded join in. Pins are
+    /// ignored. This stops as soon as the result is known, so it is cheaper than computing the
+    /// whole exchange.
+    ///
+    /// # Panics
+    /// Panics if `m` does not move a piece of the side to move.
+    #[must_use]
+    pub fn see_ge(&self, m: Move, threshold: i32) -> bool {
+        if m.kind == MoveType::Castle {
+            return threshold <= 0;
+        }
+
+        let value = |piece: Piece| SEE_VALUE[piece as usize];
+        let moving = self
+            .piece_from_square(m.from)
+            .expect("move is from an empty square");
+
+        let mut used = Bitlist::new();
+        let mut captured = 0;
+        if let Some(victim) = self.data.piece_index(m.dest) {
+            captured = value(self.data.piece_from_bit(victim));
+        } else if m.kind == MoveType::EnPassant {
+            captured = value(Piece::Pawn);
+            let square = m
+                .dest
+                .relative_south(self.side)
+                .expect("en passant onto the edge of the board");
+            used |= Bitlist::from(self.data.piece_index(square).unwrap());
+        }
+        let attacker = m.prom.map_or(moving, |prom| {
+            captured += value(prom) - value(Piece::Pawn);
+            prom
+        });
+
+        // The balance from the point of view of the side that just moved, relative to the
+        // threshold: if it is negative even before they can lose the piece they moved, or
+        // positive even after, nothing more needs to be looked at.
+        let mut swap = captured - threshold;
+        if swap < 0 {
+            return false;
+        }
+        swap = value(attacker) - swap;
+        if swap <= 0 {
+            return true;
+        }
+
+        let from = self.data.piece_index(m.from).unwrap();
+        used |= Bitlist::from(from);
+        let mut attackers =
+            self.data.attacks_to(m.dest, self.side) | self.data.attacks_to(m.dest, !self.side);
+        for piece in used {
+            attackers |= self.xray(m.dest, self.data.square_of_piece(piece), used);
+        }
+        attackers &= !used;
+
+        let mut side = self.side;
+        let mut result = true;
+        loop {
+            side = !side;
+            let side_attackers = attackers & self.data.pieces_of_colour(side);
+            if side_attackers.empty() {
+                break;
+            }
+
+            let (piece, index) = SEE_ORDER
+                .into_iter()
+                .find_map(|piece| {
+                    let of_type = side_attackers & self.piece_mask(piece);
+                    of_type.peek().map(|index| (piece, index))
+                })
+                .unwrap();
+
+            // A king can only capture if nothing recaptures it.
+            if piece == Piece::King {
+                if (attackers & self.data.pieces_of_colour(!side)).empty() {
+                    result = !result;
+                }
+                break;
+            }
+
+            result = !result;
+            swap = value(piece) - swap;
+            if swap < i32::from(result) {
+                break;
+            }
+
+            used |= Bitlist::from(index);
+            attackers &= !Bitlist::from(index);
+            attackers |= self.xray(m.dest, self.data.square_of_piece(index), used);
+        }
+
+        result
+    }
+
+    /// Return the pieces of a piece type.
+    const fn piece_mask(&self, piece: Piece) -> Bitlist {
+        match piece {
+            Piece::Pawn => self.data.pawns(),
+            Piece::Knight => self.data.knights(),
+            Piece::Bishop => self.data.bishops(),
+            Piece::Rook => self.data.rooks(),
+            Piece::Queen => self.data.queens(),
+            Piece::King => self.data.kings(),
+        }
+    }
+
+    /// Return the slider, if any, that attacks `target` through `square` once the piece on
+    /// `square` and the pieces in `used` are gone.
+    fn xray(&self, target: Square, square: Square, used: Bitlist) -> Bitlist {
+        let Some(dir) = target.direction(square) else {
+            return Bitlist::new();
+        };
+        if !dir.diagonal() && !dir.orthogonal() {
+            return Bitlist::new();
+        }
+
+        let mut next = square.travel(dir);
+        while let Some(square) = next {
+            if let Some(index) = self.data.piece_index(square) {
+                if !used.contains(index.into()) {
+                    let piece = self.data.piece_from_bit(index);
+                    if matches!(piece, Piece::Bishop | Piece::Rook | Piece::Queen)
+                        && dir.valid_for_slider(piece)
+                    {
+                        return Bitlist::from(index);
+                    }
+                    return Bitlist::new();
+                }
+            }
+            next = square.travel(dir);
+        }
+        Bitlist::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, Zobrist};
+
+    fn see_ge(fen: &str, m: &str, threshold: i32) -> bool {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let m = board.parse_move(m).unwrap();
+        board.see_ge(m, threshold)
+    }
+
+    #[test]
+    fn threshold_zero() {
+        // A free pawn.
+        assert!(see_ge("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", 0));
+        // Pawn for pawn.
+        assert!(see_ge("4k3/8/2p5/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", 0));
+        // Knight for a defended pawn.
+        assert!(!see_ge("4k3/8/2p5/3p4/8/4N3/8/4K3 w - - 0 1", "e3d5", 0));
+        // Queen takes a defended rook.
+        assert!(!see_ge("3rk3/8/8/3r4/8/8/8/3QK3 w - - 0 1", "d1d5", 0));
+        // A king cannot recapture a defended piece.
+        assert!(see_ge("8/8/4k3/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5", 0));
+        // En passant wins a pawn.
+        assert!(see_ge("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 0));
+        // A quiet move to an attacked square loses the piece.
+        assert!(!see_ge("4k3/8/2p5/8/8/4N3/8/4K3 w - - 0 1", "e3d5", 0));
+        assert!(see_ge("4k3/8/8/8/8/4N3/8/4K3 w - - 0 1", "e3d5", 0));
+    }
+
+    #[test]
+    fn batteries() {
+        // Doubled rooks win a rook defended once: RxR RxR RxR.
+        let fen = "3rk3/8/8/3r4/8/8/3R4/3RK3 w - - 0 1";
+        assert!(see_ge(fen, "d2d5", 500));
+        assert!(!see_ge(fen, "d2d5", 501));
+
+        // A queen behind a bishop: BxN pxB QxP, a pawn up after the knight and bishop trade.
+        let fen = "4k3/8/2p5/3n4/4B3/5Q2/8/4K3 w - - 0 1";
+        assert!(see_ge(fen, "e4d5", 100));
+        assert!(!see_ge(fen, "e4d5", 101));
+
+        // RxR QxR, and the rook behind the queen is never needed.
+        let fen = "3rk3/3q4/8/3r4/8/8/8/3RK3 w - - 0 1";
+        assert!(see_ge(fen, "d1d5", 0));
+        assert!(!see_ge(fen, "d1d5", 1));
+
+        // RxR QxR RxQ RxR: the queen recaptures first and is lost to the second rook.
+        let fen = "3rk3/3q4/8/3r4/8/8/3R4/3RK3 w - - 0 1";
+        assert!(see_ge(fen, "d2d5", 400));
+        assert!(!see_ge(fen, "d2d5", 401));
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::{self, Read, Write};
+
+use crate::{colour::Colour, piece::Piece};
+
+/// A position and the features derived from it, in plain arrays indexed by square (a1 = 0,
+/// h8 = 63), for writing out as training data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardSnapshot {
+    /// The piece on each square, if any.
+    pub pieces: [Option<(Colour, Piece)>; 64],
+    /// The side to move.
+    pub side: Colour,
+    /// How many pieces of each colour attack each square, indexed by colour and then square.
+    /// Squares a colour's own pieces stand on count too, as defended.
+    pub attacks: [[u8; 64]; 2],
+    /// The pieces of the side to move pinned to their king, one bit per square.
+    pub pinned: u64,
+    /// How many pieces give check.
+    pub checkers: u8,
+    /// Whether the side to move is in check.
+    pub in_check: bool,
+    /// Zobrist hash of the position.
+    pub hash: u64,
+}
+
+impl BoardSnapshot {
+    /// Size of a snapshot written by [`BoardSnapshot::write_to`], in bytes.
+    pub const BYTES: usize = 64 + 1 + 2 * 64 + 8 + 1 + 1 + 8;
+
+    /// Write the snapshot in a fixed-size binary layout: a byte per square for the pieces
+    /// (0 for empty, 1-6 for white pawn to king, 7-12 for black), the side to move (0 for
+    /// white), white's and then black's attack counts, then the pin bitboard, checker count,
+    /// check flag and hash. Multi-byte values are little-endian.
+    ///
+    /// # Errors
+    /// Returns any error from writing to `w`.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut bytes = [0; Self::BYTES];
+        for (byte, piece) in bytes.iter_mut().zip(self.pieces) {
+            *byte = match piece {
+                None => 0,
+                Some((Colour::White, piece)) => piece as u8 + 1,
+                Some((Colour::Black, piece)) => piece as u8 + 7,
+            };
+        }
+        bytes[64] = u8::from(self.side == Colour::Black);
+        bytes[65..129].copy_from_slice(&self.attacks[0]);
+        bytes[129..193].copy_from_slice(&self.attacks[1]);
+        bytes[193..201].copy_from_slice(&self.pinned.to_le_bytes());
+        bytes[201] = self.checkers;
+        bytes[202] = u8::from(self.in_check);
+        bytes[203..211].copy_from_slice(&self.hash.to_le_bytes());
+        w.write_all(&bytes)
+    }
+
+    /// Read a snapshot written by [`BoardSnapshot::write_to`].
+    ///
+    /// # Errors
+    /// Returns any error from reading `r`, or `InvalidData` for a byte out of range.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed board snapshot");
+        let mut bytes = [0; Self::BYTES];
+        r.read_exact(&mut bytes)?;
+
+        let mut pieces = [None; 64];
+        for (piece, &byte) in pieces.iter_mut().zip(&bytes[..64]) {
+            *piece = match byte {
+                0 => None,
+                1..=6 => Some((Colour::White, Piece::ALL[usize::from(byte - 1)])),
+                7..=12 => Some((Colour::Black, Piece::ALL[usize::from(byte - 7)])),
+                _ => return Err(invalid()),
+            };
+        }
+        let side = match bytes[64] {
+            0 => Colour::White,
+            1 => Colour::Black,
+            _ => return Err(invalid()),
+        };
+        let mut attacks = [[0; 64]; 2];
+        attacks[0].copy_from_slice(&bytes[65..129]);
+        attacks[1].copy_from_slice(&bytes[129..193]);
+        let in_check = match bytes[202] {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid()),
+        };
+        let u64_at = |start: usize| {
+            let mut word = [0; 8];
+            word.copy_from_slice(&bytes[start..start + 8]);
+            u64::from_le_bytes(word)
+        };
+
+        Ok(Self {
+            pieces,
+            side,
+            attacks,
+            pinned: u64_at(193),
+            checkers: bytes[201],
+            in_check,
+            hash: u64_at(203),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoardSnapshot;
+    use crate::{Board, Colour, Piece, Zobrist};
+
+    /// File and rank of a square index.
+    fn coords(square: usize) -> (i32, i32) {
+        let square = i32::try_from(square).unwrap();
+        (square % 8, square / 8)
+    }
+
+    /// Squares strictly between two squares on a line, or `None` if they share no line.
+    fn between(from: usize, to: usize) -> Option<Vec<usize>> {
+        let ((ff, fr), (tf, tr)) = (coords(from), coords(to));
+        let (df, dr) = (tf - ff, tr - fr);
+        if (df == 0 && dr == 0) || (df != 0 && dr != 0 && df.abs() != dr.abs()) {
+            return None;
+        }
+        let steps = df.abs().max(dr.abs());
+        let squares = (1..steps)
+            .map(|step| (fr + dr.signum() * step) * 8 + ff + df.signum() * step)
+            .map(|square| usize::try_from(square).unwrap())
+            .collect();
+        Some(squares)
+    }
+
+    /// Whether `piece` of `colour` on `from` attacks `to`, found from the geometry alone.
+    fn attacks(pieces: &[Option<(Colour, Piece)>; 64], from: usize, to: usize) -> bool {
+        let Some((colour, piece)) = pieces[from] else {
+            return false;
+        };
+        let ((ff, fr), (tf, tr)) = (coords(from), coords(to));
+        let (df, dr) = (tf - ff, tr - fr);
+        let forward = colour.sign();
+        let clear = |line: Vec<usize>| line.iter().all(|&square| pieces[square].is_none());
+        match piece {
+            Piece::Pawn => df.abs() == 1 && dr == forward,
+            Piece::Knight => (df.abs(), dr.abs()) == (1, 2) || (df.abs(), dr.abs()) == (2, 1),
+            Piece::King => df.abs().max(dr.abs()) == 1,
+            Piece::Bishop => df.abs() == dr.abs() && between(from, to).is_some_and(clear),
+            Piece::Rook => (df == 0 || dr == 0) && between(from, to).is_some_and(clear),
+            Piece::Queen => between(from, to).is_some_and(clear),
+        }
+    }
+
+    /// Recompute a snapshot without the move generator's attack tables or pin detection.
+    fn recompute(board: &Board) -> BoardSnapshot {
+        let mut pieces = [None; 64];
+        for (piece, &square) in pieces.iter_mut().zip(board.to_array().iter().flatten()) {
+            *piece = square;
+        }
+
+        let mut attack_counts = [[0; 64]; 2];
+        for (from, piece) in pieces.iter().enumerate() {
+            let Some((colour, _)) = *piece else {
+                continue;
+            };
+            let counts = attack_counts[usize::from(colour)].iter_mut();
+            for (to, count) in counts.enumerate() {
+                if attacks(&pieces, from, to) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let side = board.side();
+        let king_square = (0..64)
+            .find(|&square| pieces[square] == Some((side, Piece::King)))
+            .unwrap();
+        let mut pinned = 0;
+        for (pinner, piece) in pieces.iter().enumerate() {
+            let Some((colour, kind)) = *piece else {
+                continue;
+            };
+            if colour == side || !matches!(kind, Piece::Bishop | Piece::Rook | Piece::Queen) {
+                continue;
+            }
+            // A pinner would attack the king if the pinned piece were not in the way.
+            let mut without = pieces;
+            let Some(line) = between(pinner, king_square) else {
+                continue;
+            };
+            let blockers: Vec<_> = line.into_iter().filter(|&s| pieces[s].is_some()).collect();
+            if let [blocker] = blockers[..] {
+                if pieces[blocker].is_some_and(|(colour, _)| colour == side) {
+                    without[blocker] = None;
+                    if attacks(&without, pinner, king_square) {
+                        pinned |= 1 << blocker;
+                    }
+                }
+            }
+        }
+
+        let checkers = attack_counts[usize::from(!side)][king_square];
+        BoardSnapshot {
+            pieces,
+            side,
+            attacks: attack_counts,
+            pinned,
+            checkers,
+            in_check: checkers > 0,
+            hash: board.hash(),
+        }
+    }
+
+    #[test]
+    fn matches_recomputation() {
+        let zobrist = Zobrist::new();
+        let fens = [
+            // Kiwipete, from both sides
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            // In check from the rook, with the d2 pawn pinned by the bishop
+            "4k3/8/8/8/1b6/8/3P4/4K2r w - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let snapshot = board.snapshot();
+            assert_eq!(snapshot, recompute(&board), "{fen}");
+
+            let mut bytes = Vec::new();
+            snapshot.write_to(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), BoardSnapshot::BYTES);
+            let read = BoardSnapshot::read_from(&mut bytes.as_slice()).unwrap();
+            assert_eq!(read, snapshot, "{fen}");
+        }
+
+        let board = Board::from_fen(fens[2], &zobrist).unwrap();
+        let snapshot = board.snapshot();
+        assert_eq!(snapshot.pinned, 1 << 11);
+        assert_eq!((snapshot.checkers, snapshot.in_check), (1, true));
+
+        // Out-of-range bytes are rejected.
+        let mut bytes = Vec::new();
+        snapshot.write_to(&mut bytes).unwrap();
+        bytes[0] = 13;
+        assert!(BoardSnapshot::read_from(&mut bytes.as_slice()).is_err());
+    }
+}
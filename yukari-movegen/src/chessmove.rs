@@ -1,8 +1,13 @@
 use crate::{
+    colour::Colour,
     piece::Piece,
     square::{File, Rank, Square},
 };
 use std::fmt::Display;
+use tinyvec::ArrayVec;
+
+/// A list with room for the moves of any position, for `Board::generate` to fill.
+pub type MoveList = ArrayVec<[Move; 256]>;
 
 #[derive(Copy, Clone, Default, PartialEq, Eq)]
 pub struct Move {
@@ -25,8 +30,7 @@ impl Display for Move {
         )?;
 
         if let Some(prom) = self.prom {
-            static PROMOTE_CHAR: [char; 6] = ['p', 'n', 'b', 'r', 'q', 'k'];
-            write!(f, "{}", PROMOTE_CHAR[prom as usize])?;
+            write!(f, "{}", prom.to_coloured_char(Colour::Black))?;
         }
 
         Ok(())
@@ -60,8 +64,9 @@ impl Move {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
 pub enum MoveType {
+    #[default]
     Normal,
     Capture,
     Castle,
@@ -70,9 +75,3 @@ pub enum MoveType {
     Promotion,
     CapturePromotion,
 }
-
-impl Default for MoveType {
-    fn default() -> Self {
-        Self::Normal
-    }
-}
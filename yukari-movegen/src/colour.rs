@@ -1,7 +1,8 @@
+use std::fmt::{self, Display};
 use std::ops::Not;
 
 /// A piece colour.
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Colour {
     /// White pieces.
     White,
@@ -29,3 +30,65 @@ impl Not for Colour {
         }
     }
 }
+
+impl Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl Colour {
+    /// Both colours, white first, in the order of their indices.
+    pub const BOTH: [Self; 2] = [Self::White, Self::Black];
+
+    /// Parse a FEN side-to-move letter: `w` or `b`.
+    #[must_use]
+    pub const fn from_char(c: char) -> Option<Self> {
+        match c {
+            'w' => Some(Self::White),
+            'b' => Some(Self::Black),
+            _ => None,
+        }
+    }
+
+    /// The FEN side-to-move letter of this colour.
+    #[must_use]
+    pub const fn to_char(self) -> char {
+        match self {
+            Self::White => 'w',
+            Self::Black => 'b',
+        }
+    }
+
+    /// The sign of this colour's scores from white's point of view: 1 for white, -1 for black.
+    #[must_use]
+    pub const fn sign(self) -> i32 {
+        match self {
+            Self::White => 1,
+            Self::Black => -1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Colour;
+
+    #[test]
+    fn char_round_trip() {
+        for colour in Colour::BOTH {
+            assert_eq!(Colour::from_char(colour.to_char()), Some(colour));
+            assert_eq!(colour.to_string(), colour.to_char().to_string());
+        }
+        assert!(Colour::from_char('W').is_none());
+        assert!(Colour::from_char('x').is_none());
+    }
+
+    #[test]
+    fn both_and_sign() {
+        assert_eq!(Colour::BOTH.map(usize::from), [0, 1]);
+        assert_eq!(Colour::White.sign(), 1);
+        assert_eq!(Colour::Black.sign(), -1);
+        assert_eq!((!Colour::White).sign(), -Colour::White.sign());
+    }
+}
@@ -3,15 +3,24 @@
 mod board;
 mod chessmove;
 mod colour;
+#[cfg(feature = "parallel")]
+mod parallel;
 mod piece;
+mod positions;
 mod square;
 
-pub use board::{Board, PieceIndex, Zobrist};
-pub use chessmove::{Move, MoveType};
+pub use board::{
+    Bitlist, Board, BoardSnapshot, CastleRights, CastleSide, FenError, MoveError, PieceIndex,
+    Zobrist,
+};
+pub use chessmove::{Move, MoveList, MoveType};
 pub use colour::Colour;
+#[cfg(feature = "parallel")]
+pub use parallel::{perft_parallel, PerftTable};
 pub use piece::Piece;
+pub use positions::BENCH_FENS;
 pub use square::Square;
-use tinyvec::ArrayVec;
+use std::ops::AddAssign;
 
 /// Count the number of legal chess positions after N moves.
 #[inline]
@@ -20,15 +29,11 @@ pub fn perft(board: &Board, zobrist: &Zobrist, depth: u32) -> u64 {
     if depth == 0 {
         1
     } else if depth == 1 {
-        let moves: [Move; 256] = [Move::default(); 256];
-        let mut moves = ArrayVec::from(moves);
-        moves.set_len(0);
+        let mut moves = MoveList::new();
         board.generate(&mut moves);
         moves.len() as u64
     } else {
-        let moves: [Move; 256] = [Move::default(); 256];
-        let mut moves = ArrayVec::from(moves);
-        moves.set_len(0);
+        let mut moves = MoveList::new();
         board.generate(&mut moves);
 
         let mut count = 0;
@@ -40,9 +45,161 @@ pub fn perft(board: &Board, zobrist: &Zobrist, depth: u32) -> u64 {
     }
 }
 
+/// Counts of the kinds of leaf moves in a perft tree, to check a move generator against the
+/// published breakdowns, which catch bugs that leave the node totals right.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    /// Captures, including en-passant captures and capturing promotions
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    /// Promotions, capturing or not
+    pub promotions: u64,
+    pub checks: u64,
+    pub checkmates: u64,
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.nodes += rhs.nodes;
+        self.captures += rhs.captures;
+        self.en_passants += rhs.en_passants;
+        self.castles += rhs.castles;
+        self.promotions += rhs.promotions;
+        self.checks += rhs.checks;
+        self.checkmates += rhs.checkmates;
+    }
+}
+
+/// Count the legal chess positions after N moves, like `perft`, classifying the moves made at
+/// the last ply. Every leaf is made to test for check, so this is much slower than `perft`.
+#[must_use]
+pub fn perft_stats(board: &Board, zobrist: &Zobrist, depth: u32) -> PerftStats {
+    let mut stats = PerftStats::default();
+    if depth == 0 {
+        stats.nodes = 1;
+        return stats;
+    }
+    let mut moves = MoveList::new();
+    board.generate(&mut moves);
+
+    for m in moves {
+        let child = board.make(m, zobrist);
+        if depth > 1 {
+            stats += perft_stats(&child, zobrist, depth - 1);
+            continue;
+        }
+        stats.nodes += 1;
+        match m.kind {
+            MoveType::Capture => stats.captures += 1,
+            MoveType::EnPassant => {
+                stats.captures += 1;
+                stats.en_passants += 1;
+            }
+            MoveType::Castle => stats.castles += 1,
+            MoveType::Promotion => stats.promotions += 1,
+            MoveType::CapturePromotion => {
+                stats.captures += 1;
+                stats.promotions += 1;
+            }
+            MoveType::Normal | MoveType::DoublePush => {}
+        }
+        if child.in_check() {
+            stats.checks += 1;
+            if child.is_checkmate() {
+                stats.checkmates += 1;
+            }
+        }
+    }
+    stats
+}
+
+/// Count the legal positions N moves after each legal move, in the order they are generated.
+///
+/// Comparing the counts against another move generator's finds where this one goes wrong.
+/// They sum to `perft` at the same depth; a depth of zero has no moves to divide by.
+#[must_use]
+pub fn perft_divide(board: &Board, zobrist: &Zobrist, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mut moves = MoveList::new();
+    board.generate(&mut moves);
+    moves
+        .into_iter()
+        .map(|m| (m, perft(&board.make(m, zobrist), zobrist, depth - 1)))
+        .collect()
+}
+
 #[cfg(test)]
 mod perft {
-    use crate::{perft, Board, Zobrist};
+    use crate::{perft, perft_divide, perft_stats, Board, PerftStats, Zobrist};
+
+    #[test]
+    fn perft_stats_breakdown() {
+        let zobrist = Zobrist::new();
+        let startpos = Board::startpos(&zobrist);
+        let expected = PerftStats {
+            nodes: 197_281,
+            captures: 1576,
+            checks: 469,
+            checkmates: 8,
+            ..PerftStats::default()
+        };
+        assert_eq!(perft_stats(&startpos, &zobrist, 4), expected);
+
+        let kiwipete = Board::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &zobrist,
+        )
+        .unwrap();
+        let expected = PerftStats {
+            nodes: 97862,
+            captures: 17102,
+            en_passants: 45,
+            castles: 3162,
+            promotions: 0,
+            checks: 993,
+            checkmates: 1,
+        };
+        assert_eq!(perft_stats(&kiwipete, &zobrist, 3), expected);
+        let expected = PerftStats {
+            nodes: 4_085_603,
+            captures: 757_163,
+            en_passants: 1929,
+            castles: 128_013,
+            promotions: 15172,
+            checks: 25523,
+            checkmates: 43,
+        };
+        assert_eq!(perft_stats(&kiwipete, &zobrist, 4), expected);
+    }
+
+    #[test]
+    fn divide() {
+        let zobrist = Zobrist::new();
+        let startpos = Board::startpos(&zobrist);
+        let counts = perft_divide(&startpos, &zobrist, 3);
+        assert_eq!(counts.len(), 20);
+        assert_eq!(counts.iter().map(|&(_, n)| n).sum::<u64>(), 8902);
+        let count = |s: &str| {
+            let m = startpos.parse_move(s).unwrap();
+            counts.iter().find(|&&(other, _)| other == m).unwrap().1
+        };
+        assert_eq!(count("e2e4"), 600);
+        assert_eq!(count("b1c3"), 440);
+        assert_eq!(count("f2f3"), 380);
+
+        let kiwipete = Board::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &zobrist,
+        )
+        .unwrap();
+        let counts = perft_divide(&kiwipete, &zobrist, 3);
+        assert_eq!(counts.iter().map(|&(_, n)| n).sum::<u64>(), 97862);
+        assert!(perft_divide(&kiwipete, &zobrist, 0).is_empty());
+    }
 
     #[test]
     fn perft_test1() {
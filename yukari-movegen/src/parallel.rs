@@ -0,0 +1,137 @@
+use crate::{perft, Board, MoveList, Zobrist};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of entries in the perft hash table (16 bytes each).
+const TABLE_ENTRIES: usize = 1 << 20;
+
+/// A lockless hash table of perft subtree counts, shared between threads.
+///
+/// Each entry stores `(hash ^ data, data)`, where `data` packs the node count and the remaining depth.
+/// A torn write from a racing thread fails the key check rather than returning a bad count.
+/// Counts stay valid between calls, so one table can serve many of them.
+pub struct PerftTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl Default for PerftTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PerftTable {
+    /// Allocate an empty table of `TABLE_ENTRIES` entries.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut entries = Vec::with_capacity(TABLE_ENTRIES);
+        entries.resize_with(TABLE_ENTRIES, || [AtomicU64::new(0), AtomicU64::new(0)]);
+        Self { entries }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn entry(&self, hash: u64) -> &[AtomicU64; 2] {
+        &self.entries[(hash as usize) & (TABLE_ENTRIES - 1)]
+    }
+
+    fn get(&self, hash: u64, depth: u32) -> Option<u64> {
+        let entry = self.entry(hash);
+        let data = entry[1].load(Ordering::Relaxed);
+        let key = entry[0].load(Ordering::Relaxed) ^ data;
+        if key == hash && data & 0xFF == u64::from(depth) {
+            return Some(data >> 8);
+        }
+        None
+    }
+
+    fn set(&self, hash: u64, depth: u32, count: u64) {
+        let entry = self.entry(hash);
+        let data = (count << 8) | u64::from(depth);
+        entry[0].store(hash ^ data, Ordering::Relaxed);
+        entry[1].store(data, Ordering::Relaxed);
+    }
+}
+
+fn perft_hashed(board: &Board, zobrist: &Zobrist, depth: u32, table: &PerftTable) -> u64 {
+    if depth <= 1 {
+        return perft(board, zobrist, depth);
+    }
+
+    if let Some(count) = table.get(board.hash(), depth) {
+        return count;
+    }
+
+    let mut moves = MoveList::new();
+    board.generate(&mut moves);
+
+    let mut count = 0;
+    for m in moves {
+        let board = board.make(m, zobrist);
+        count += perft_hashed(&board, zobrist, depth - 1, table);
+    }
+
+    table.set(board.hash(), depth, count);
+    count
+}
+
+/// Count the number of legal chess positions after N moves, splitting the root moves across the
+/// threads of the current rayon pool.
+///
+/// Subtree counts are shared between threads through `table`, keyed on position hash and depth,
+/// so transpositions are only counted once. Run it inside `ThreadPool::install` to choose the
+/// number of threads.
+#[must_use]
+pub fn perft_parallel(board: &Board, zobrist: &Zobrist, depth: u32, table: &PerftTable) -> u64 {
+    if depth <= 1 {
+        return perft(board, zobrist, depth);
+    }
+
+    let mut moves = MoveList::new();
+    board.generate(&mut moves);
+
+    moves
+        .par_iter()
+        .map(|m| {
+            let board = board.make(*m, zobrist);
+            perft_hashed(&board, zobrist, depth - 1, table)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{perft_parallel, PerftTable};
+    use crate::{perft, Board, Zobrist};
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let zobrist = Zobrist::new();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        // One table for every position, as its counts are keyed on the position
+        let table = PerftTable::new();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/1R2K2R b Kkq - 0 1",
+            "8/8/8/8/8/8/6k1/4K2R w K - 0 1",
+            "3k4/3pp3/8/8/8/8/3PP3/3K4 w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4PpP1/1BNP4/PPP2P1P/3R1RK1 b - g3 0 14",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            for depth in 0..=4 {
+                assert_eq!(
+                    pool.install(|| perft_parallel(&board, &zobrist, depth, &table)),
+                    perft(&board, &zobrist, depth),
+                    "perft mismatch for {fen} at depth {depth}"
+                );
+            }
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use std::fmt::{self, Display};
+
+use crate::colour::Colour;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Piece {
     Pawn,
@@ -7,3 +11,125 @@ pub enum Piece {
     Queen,
     King,
 }
+
+impl Piece {
+    /// Every piece type, in the order of their discriminants.
+    pub const ALL: [Self; 6] = [
+        Self::Pawn,
+        Self::Knight,
+        Self::Bishop,
+        Self::Rook,
+        Self::Queen,
+        Self::King,
+    ];
+
+    /// Parse an uppercase piece letter, as used in SAN.
+    #[must_use]
+    pub const fn from_char(c: char) -> Option<Self> {
+        match c {
+            'P' => Some(Self::Pawn),
+            'N' => Some(Self::Knight),
+            'B' => Some(Self::Bishop),
+            'R' => Some(Self::Rook),
+            'Q' => Some(Self::Queen),
+            'K' => Some(Self::King),
+            _ => None,
+        }
+    }
+
+    /// The uppercase letter of this piece, as used in SAN.
+    #[must_use]
+    pub const fn to_char(self) -> char {
+        match self {
+            Self::Pawn => 'P',
+            Self::Knight => 'N',
+            Self::Bishop => 'B',
+            Self::Rook => 'R',
+            Self::Queen => 'Q',
+            Self::King => 'K',
+        }
+    }
+
+    /// Parse a FEN piece letter: uppercase for white, lowercase for black.
+    #[must_use]
+    pub const fn from_coloured_char(c: char) -> Option<(Self, Colour)> {
+        let colour = if c.is_ascii_uppercase() {
+            Colour::White
+        } else {
+            Colour::Black
+        };
+        match Self::from_char(c.to_ascii_uppercase()) {
+            Some(piece) => Some((piece, colour)),
+            None => None,
+        }
+    }
+
+    /// The FEN letter of this piece: uppercase for white, lowercase for black.
+    #[must_use]
+    pub const fn to_coloured_char(self, colour: Colour) -> char {
+        match colour {
+            Colour::White => self.to_char(),
+            Colour::Black => self.to_char().to_ascii_lowercase(),
+        }
+    }
+}
+
+/// Parses a piece letter of either case, ignoring its colour.
+impl TryFrom<char> for Piece {
+    type Error = ();
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        Self::from_coloured_char(c)
+            .map(|(piece, _)| piece)
+            .ok_or(())
+    }
+}
+
+impl From<Piece> for char {
+    fn from(piece: Piece) -> Self {
+        piece.to_char()
+    }
+}
+
+impl Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Piece;
+    use crate::colour::Colour;
+
+    #[test]
+    fn char_round_trip() {
+        for piece in Piece::ALL {
+            assert_eq!(Piece::from_char(piece.to_char()), Some(piece));
+            for colour in Colour::BOTH {
+                let c = piece.to_coloured_char(colour);
+                assert_eq!(Piece::from_coloured_char(c), Some((piece, colour)));
+            }
+        }
+        assert_eq!(Piece::Knight.to_coloured_char(Colour::Black), 'n');
+        // SAN piece letters are uppercase only, so that `b` can mean the b-file.
+        assert_eq!(Piece::from_char('b'), None);
+        assert_eq!(Piece::from_char('x'), None);
+        assert!(Piece::from_coloured_char('x').is_none());
+        assert!(Piece::from_coloured_char('1').is_none());
+    }
+
+    #[test]
+    fn conversions() {
+        for (i, piece) in Piece::ALL.into_iter().enumerate() {
+            assert_eq!(piece as usize, i);
+            let c = char::from(piece);
+            assert_eq!(piece.to_string(), c.to_string());
+            assert_eq!(Piece::try_from(c), Ok(piece));
+            assert_eq!(Piece::try_from(c.to_ascii_lowercase()), Ok(piece));
+        }
+        assert_eq!(Piece::try_from('x'), Err(()));
+        assert_eq!(Piece::try_from(' '), Err(()));
+        assert_eq!(Piece::try_from('1'), Err(()));
+    }
+}
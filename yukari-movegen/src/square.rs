@@ -353,7 +353,7 @@ impl Rank {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum File {
     A,
     B,
@@ -454,6 +454,16 @@ impl From<Square> for Square16x8 {
     }
 }
 
+/// Look up the `Direction` of a vector between two 16x8 squares.
+#[inline]
+fn direction_of_vector(vector: usize) -> Option<Direction> {
+    if cfg!(feature = "safe-fallbacks") {
+        return DIRECTIONS[vector];
+    }
+    // SAFETY: vectors between on-board squares are always within the table.
+    unsafe { *DIRECTIONS.get_unchecked(vector) }
+}
+
 impl Square16x8 {
     pub const fn from_square(square: Square) -> Self {
         let square = square.into_inner();
@@ -490,7 +500,7 @@ impl Square16x8 {
     /// Return the `Direction` between two squares, if any exists.
     #[must_use]
     pub(crate) fn direction(self, dest: Self) -> Option<Direction> {
-        unsafe { *DIRECTIONS.get_unchecked(self.vector(dest)) }
+        direction_of_vector(self.vector(dest))
     }
 
     /// An iterator over the squares in a `Direction`.
@@ -564,18 +574,17 @@ impl FromStr for Square {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, ()> {
-        if !s.is_ascii() {
+        let &[file, rank] = s.as_bytes() else {
             return Err(());
-        }
-        let chars = s.as_bytes();
-        if !(b'a'..=b'h').contains(&chars[0]) {
+        };
+        if !(b'a'..=b'h').contains(&file) {
             return Err(());
         }
-        if !(b'1'..=b'8').contains(&chars[1]) {
+        if !(b'1'..=b'8').contains(&rank) {
             return Err(());
         }
-        let file = chars[0] - b'a';
-        let rank = chars[1] - b'1';
+        let file = file - b'a';
+        let rank = rank - b'1';
         // SAFETY: values are constrained above and the "plus one" ensures this will never be zero.
         let square = unsafe { NonZeroU8::new_unchecked((8 * rank + file) + 1) };
         Ok(Self(square))
@@ -609,8 +618,15 @@ impl Square {
     /// # Safety
     ///
     /// `sq` must be in the range 0-63.
+    ///
+    /// # Panics
+    ///
+    /// With the `safe-fallbacks` feature, panics if `sq` is out of range.
     #[must_use]
     pub const unsafe fn from_u8_unchecked(sq: u8) -> Self {
+        if cfg!(feature = "safe-fallbacks") {
+            assert!(sq < 64, "square out of range");
+        }
         Self(NonZeroU8::new_unchecked(sq + 1))
     }
 
@@ -621,13 +637,26 @@ impl Square {
         (self.0.get() - 1) & 63
     }
 
+    /// Return the number of king moves between two squares.
+    #[must_use]
+    pub const fn distance(self, other: Self) -> u8 {
+        let (from, to) = (self.into_inner(), other.into_inner());
+        let ranks = (from >> 3).abs_diff(to >> 3);
+        let files = (from & 7).abs_diff(to & 7);
+        if ranks > files {
+            ranks
+        } else {
+            files
+        }
+    }
+
     /// Return the `Direction` between two squares, if any exists.
     #[must_use]
     pub fn direction(self, dest: Self) -> Option<Direction> {
         let dest = Square16x8::from(dest);
         let from = Square16x8::from(self);
 
-        unsafe { *DIRECTIONS.get_unchecked(from.vector(dest)) }
+        direction_of_vector(from.vector(dest))
     }
 
     /// Return the `Square` in a given `Direction`, if one exists.
@@ -721,7 +750,7 @@ impl Square {
 
     #[must_use]
     pub const fn flip(self) -> Self {
-        unsafe { Self::from_u8_unchecked(self.into_inner() ^ 56) }
+        unsafe { Self::from_u8_unchecked(self.into_inner() ^ 0o70) }
     }
 }
 
@@ -920,3 +949,40 @@ impl Iterator for KingIter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Direction, Square};
+
+    #[test]
+    fn square_conversions() {
+        for sq in 0_u8..64 {
+            // SAFETY: sq is always in range.
+            let square = unsafe { Square::from_u8_unchecked(sq) };
+            assert_eq!(square.into_inner(), sq);
+            assert_eq!(Square::from_str(&square.to_string()), Ok(square));
+            assert_eq!(square.flip().flip(), square);
+        }
+        assert_eq!(Square::from_str("e9"), Err(()));
+        assert_eq!(Square::from_str("i1"), Err(()));
+        assert_eq!(Square::from_str("e"), Err(()));
+    }
+
+    #[test]
+    fn square_geometry() {
+        let square = |s| Square::from_str(s).unwrap();
+        assert_eq!(
+            square("a1").direction(square("h8")),
+            Some(Direction::NorthEast)
+        );
+        assert_eq!(square("e4").direction(square("e1")), Some(Direction::South));
+        assert_eq!(square("a1").direction(square("c4")), None);
+        assert_eq!(square("a1").distance(square("h8")), 7);
+        assert_eq!(square("a1").knight_attacks().count(), 2);
+        assert_eq!(square("d4").knight_attacks().count(), 8);
+        assert_eq!(square("h8").king_attacks().count(), 3);
+        assert_eq!(square("h8").travel(Direction::North), None);
+    }
+}
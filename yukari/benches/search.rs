@@ -1,6 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use tinyvec::ArrayVec;
-use yukari::Search;
+use yukari::{Search, TranspositionTable};
 use yukari_movegen::{Board, Zobrist};
 
 pub fn search_bench(c: &mut Criterion) {
@@ -18,7 +18,8 @@ pub fn search_bench(c: &mut Criterion) {
     group.noise_threshold(0.025);
 
     let nodes = {
-        let mut s = Search::new(None, &zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
         let mut pv = ArrayVec::new();
         let mut keystack = Vec::new();
         s.search_root(&kiwipete, 3, &mut pv, &mut keystack);
@@ -27,7 +28,8 @@ pub fn search_bench(c: &mut Criterion) {
 
     group.throughput(Throughput::Elements(nodes));
     group.bench_with_input("kiwipete-3", &kiwipete, |b, board| {
-        let mut s = Search::new(None, &zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
         let mut pv = ArrayVec::new();
         let mut keystack = Vec::new();
         b.iter(|| {
@@ -36,7 +38,8 @@ pub fn search_bench(c: &mut Criterion) {
     });
 
     let nodes = {
-        let mut s = Search::new(None, &zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
         let mut pv = ArrayVec::new();
         let mut keystack = Vec::new();
         s.search_root(&kiwipete, 4, &mut pv, &mut keystack);
@@ -45,7 +48,8 @@ pub fn search_bench(c: &mut Criterion) {
 
     group.throughput(Throughput::Elements(nodes));
     group.bench_with_input("kiwipete-4", &kiwipete, |b, board| {
-        let mut s = Search::new(None, &zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
         let mut pv = ArrayVec::new();
         let mut keystack = Vec::new();
         b.iter(|| {
@@ -0,0 +1,21 @@
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    // Identify the exact commit in debug output; builds from a source tarball have no git history.
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| "unknown".to_string(), |hash| hash.trim().to_string());
+    println!("cargo:rustc-env=YUKARI_GIT_HASH={hash}");
+
+    // A missing path would make cargo rerun this on every build.
+    for path in ["../.git/HEAD", "../.git/index"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+}
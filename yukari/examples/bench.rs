@@ -1,5 +1,5 @@
 use tinyvec::ArrayVec;
-use yukari::Search;
+use yukari::{Search, TranspositionTable};
 use yukari_movegen::{Board, Zobrist};
 
 use std::time::Instant;
@@ -63,11 +63,11 @@ fn main() {
     for fen in fens {
         let zobrist = Zobrist::new();
         let board = Board::from_fen(fen, &zobrist).unwrap();
-        let mut s = Search::new(None, &zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
         let start = Instant::now();
         let mut keystack = Vec::new();
         let mut pv = ArrayVec::new();
-        pv.set_len(0);
         let score = s.search_root(&board, 8, &mut pv, &mut keystack);
         let now = Instant::now().duration_since(start);
         print!(
@@ -1,5 +1,5 @@
 use tinyvec::ArrayVec;
-use yukari::Search;
+use yukari::{Search, TranspositionTable};
 use yukari_movegen::{Board, Zobrist};
 
 use std::time::Instant;
@@ -19,12 +19,12 @@ fn main() {
     )
     .unwrap();
 
-    let mut s = Search::new(None, &zobrist);
+    let mut tt = TranspositionTable::default();
+    let mut s = Search::new(None, &zobrist, &mut tt);
     let start = Instant::now();
     for depth in 1..=10 {
         let mut keystack = Vec::new();
         let mut pv = ArrayVec::new();
-        pv.set_len(0);
         let score = s.search_root(&board, depth, &mut pv, &mut keystack);
         let now = Instant::now().duration_since(start);
         print!(
@@ -50,4 +50,5 @@ fn main() {
         ((s.nodes() + s.qnodes()) as f64).powf(0.1)
     );
     println!("# Nullmove success: {:.3}%", s.nullmove_success());
+    println!("# Seldepth: {}", s.seldepth());
 }
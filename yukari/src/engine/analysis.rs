@@ -0,0 +1,214 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use tinyvec::ArrayVec;
+use yukari_movegen::{Board, FenError, Move, Zobrist};
+
+use crate::{mate_in, Search, TranspositionTable};
+
+/// The result of analysing a single position
+#[derive(Clone)]
+pub struct Analysis {
+    /// The position, as given
+    pub fen: String,
+    /// Score of the last completed depth, from the side to move's point of view
+    pub score: i32,
+    /// Last completed depth
+    pub depth: i32,
+    /// Nodes searched, including quiescence
+    pub nodes: u64,
+    /// Time spent searching
+    pub time: Duration,
+    /// Principal variation of the last completed depth
+    pub pv: Vec<Move>,
+}
+
+impl Analysis {
+    /// Search a position to `depth`, stopping early if `time` runs out.
+    /// Results from earlier positions in `tt` are reused where they apply.
+    ///
+    /// # Errors
+    /// Returns a `FenError` if the position cannot be parsed.
+    pub fn run(
+        fen: &str,
+        depth: i32,
+        time: Option<Duration>,
+        zobrist: &Zobrist,
+        tt: &mut TranspositionTable,
+    ) -> Result<Self, FenError> {
+        let board = Board::from_fen(fen, zobrist)?;
+        let start = Instant::now();
+        let stop_after = time.map(|time| start + time);
+        let mut s = Search::new(stop_after, zobrist, tt);
+        let mut analysis = Self {
+            fen: fen.to_string(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            time: Duration::ZERO,
+            pv: Vec::new(),
+        };
+
+        for depth in 1..=depth {
+            let mut pv = ArrayVec::new();
+            let mut keystack = vec![board.hash()];
+            let score = s.search_root(&board, depth, &mut pv, &mut keystack);
+            // A search that ran out of time is incomplete, so keep the last depth.
+            if stop_after.is_some_and(|stop_after| Instant::now() >= stop_after) {
+                break;
+            }
+            analysis.score = score;
+            analysis.depth = depth;
+            analysis.pv = pv.to_vec();
+        }
+
+        analysis.nodes = s.nodes() + s.qnodes();
+        analysis.time = start.elapsed();
+        Ok(analysis)
+    }
+
+    /// Format the analysis as a single-line JSON object
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = write!(json, "{{\"fen\":{}", json_string(&self.fen));
+        match self.pv.first() {
+            Some(m) => {
+                let _ = write!(json, ",\"bestmove\":\"{m}\"");
+            }
+            None => json.push_str(",\"bestmove\":null"),
+        }
+        match mate_in(self.score) {
+            Some(mate) => {
+                let _ = write!(json, ",\"score\":{{\"mate\":{mate}}}");
+            }
+            None => {
+                let _ = write!(json, ",\"score\":{{\"cp\":{}}}", self.score);
+            }
+        }
+        let _ = write!(
+            json,
+            ",\"depth\":{},\"nodes\":{},\"time\":{}",
+            self.depth,
+            self.nodes,
+            self.time.as_millis()
+        );
+        json.push_str(",\"pv\":[");
+        for (i, m) in self.pv.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "\"{m}\"");
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+/// Extract the position from a FEN or EPD line, dropping any EPD operations
+#[must_use]
+pub fn epd_position(line: &str) -> String {
+    let mut fields = line.split_ascii_whitespace();
+    let mut position: Vec<&str> = fields.by_ref().take(4).collect();
+    // FENs carry move counters where EPDs carry operations.
+    position.extend(
+        fields
+            .take(2)
+            .take_while(|field| field.bytes().all(|b| b.is_ascii_digit())),
+    );
+    position.join(" ")
+}
+
+/// Format a position that could not be analysed as a single-line JSON object
+#[must_use]
+pub fn error_json(fen: &str, error: &FenError) -> String {
+    format!(
+        "{{\"fen\":{},\"error\":{}}}",
+        json_string(fen),
+        json_string(&error.to_string())
+    )
+}
+
+/// Quote and escape a string for JSON
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use yukari_movegen::{FenError, Zobrist};
+
+    use super::{epd_position, error_json, json_string, Analysis};
+    use crate::TranspositionTable;
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("id \"a\\b\"\n\u{1}"),
+            "\"id \\\"a\\\\b\\\"\\n\\u0001\""
+        );
+    }
+
+    #[test]
+    fn epd_lines() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        assert_eq!(epd_position(fen), fen);
+        assert_eq!(
+            epd_position("4k3/8/8/8/8/8/8/R3K3 w Q - bm Ra8+; id \"test\";"),
+            "4k3/8/8/8/8/8/8/R3K3 w Q -"
+        );
+        assert_eq!(epd_position("  8/8 w "), "8/8 w");
+    }
+
+    #[test]
+    fn analysis_json() {
+        let zobrist = Zobrist::new();
+        let mut tt = TranspositionTable::default();
+        let fen = "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1";
+        let analysis = Analysis::run(fen, 3, None, &zobrist, &mut tt).unwrap();
+        let json = analysis.to_json();
+        assert!(json.starts_with(
+            "{\"fen\":\"4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1\",\"bestmove\":\"d2d5\",\"score\":{\"cp\":"
+        ));
+        assert!(json.contains(",\"depth\":3,"));
+        assert!(json.contains(",\"pv\":[\"d2d5\","));
+        assert!(json.ends_with("\"]}"));
+        assert!(!json.contains('\n'));
+
+        // Mate in one is reported as a mate score.
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        let analysis = Analysis::run(fen, 3, None, &zobrist, &mut tt).unwrap();
+        assert!(analysis.to_json().contains("\"score\":{\"mate\":1}"));
+    }
+
+    #[test]
+    fn error_objects() {
+        let zobrist = Zobrist::new();
+        let mut tt = TranspositionTable::default();
+        let error = Analysis::run("not a fen", 3, None, &zobrist, &mut tt)
+            .err()
+            .unwrap();
+        assert_eq!(error, FenError::BadPlacement);
+        assert_eq!(
+            error_json("not a fen", &error),
+            format!("{{\"fen\":\"not a fen\",\"error\":\"{error}\"}}")
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt::Display;
+use std::io::{self, Stdout, Write};
+
+/// Where the engine's replies to the GUI go, a line at a time.
+///
+/// Every line is flushed as soon as it is written, whatever buffering the writer does, so a
+/// GUI reading through a pipe sees a move as soon as it is made rather than thinking the
+/// engine has hung while it loses on time.
+pub struct Comms<W: Write = Stdout> {
+    out: W,
+}
+
+impl Comms {
+    /// Replies on standard output.
+    #[must_use]
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> Comms<W> {
+    #[must_use]
+    pub const fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Send one line to the GUI, flushed through the writer.
+    ///
+    /// # Panics
+    /// Panics if the line cannot be written, as `println!` does: there is no GUI left to
+    /// play for.
+    pub fn send(&mut self, line: impl Display) {
+        writeln!(self.out, "{line}")
+            .and_then(|()| self.out.flush())
+            .expect("failed writing to the GUI");
+    }
+
+    /// The writer the lines go to.
+    pub const fn get_ref(&self) -> &W {
+        &self.out
+    }
+}
+
+impl Default for Comms {
+    fn default() -> Self {
+        Self::stdout()
+    }
+}
+
+impl Clone for Comms {
+    fn clone(&self) -> Self {
+        Self::stdout()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Comms;
+    use std::io::BufWriter;
+
+    #[test]
+    fn replies_are_flushed() {
+        // The buffer would hold the move until it filled up, were it not flushed.
+        let mut comms = Comms::new(BufWriter::new(Vec::new()));
+        comms.send("move e2e4");
+        assert_eq!(comms.get_ref().get_ref(), b"move e2e4\n");
+        comms.send(format_args!("pong {}", 3));
+        assert_eq!(comms.get_ref().get_ref(), b"move e2e4\npong 3\n");
+    }
+}
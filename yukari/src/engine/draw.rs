@@ -0,0 +1,138 @@
+/// Number of full moves without progress before a game counts as drawish.
+const NO_PROGRESS_MOVES: usize = 30;
+/// Largest score (in centipawns, either way) that still counts as level.
+const LEVEL_MARGIN: i32 = 15;
+
+/// A single ply of the game, as the draw adjudicator sees it.
+#[derive(Clone, Copy, Debug)]
+struct Ply {
+    /// Our search score after this ply, if we made it
+    score: Option<i32>,
+    /// Whether this ply was a pawn move or a capture
+    irreversible: bool,
+}
+
+/// Tracks the game history to spot long games where neither side is making progress
+#[derive(Clone, Debug)]
+pub struct DrawAdjudicator {
+    /// Every ply of the game so far
+    history: Vec<Ply>,
+    /// Number of full moves without progress needed to count as drawish
+    moves: usize,
+    /// Largest score that still counts as level
+    margin: i32,
+}
+
+impl DrawAdjudicator {
+    /// Construct a new adjudicator, needing `moves` full moves of level scores without pawn moves or captures
+    #[must_use]
+    pub const fn new(moves: usize, margin: i32) -> Self {
+        Self {
+            history: Vec::new(),
+            moves,
+            margin,
+        }
+    }
+
+    /// Change the number of full moves and the score margin, keeping the game history
+    pub fn set_limits(&mut self, moves: usize, margin: i32) {
+        self.moves = moves;
+        self.margin = margin;
+    }
+
+    /// Forget the game history, for a new game or position
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    /// Record a ply of the game; `score` is our search score if the move was ours
+    pub fn push(&mut self, score: Option<i32>, irreversible: bool) {
+        self.history.push(Ply {
+            score,
+            irreversible,
+        });
+    }
+
+    /// Check if the game is drawish: the last N full moves had no pawn moves or captures,
+    /// and every score we gave in that time was level
+    #[must_use]
+    pub fn is_drawish(&self) -> bool {
+        let plies = 2 * self.moves;
+        if plies == 0 || self.history.len() < plies {
+            return false;
+        }
+
+        let recent = &self.history[self.history.len() - plies..];
+        if recent.iter().any(|ply| ply.irreversible) {
+            return false;
+        }
+
+        // We have to have been thinking about the game to judge it.
+        let mut scores = recent.iter().filter_map(|ply| ply.score).peekable();
+        scores.peek().is_some() && scores.all(|score| score.abs() <= self.margin)
+    }
+}
+
+impl Default for DrawAdjudicator {
+    fn default() -> Self {
+        Self::new(NO_PROGRESS_MOVES, LEVEL_MARGIN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DrawAdjudicator;
+
+    /// Play `moves` full moves of shuffling, with us scoring every move as `score`.
+    fn shuffle(adjudicator: &mut DrawAdjudicator, moves: usize, score: i32) {
+        for _ in 0..moves {
+            adjudicator.push(Some(score), false);
+            adjudicator.push(None, false);
+        }
+    }
+
+    #[test]
+    fn offers_after_shuffling() {
+        let mut adjudicator = DrawAdjudicator::new(30, 15);
+        shuffle(&mut adjudicator, 29, 5);
+        assert!(!adjudicator.is_drawish());
+        shuffle(&mut adjudicator, 1, -15);
+        assert!(adjudicator.is_drawish());
+    }
+
+    #[test]
+    fn progress_resets() {
+        let mut adjudicator = DrawAdjudicator::new(30, 15);
+        shuffle(&mut adjudicator, 30, 0);
+        // The opponent pushes a pawn.
+        adjudicator.push(Some(0), false);
+        adjudicator.push(None, true);
+        assert!(!adjudicator.is_drawish());
+        shuffle(&mut adjudicator, 29, 0);
+        assert!(!adjudicator.is_drawish());
+        shuffle(&mut adjudicator, 1, 0);
+        assert!(adjudicator.is_drawish());
+    }
+
+    #[test]
+    fn unlevel_scores() {
+        let mut adjudicator = DrawAdjudicator::new(30, 15);
+        shuffle(&mut adjudicator, 29, 0);
+        shuffle(&mut adjudicator, 1, 16);
+        assert!(!adjudicator.is_drawish());
+
+        // Without any of our scores, we cannot tell.
+        let mut adjudicator = DrawAdjudicator::new(30, 15);
+        for _ in 0..60 {
+            adjudicator.push(None, false);
+        }
+        assert!(!adjudicator.is_drawish());
+
+        // The configured number of moves is respected.
+        let mut adjudicator = DrawAdjudicator::new(10, 15);
+        shuffle(&mut adjudicator, 10, 0);
+        assert!(adjudicator.is_drawish());
+        adjudicator.clear();
+        assert!(!adjudicator.is_drawish());
+    }
+}
@@ -1,2 +1,14 @@
+mod analysis;
+mod comms;
+mod draw;
+mod options;
+mod selfplay;
 mod tc;
+mod version;
+pub use analysis::*;
+pub use comms::*;
+pub use draw::*;
+pub use options::*;
+pub use selfplay::*;
 pub use tc::*;
+pub use version::*;
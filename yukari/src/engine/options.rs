@@ -0,0 +1,433 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use crate::{SearchParams, DEFAULT_HASH_MB};
+
+/// An error from setting an engine option
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionError {
+    /// There is no option with that name
+    Unknown,
+    /// The value could not be parsed or is out of range
+    BadValue,
+}
+
+impl Display for OptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "unknown option"),
+            Self::BadValue => write!(f, "bad option value"),
+        }
+    }
+}
+
+/// A numeric option and its allowed range, as advertised to the GUI
+struct Spin {
+    name: &'static str,
+    min: i32,
+    max: i32,
+}
+
+/// Every numeric option the engine understands
+const SPINS: [Spin; 5] = [
+    Spin {
+        name: "Random Margin",
+        min: 0,
+        max: 1000,
+    },
+    Spin {
+        name: "Draw Moves",
+        min: 1,
+        max: 500,
+    },
+    Spin {
+        name: "Draw Margin",
+        min: 0,
+        max: 1000,
+    },
+    Spin {
+        name: "MultiPV",
+        min: 1,
+        max: 64,
+    },
+    Spin {
+        name: "Move Overhead",
+        min: 0,
+        max: 5000,
+    },
+];
+
+/// Every on/off option the engine understands
+const CHECKS: [&str; 3] = ["Learn", "Null Move", "Futility Pruning"];
+
+/// Where the learn file is kept unless the GUI says otherwise
+const DEFAULT_LEARN_FILE: &str = "yukari.learn";
+
+/// Largest transposition table the GUI may ask for, in megabytes
+const MAX_HASH_MB: usize = 65536;
+/// Most search threads the GUI may ask for
+const MAX_THREADS: usize = 256;
+
+/// User-settable engine options, shared between protocols
+///
+/// Besides the advertised options, this holds the settings xboard negotiates with its own
+/// commands (`memory`, `cores` and `egtpath`), set under their UCI names so that every
+/// protocol goes through `apply`, and the search parameters, which can be set by name for
+/// tuning but are not advertised.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// How far below the best move (in centipawns) a move may score and still be played in random mode
+    pub random_margin: i32,
+    /// Number of full moves without progress before offering a draw
+    pub draw_moves: usize,
+    /// Largest score (in centipawns, either way) that still counts as level for draw offers
+    pub draw_margin: i32,
+    /// Number of best lines to search and report
+    pub multi_pv: usize,
+    /// Transposition table size, in megabytes
+    pub hash_mb: usize,
+    /// Number of search threads; the search is single-threaded for now, so this is only recorded
+    pub threads: usize,
+    /// Where to find Syzygy tablebases, once they are supported
+    pub syzygy_path: Option<PathBuf>,
+    /// Whether to remember the positions of each game and the moves played in them
+    pub learn: bool,
+    /// Where the remembered positions are kept between games
+    pub learn_path: PathBuf,
+    /// Whether the search prunes with null moves; analysis may want the slower, surer search
+    pub null_move: bool,
+    /// Whether the search prunes one ply from the horizon with a futility margin
+    pub futility: bool,
+    /// Search reductions, margins and time use, settable for tuning but not advertised
+    pub search: SearchParams,
+}
+
+/// Parse a count in `min..=max`
+fn parse_count(value: &str, min: usize, max: usize) -> Result<usize, OptionError> {
+    let value = value
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| OptionError::BadValue)?;
+    if !(min..=max).contains(&value) {
+        return Err(OptionError::BadValue);
+    }
+    Ok(value)
+}
+
+impl Options {
+    /// Look up the current value of an option
+    fn value(&self, name: &str) -> Option<i32> {
+        match name {
+            "Random Margin" => Some(self.random_margin),
+            "Draw Moves" => i32::try_from(self.draw_moves).ok(),
+            "Draw Margin" => Some(self.draw_margin),
+            "MultiPV" => i32::try_from(self.multi_pv).ok(),
+            "Move Overhead" => Some(self.search.move_overhead_ms),
+            _ => None,
+        }
+    }
+
+    /// Look up whether an on/off option is on
+    fn enabled(&self, name: &str) -> Option<bool> {
+        match name {
+            "Learn" => Some(self.learn),
+            "Null Move" => Some(self.null_move),
+            "Futility Pruning" => Some(self.futility),
+            _ => None,
+        }
+    }
+
+    /// Set an option by name from its textual value
+    ///
+    /// # Errors
+    /// Returns `OptionError::Unknown` for an unknown option name, and `OptionError::BadValue`
+    /// if the value is not a number in the option's range, or for an on/off option, not `1`,
+    /// `0`, `true` or `false`
+    pub fn apply(&mut self, name: &str, value: &str) -> Result<(), OptionError> {
+        match name {
+            "Hash" => {
+                self.hash_mb = parse_count(value, 1, MAX_HASH_MB)?;
+                return Ok(());
+            }
+            "Threads" => {
+                self.threads = parse_count(value, 1, MAX_THREADS)?;
+                return Ok(());
+            }
+            "SyzygyPath" => {
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err(OptionError::BadValue);
+                }
+                self.syzygy_path = Some(PathBuf::from(value));
+                return Ok(());
+            }
+            "LearnFile" => {
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err(OptionError::BadValue);
+                }
+                self.learn_path = PathBuf::from(value);
+                return Ok(());
+            }
+            _ => {}
+        }
+        if CHECKS.contains(&name) {
+            let on = match value.trim() {
+                "1" | "true" => true,
+                "0" | "false" => false,
+                _ => return Err(OptionError::BadValue),
+            };
+            match name {
+                "Learn" => self.learn = on,
+                "Null Move" => self.null_move = on,
+                "Futility Pruning" => self.futility = on,
+                _ => unreachable!("option {name} has no field"),
+            }
+            return Ok(());
+        }
+        if let Some((param, min)) = self.search_param(name) {
+            *param = value
+                .trim()
+                .parse::<i32>()
+                .ok()
+                .filter(|&value| value >= min)
+                .ok_or(OptionError::BadValue)?;
+            return Ok(());
+        }
+
+        let spin = SPINS
+            .iter()
+            .find(|spin| spin.name == name)
+            .ok_or(OptionError::Unknown)?;
+        let value = value
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| OptionError::BadValue)?;
+        if !(spin.min..=spin.max).contains(&value) {
+            return Err(OptionError::BadValue);
+        }
+
+        match name {
+            "Random Margin" => self.random_margin = value,
+            "Draw Moves" => {
+                self.draw_moves = usize::try_from(value).map_err(|_| OptionError::BadValue)?;
+            }
+            "Draw Margin" => self.draw_margin = value,
+            "MultiPV" => {
+                self.multi_pv = usize::try_from(value).map_err(|_| OptionError::BadValue)?;
+            }
+            "Move Overhead" => self.search.move_overhead_ms = value,
+            _ => unreachable!("option {name} has no field"),
+        }
+        Ok(())
+    }
+
+    /// Look up one of the search parameters by option name, along with its least value
+    fn search_param(&mut self, name: &str) -> Option<(&mut i32, i32)> {
+        let search = &mut self.search;
+        match name {
+            "NullMoveReduction" => Some((&mut search.null_move_reduction, 0)),
+            "NullMoveDepthDivisor" => Some((&mut search.null_move_depth_divisor, 1)),
+            "FutilityMargin" => Some((&mut search.futility_margin, 0)),
+            "DepthLimit" => Some((&mut search.depth_limit, 2)),
+            "IncrementMoves" => Some((&mut search.increment_moves, 1)),
+            _ => None,
+        }
+    }
+
+    /// Generate the `option name ... type ...` lines advertising the options over UCI
+    #[must_use]
+    pub fn uci_options(&self) -> Vec<String> {
+        SPINS
+            .iter()
+            .map(|spin| {
+                format!(
+                    "option name {} type spin default {} min {} max {}",
+                    spin.name,
+                    self.value(spin.name).unwrap_or_default(),
+                    spin.min,
+                    spin.max
+                )
+            })
+            .chain(CHECKS.iter().map(|&name| {
+                format!(
+                    "option name {name} type check default {}",
+                    self.enabled(name).unwrap_or_default()
+                )
+            }))
+            .collect()
+    }
+
+    /// Generate the `feature option="..."` lines advertising the options over xboard
+    #[must_use]
+    pub fn xboard_features(&self) -> Vec<String> {
+        SPINS
+            .iter()
+            .map(|spin| {
+                format!(
+                    "feature option=\"{} -spin {} {} {}\"",
+                    spin.name,
+                    self.value(spin.name).unwrap_or_default(),
+                    spin.min,
+                    spin.max
+                )
+            })
+            .chain(CHECKS.iter().map(|&name| {
+                format!(
+                    "feature option=\"{name} -check {}\"",
+                    u8::from(self.enabled(name).unwrap_or_default())
+                )
+            }))
+            .collect()
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            random_margin: 10,
+            draw_moves: 30,
+            draw_margin: 15,
+            multi_pv: 1,
+            hash_mb: DEFAULT_HASH_MB,
+            threads: 1,
+            syzygy_path: None,
+            learn: false,
+            learn_path: PathBuf::from(DEFAULT_LEARN_FILE),
+            null_move: true,
+            futility: true,
+            search: SearchParams::DEFAULT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{OptionError, Options, CHECKS, SPINS};
+
+    #[test]
+    fn apply_updates_fields() {
+        let mut options = Options::default();
+        options.apply("Random Margin", "25").unwrap();
+        assert_eq!(options.random_margin, 25);
+        options.apply("Draw Moves", "40").unwrap();
+        assert_eq!(options.draw_moves, 40);
+        options.apply("Draw Margin", " 5").unwrap();
+        assert_eq!(options.draw_margin, 5);
+        options.apply("MultiPV", "3").unwrap();
+        assert_eq!(options.multi_pv, 3);
+        options.apply("Null Move", "0").unwrap();
+        assert!(!options.null_move);
+        options.apply("Futility Pruning", "0").unwrap();
+        assert!(!options.futility);
+        options.apply("Move Overhead", "250").unwrap();
+        assert_eq!(options.search.move_overhead_ms, 250);
+
+        // Every advertised option can be set.
+        for spin in &SPINS {
+            assert_eq!(options.apply(spin.name, &spin.max.to_string()), Ok(()));
+            assert_eq!(options.value(spin.name), Some(spin.max));
+        }
+        for name in CHECKS {
+            assert_eq!(options.apply(name, "1"), Ok(()));
+            assert_eq!(options.enabled(name), Some(true));
+            assert_eq!(options.apply(name, "false"), Ok(()));
+            assert_eq!(options.enabled(name), Some(false));
+        }
+    }
+
+    #[test]
+    fn apply_rejects_bad_options() {
+        let mut options = Options::default();
+        assert_eq!(options.apply("Ponder", "1"), Err(OptionError::Unknown));
+        assert_eq!(
+            options.apply("random margin", "10"),
+            Err(OptionError::Unknown)
+        );
+        assert_eq!(options.apply("Draw Moves", "0"), Err(OptionError::BadValue));
+        assert_eq!(
+            options.apply("Move Overhead", "5001"),
+            Err(OptionError::BadValue)
+        );
+        assert_eq!(
+            options.apply("Draw Margin", "lots"),
+            Err(OptionError::BadValue)
+        );
+        assert_eq!(options.apply("Learn", "2"), Err(OptionError::BadValue));
+        assert_eq!(options, Options::default());
+    }
+
+    #[test]
+    fn apply_negotiated_settings() {
+        let mut options = Options::default();
+        options.apply("Hash", "64").unwrap();
+        assert_eq!(options.hash_mb, 64);
+        options.apply("Threads", "4").unwrap();
+        assert_eq!(options.threads, 4);
+        options.apply("SyzygyPath", " /tb/syzygy").unwrap();
+        assert_eq!(options.syzygy_path, Some("/tb/syzygy".into()));
+        options.apply("LearnFile", "games.learn").unwrap();
+        assert_eq!(options.learn_path, PathBuf::from("games.learn"));
+
+        let mut bad = options.clone();
+        assert_eq!(bad.apply("Hash", "0"), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("Hash", "-16"), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("Hash", "1000000"), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("Threads", "0"), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("Threads", "2.5"), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("SyzygyPath", " "), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("LearnFile", ""), Err(OptionError::BadValue));
+        assert_eq!(bad, options);
+    }
+
+    #[test]
+    fn apply_search_params() {
+        let mut options = Options::default();
+        options.apply("NullMoveReduction", "2").unwrap();
+        assert_eq!(options.search.null_move_reduction, 2);
+        options.apply("FutilityMargin", "150").unwrap();
+        assert_eq!(options.search.futility_margin, 150);
+        options.apply("Move Overhead", "100").unwrap();
+        assert_eq!(options.search.move_overhead_ms, 100);
+
+        let mut bad = options.clone();
+        assert_eq!(
+            bad.apply("NullMoveDepthDivisor", "0"),
+            Err(OptionError::BadValue)
+        );
+        assert_eq!(bad.apply("DepthLimit", "1"), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("Move Overhead", "-5"), Err(OptionError::BadValue));
+        assert_eq!(bad.apply("MoveOverhead", "100"), Err(OptionError::Unknown));
+        assert_eq!(bad, options);
+
+        // They are not advertised.
+        assert!(options
+            .xboard_features()
+            .iter()
+            .all(|feature| !feature.contains("NullMoveReduction")));
+    }
+
+    #[test]
+    fn advertised_options() {
+        let options = Options::default();
+        assert_eq!(
+            options.xboard_features()[0],
+            "feature option=\"Random Margin -spin 10 0 1000\""
+        );
+        assert_eq!(
+            options.uci_options()[0],
+            "option name Random Margin type spin default 10 min 0 max 1000"
+        );
+        assert!(options
+            .xboard_features()
+            .contains(&"feature option=\"Learn -check 0\"".to_owned()));
+        assert!(options
+            .xboard_features()
+            .contains(&"feature option=\"Null Move -check 1\"".to_owned()));
+        assert!(options
+            .uci_options()
+            .contains(&"option name Learn type check default false".to_owned()));
+    }
+}
@@ -0,0 +1,924 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use yukari_movegen::{Board, Colour, Move, MoveList, MoveType, Piece, Zobrist};
+
+use super::{DrawAdjudicator, TimeControl, TimeMode};
+use crate::is_repetition_draw;
+
+/// Short openings to start games from, each played once with either colour
+pub const OPENINGS: [&[&str]; 8] = [
+    &["e2e4", "e7e5", "g1f3", "b8c6"],
+    &["e2e4", "c7c5", "g1f3", "d7d6"],
+    &["e2e4", "e7e6", "d2d4", "d7d5"],
+    &["e2e4", "c7c6", "d2d4", "d7d5"],
+    &["d2d4", "d7d5", "c2c4", "e7e6"],
+    &["d2d4", "g8f6", "c2c4", "g7g6"],
+    &["c2c4", "e7e5", "b1c3", "g8f6"],
+    &["g1f3", "d7d5", "g2g3", "g8f6"],
+];
+
+/// Where the games of a match start: a position, and moves to play from it before the
+/// engines take over
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Opening {
+    /// The starting position in FEN, or `None` for the usual one
+    pub fen: Option<String>,
+    /// Moves from the starting position, in coordinate notation
+    pub moves: Vec<String>,
+}
+
+impl Opening {
+    /// The built-in openings
+    #[must_use]
+    pub fn builtin() -> Vec<Self> {
+        OPENINGS
+            .iter()
+            .map(|moves| Self {
+                fen: None,
+                moves: moves.iter().map(|&m| m.to_string()).collect(),
+            })
+            .collect()
+    }
+
+    /// Parse a line of an openings file: an EPD or FEN position, or moves from the start
+    /// position in SAN or coordinate notation, where move numbers and a result may appear
+    /// as they do in PGN (`1. e4 e5 2. Nf3 *`).
+    ///
+    /// # Errors
+    /// Returns a description of the problem if the line is neither a legal position nor a
+    /// line of legal moves.
+    pub fn parse(line: &str, zobrist: &Zobrist) -> Result<Self, String> {
+        let line = line.trim();
+        if line
+            .split_whitespace()
+            .next()
+            .is_some_and(|field| field.contains('/'))
+        {
+            let board = Board::from_fen(line, zobrist).map_err(|err| err.to_string())?;
+            // EPD has no move counters, and EPD operations are dropped.
+            let fullmove = line.split_whitespace().nth(5);
+            let fullmove = fullmove.and_then(|n| n.parse().ok()).unwrap_or(1);
+            return Ok(Self {
+                fen: Some(board.to_fen(fullmove)),
+                moves: Vec::new(),
+            });
+        }
+
+        let mut board = Board::startpos(zobrist);
+        let mut moves = Vec::new();
+        for token in line.split_whitespace() {
+            if matches!(token, "*" | "1-0" | "0-1" | "1/2-1/2") {
+                continue;
+            }
+            // Move numbers may be written apart from or against their move: `1. e4`, `1.e4`.
+            let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+            if token.is_empty() {
+                continue;
+            }
+            let m = board
+                .parse_move(token)
+                .map_err(|err| format!("{token}: {err}"))?;
+            moves.push(m.to_string());
+            board = board.make(m, zobrist);
+        }
+        Ok(Self { fen: None, moves })
+    }
+
+    /// Play no more than `plies` moves of the opening
+    pub fn truncate(&mut self, plies: usize) {
+        self.moves.truncate(plies);
+    }
+}
+
+/// Read an openings file, one opening per line as [`Opening::parse`] takes them. Blank lines
+/// and lines starting with `#` are skipped.
+///
+/// # Errors
+/// Returns an error if the file cannot be read, or names the first line that is not an
+/// opening.
+pub fn read_openings(reader: impl BufRead, zobrist: &Zobrist) -> io::Result<Vec<Opening>> {
+    let mut openings = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let opening = Opening::parse(&line, zobrist).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {err}", index + 1),
+            )
+        })?;
+        openings.push(opening);
+    }
+    Ok(openings)
+}
+
+/// The order in which a match goes through its openings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OpeningOrder {
+    /// In the order given
+    #[default]
+    Sequential,
+    /// Shuffled at the start of the match
+    Random,
+}
+
+/// How long a player has to answer `protover` before we carry on without its features
+const FEATURE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How late a move may arrive, over the mover's clock, before we stop waiting for it
+const MOVE_GRACE: Duration = Duration::from_secs(1);
+/// Score (in centipawns) at or below which a player's own evaluation counts as lost
+const RESIGN_SCORE: i32 = -1000;
+/// Number of consecutive moves a player must see itself lost before it resigns
+const RESIGN_MOVES: u32 = 3;
+
+/// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    /// The result as written in PGN
+    #[must_use]
+    pub const fn pgn(self) -> &'static str {
+        match self {
+            Self::WhiteWins => "1-0",
+            Self::BlackWins => "0-1",
+            Self::Draw => "1/2-1/2",
+        }
+    }
+
+    /// The result when `colour` wins
+    const fn win_for(colour: Colour) -> Self {
+        match colour {
+            Colour::White => Self::WhiteWins,
+            Colour::Black => Self::BlackWins,
+        }
+    }
+}
+
+/// The settings for a match between two xboard engines
+#[derive(Clone, Debug)]
+pub struct MatchConfig {
+    /// The two engines; the first plays white in even-numbered games
+    pub engines: [PathBuf; 2],
+    /// Number of games to play
+    pub games: u32,
+    /// Time control for both players
+    pub tc: TimeMode,
+    /// Openings to start pairs of games from, one with either colour; with none, every game
+    /// starts from the start position
+    pub openings: Vec<Opening>,
+    /// Whether the openings are played in turn or shuffled
+    pub opening_order: OpeningOrder,
+}
+
+/// A finished game
+#[derive(Clone, Debug)]
+pub struct Game {
+    /// Names of the white and black players
+    pub players: [String; 2],
+    /// The starting position in FEN, if not the usual one
+    pub fen: Option<String>,
+    /// Every move of the game, in SAN
+    pub moves: Vec<String>,
+    /// How the game ended
+    pub result: GameResult,
+    /// Why the game ended
+    pub reason: String,
+}
+
+impl Game {
+    /// Format the game as PGN, numbered as round `round` of the match
+    #[must_use]
+    pub fn to_pgn(&self, round: u32, tc: TimeMode) -> String {
+        let mut pgn = String::new();
+        let _ = writeln!(pgn, "[Event \"Yukari self-play\"]");
+        let _ = writeln!(pgn, "[Site \"?\"]");
+        let _ = writeln!(pgn, "[Date \"????.??.??\"]");
+        let _ = writeln!(pgn, "[Round \"{round}\"]");
+        let _ = writeln!(pgn, "[White \"{}\"]", self.players[0]);
+        let _ = writeln!(pgn, "[Black \"{}\"]", self.players[1]);
+        let _ = writeln!(pgn, "[Result \"{}\"]", self.result.pgn());
+        let _ = writeln!(pgn, "[TimeControl \"{}\"]", pgn_time_control(tc));
+        let _ = writeln!(pgn, "[Termination \"{}\"]", self.reason);
+        // Moves are numbered on from the position's fullmove number, after its side to move.
+        let (mut ply, fullmove) = self.fen.as_deref().map_or((0, 1), |fen| {
+            let _ = writeln!(pgn, "[SetUp \"1\"]");
+            let _ = writeln!(pgn, "[FEN \"{fen}\"]");
+            let fields: Vec<_> = fen.split_whitespace().collect();
+            let fullmove = fields.get(5).and_then(|n| n.parse().ok()).unwrap_or(1);
+            (usize::from(fields.get(1) == Some(&"b")), fullmove)
+        });
+        pgn.push('\n');
+
+        // Movetext lines are kept under 80 characters.
+        let mut tokens = Vec::new();
+        for (index, m) in self.moves.iter().enumerate() {
+            if ply % 2 == 0 {
+                tokens.push(format!("{}.", fullmove + ply / 2));
+            } else if index == 0 {
+                tokens.push(format!("{}...", fullmove + ply / 2));
+            }
+            tokens.push(m.clone());
+            ply += 1;
+        }
+        tokens.push(self.result.pgn().to_string());
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() >= 80 {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn.push_str(&token);
+        }
+        pgn.push_str("\n\n");
+        pgn
+    }
+}
+
+/// The PGN `TimeControl` tag value for a time control
+fn pgn_time_control(tc: TimeMode) -> String {
+    match tc {
+        TimeMode::St(secs) => format!("*{secs}"),
+        TimeMode::Incremental { base, increment } => format!("{base}+{increment}"),
+        TimeMode::Classical { base, mps } => format!("{mps}/{base}"),
+    }
+}
+
+/// Parse a time control written as `base+increment` in seconds, like `10+0.1`
+#[must_use]
+pub fn parse_match_tc(s: &str) -> Option<TimeMode> {
+    let (base, increment) = s.split_once('+').unwrap_or((s, "0"));
+    let base: f32 = base.parse().ok()?;
+    let increment: f32 = increment.parse().ok()?;
+    (base > 0.0 && increment >= 0.0).then_some(TimeMode::Incremental { base, increment })
+}
+
+/// Wins, losses and draws from the first engine's point of view
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchStats {
+    /// Count a game in which the first engine played `colour`
+    pub fn record(&mut self, result: GameResult, colour: Colour) {
+        match (result, colour) {
+            (GameResult::Draw, _) => self.draws += 1,
+            (GameResult::WhiteWins, Colour::White) | (GameResult::BlackWins, Colour::Black) => {
+                self.wins += 1;
+            }
+            _ => self.losses += 1,
+        }
+    }
+
+    /// Number of games counted
+    #[must_use]
+    pub const fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Fraction of the points the first engine scored
+    #[must_use]
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (f64::from(self.wins) + f64::from(self.draws) / 2.0) / f64::from(self.games())
+    }
+
+    /// The Elo difference implied by the score; `None` when one side scored every point
+    #[must_use]
+    pub fn elo(&self) -> Option<f64> {
+        let score = self.score();
+        (score > 0.0 && score < 1.0).then(|| -400.0 * (1.0 / score - 1.0).log10())
+    }
+
+    /// Likelihood of superiority: the chance the first engine is really the stronger
+    #[must_use]
+    pub fn los(&self) -> f64 {
+        let decisive = f64::from(self.wins + self.losses);
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        let margin = f64::from(self.wins) - f64::from(self.losses);
+        0.5 * (1.0 + erf(margin / (2.0 * decisive).sqrt()))
+    }
+}
+
+/// The error function, to within 1.5e-7 (Abramowitz and Stegun 7.1.26)
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / 0.327_591_1f64.mul_add(x.abs(), 1.0);
+    let poly = t * 1.061_405_429f64
+        .mul_add(t, -1.453_152_027)
+        .mul_add(t, 1.421_413_741)
+        .mul_add(t, -0.284_496_736)
+        .mul_add(t, 0.254_829_592);
+    let y = 1.0 - poly * (-x * x).exp();
+    y.copysign(x)
+}
+
+/// An engine running as a child process, spoken to over xboard
+struct Player {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines the engine printed, read on a separate thread so waits can time out
+    lines: Receiver<String>,
+    clock: TimeControl,
+}
+
+impl Player {
+    /// Start an engine and wait for it to finish sending its features
+    fn spawn(path: &PathBuf, tc: TimeMode) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("child stdin is piped");
+        let stdout = child.stdout.take().expect("child stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut player = Self {
+            name: path.display().to_string(),
+            child,
+            stdin,
+            lines,
+            clock: TimeControl::new(tc),
+        };
+        player.send("xboard")?;
+        player.send("protover 2")?;
+        let deadline = Instant::now() + FEATURE_TIMEOUT;
+        while let Ok(line) = player.recv(deadline) {
+            if let Some((_, rest)) = line.split_once("myname=\"") {
+                if let Some((name, _)) = rest.split_once('"') {
+                    player.name = name.to_string();
+                }
+            }
+            if line.contains("done=1") {
+                break;
+            }
+        }
+        Ok(player)
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{command}")?;
+        self.stdin.flush()
+    }
+
+    fn recv(&self, deadline: Instant) -> Result<String, RecvTimeoutError> {
+        self.lines
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Wait for the engine's move, along with the last score it reported while thinking
+    fn wait_move(&self, deadline: Instant) -> Result<(String, Option<i32>), RecvTimeoutError> {
+        let mut score = None;
+        loop {
+            let line = self.recv(deadline)?;
+            if let Some(m) = line.strip_prefix("move ") {
+                return Ok((m.trim().to_string(), score));
+            }
+            // Thinking output is `depth score time nodes pv...`.
+            let mut fields = line.split_ascii_whitespace();
+            if let (Some(depth), Some(value)) = (fields.next(), fields.next()) {
+                if depth.parse::<u32>().is_ok() {
+                    score = value.parse().ok().or(score);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Check whether the game is over by the rules, or by adjudication
+fn game_over(
+    board: &Board,
+    keystack: &[u64],
+    halfmoves: u32,
+    adjudicator: &DrawAdjudicator,
+) -> Option<(GameResult, &'static str)> {
+    if board.is_checkmate() {
+        return Some((GameResult::win_for(!board.side()), "checkmate"));
+    }
+    if board.is_stalemate() {
+        return Some((GameResult::Draw, "stalemate"));
+    }
+    if is_repetition_draw(keystack, board.hash()) {
+        return Some((GameResult::Draw, "threefold repetition"));
+    }
+    if halfmoves >= 100 {
+        return Some((GameResult::Draw, "fifty move rule"));
+    }
+    let heavy = board.pawns() | board.rooks() | board.queens();
+    let minors = board.knights() | board.bishops();
+    if heavy.empty() && minors.count_ones() <= 1 {
+        return Some((GameResult::Draw, "insufficient material"));
+    }
+    if adjudicator.is_drawish() {
+        return Some((GameResult::Draw, "adjudicated draw"));
+    }
+    None
+}
+
+/// Format a legal move in SAN
+#[must_use]
+pub fn san(board: &Board, m: Move, zobrist: &Zobrist) -> String {
+    let mut san = String::new();
+    let square = m.dest.to_string();
+    if m.kind == MoveType::Castle {
+        san.push_str(if square.starts_with('g') {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else {
+        let piece = board
+            .piece_from_square(m.from)
+            .expect("move has a piece to move");
+        let from = m.from.to_string();
+        if piece == Piece::Pawn {
+            if m.is_capture() {
+                san.push_str(&from[..1]);
+                san.push('x');
+            }
+        } else {
+            san.push(piece.to_char());
+
+            // Name the file, rank, or both, of the piece that moves if another could move there too.
+            let mut moves = MoveList::new();
+            board.generate(&mut moves);
+            let rivals: Vec<String> = moves
+                .iter()
+                .filter(|other| {
+                    other.dest == m.dest
+                        && other.from != m.from
+                        && board.piece_from_square(other.from) == Some(piece)
+                })
+                .map(|other| other.from.to_string())
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|rival| rival[..1] != from[..1]) {
+                    san.push_str(&from[..1]);
+                } else if rivals.iter().all(|rival| rival[1..] != from[1..]) {
+                    san.push_str(&from[1..]);
+                } else {
+                    san.push_str(&from);
+                }
+            }
+            if m.is_capture() {
+                san.push('x');
+            }
+        }
+        san.push_str(&square);
+        if let Some(prom) = m.prom {
+            san.push('=');
+            san.push(prom.to_char());
+        }
+    }
+
+    if board.gives_checkmate(m, zobrist) {
+        san.push('#');
+    } else if board.gives_check(m) {
+        san.push('+');
+    }
+    san
+}
+
+/// The referee's view of a game in progress
+struct GameState {
+    board: Board,
+    /// Hashes of every position so far, for spotting repetitions
+    keystack: Vec<u64>,
+    /// Plies since the last pawn move or capture
+    halfmoves: u32,
+    adjudicator: DrawAdjudicator,
+    /// Moves played so far, in SAN
+    moves: Vec<String>,
+}
+
+impl GameState {
+    /// Start from `board`, which may come from a position partway through a game
+    fn new(board: Board) -> Self {
+        Self {
+            keystack: vec![board.hash()],
+            halfmoves: board.halfmove_clock(),
+            board,
+            adjudicator: DrawAdjudicator::default(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Play a legal move; `score` is what the mover reported for it
+    fn play(&mut self, m: Move, score: Option<i32>, zobrist: &Zobrist) {
+        self.moves.push(san(&self.board, m, zobrist));
+        let irreversible = self.board.piece_from_square(m.from) == Some(Piece::Pawn)
+            || self.board.piece_from_square(m.dest).is_some();
+        self.halfmoves = if irreversible { 0 } else { self.halfmoves + 1 };
+        self.adjudicator.push(score, irreversible);
+        self.board = self.board.make(m, zobrist);
+        self.keystack.push(self.board.hash());
+    }
+
+    fn over(&self) -> Option<(GameResult, &'static str)> {
+        game_over(
+            &self.board,
+            &self.keystack,
+            self.halfmoves,
+            &self.adjudicator,
+        )
+    }
+}
+
+/// Play one game; `players` are white then black
+fn play_game(
+    players: &mut [Player; 2],
+    opening: &Opening,
+    tc: TimeMode,
+    zobrist: &Zobrist,
+) -> io::Result<Game> {
+    let board = opening.fen.as_deref().map_or_else(
+        || Board::startpos(zobrist),
+        |fen| Board::from_fen(fen, zobrist).expect("opening positions are checked when read"),
+    );
+    let mut game = GameState::new(board);
+    let mut losing_moves = [0; 2];
+
+    for player in players.iter_mut() {
+        player.send("new")?;
+        player.send("force")?;
+        player.send(&tc.to_string())?;
+        if let Some(fen) = &opening.fen {
+            player.send(&format!("setboard {fen}"))?;
+        }
+        player.clock = TimeControl::new(tc);
+    }
+
+    for text in &opening.moves {
+        let m = game
+            .board
+            .parse_move(text)
+            .expect("opening moves are checked when read");
+        for player in players.iter_mut() {
+            player.send(&m.to_string())?;
+        }
+        game.play(m, None, zobrist);
+    }
+
+    let (result, reason) = loop {
+        if let Some((result, reason)) = game.over() {
+            break (result, reason.to_string());
+        }
+
+        let side = game.board.side();
+        let mover = usize::from(side == Colour::Black);
+        let [white, black] = players;
+        let (player, opponent) = if mover == 0 {
+            (white, black)
+        } else {
+            (black, white)
+        };
+
+        let remaining = player.clock.remaining();
+        player.send(&format!("time {:.0}", remaining * 100.0))?;
+        player.send(&format!("otim {:.0}", opponent.clock.remaining() * 100.0))?;
+        player.send("go")?;
+        let start = Instant::now();
+        let reply = player.wait_move(start + Duration::from_secs_f32(remaining) + MOVE_GRACE);
+        let elapsed = start.elapsed();
+        // Keep the engine from answering moves we relay to it.
+        player.send("force")?;
+
+        let winner = GameResult::win_for(!side);
+        let name = if side == Colour::White {
+            "White"
+        } else {
+            "Black"
+        };
+        let (text, score) = match reply {
+            Ok(reply) => reply,
+            Err(RecvTimeoutError::Timeout) => break (winner, format!("{name} loses on time")),
+            Err(RecvTimeoutError::Disconnected) => {
+                break (winner, format!("{name} disconnects"));
+            }
+        };
+        if !player.clock.elapse(elapsed.as_secs_f32()) {
+            break (winner, format!("{name} loses on time"));
+        }
+        let Ok(m) = game.board.parse_move(&text) else {
+            break (winner, format!("{name} plays illegal move {text}"));
+        };
+
+        // A player that has seen itself lost for several moves resigns.
+        if score.is_some_and(|score| score <= RESIGN_SCORE) {
+            losing_moves[mover] += 1;
+        } else {
+            losing_moves[mover] = 0;
+        }
+        if losing_moves[mover] >= RESIGN_MOVES {
+            break (winner, format!("{name} resigns"));
+        }
+
+        opponent.send(&m.to_string())?;
+        game.play(m, score, zobrist);
+    };
+
+    Ok(Game {
+        players: [players[0].name.clone(), players[1].name.clone()],
+        fen: opening.fen.clone(),
+        moves: game.moves,
+        result,
+        reason,
+    })
+}
+
+/// Play a match, printing a line per game to `log` and every game as PGN to `pgn`.
+/// Colours alternate between games, and each opening is played once with either colour,
+/// starting over when the match outlasts the openings.
+///
+/// # Errors
+/// Returns an error if an engine cannot be started or talked to, or the output cannot be written.
+pub fn run_match(
+    config: &MatchConfig,
+    log: &mut impl Write,
+    pgn: &mut impl Write,
+) -> io::Result<MatchStats> {
+    let zobrist = Zobrist::new();
+    let mut first = Player::spawn(&config.engines[0], config.tc)?;
+    let mut second = Player::spawn(&config.engines[1], config.tc)?;
+    // An engine playing itself is told apart by number, so the PGN shows who had which colour.
+    if first.name == second.name {
+        first.name.push_str(" 1");
+        second.name.push_str(" 2");
+    }
+    let mut stats = MatchStats::default();
+
+    let mut openings = config.openings.clone();
+    if config.opening_order == OpeningOrder::Random {
+        openings.shuffle(&mut StdRng::from_entropy());
+    }
+    if openings.is_empty() {
+        openings.push(Opening::default());
+    }
+
+    for game_index in 0..config.games {
+        let opening = &openings[(game_index as usize / 2) % openings.len()];
+        let colour = if game_index % 2 == 0 {
+            Colour::White
+        } else {
+            Colour::Black
+        };
+
+        // Swap the players in and out of the array so each keeps its process between games.
+        let (white, black) = match colour {
+            Colour::White => (first, second),
+            Colour::Black => (second, first),
+        };
+        let mut players = [white, black];
+        let game = play_game(&mut players, opening, config.tc, &zobrist);
+        let [white, black] = players;
+        (first, second) = match colour {
+            Colour::White => (white, black),
+            Colour::Black => (black, white),
+        };
+        let game = game?;
+
+        stats.record(game.result, colour);
+        writeln!(
+            log,
+            "Game {}: {} vs {}: {} {{{}}}",
+            game_index + 1,
+            game.players[0],
+            game.players[1],
+            game.result.pgn(),
+            game.reason
+        )?;
+        write!(pgn, "{}", game.to_pgn(game_index + 1, config.tc))?;
+        pgn.flush()?;
+    }
+
+    let elo = stats
+        .elo()
+        .map_or_else(|| "inf".to_string(), |elo| format!("{elo:+.0}"));
+    writeln!(
+        log,
+        "Score of {} vs {}: {} - {} - {} [{:.3}] {}, Elo {elo}, LOS {:.1}%",
+        first.name,
+        second.name,
+        stats.wins,
+        stats.losses,
+        stats.draws,
+        stats.score(),
+        stats.games(),
+        100.0 * stats.los()
+    )?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use yukari_movegen::{Board, Colour, Zobrist};
+
+    use super::{
+        game_over, parse_match_tc, read_openings, san, Game, GameResult, MatchStats, Opening,
+        OPENINGS,
+    };
+    use crate::engine::{DrawAdjudicator, TimeMode};
+
+    #[test]
+    fn san_moves() {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen("r3k2r/1P6/8/8/8/8/5R2/R3K1NR w KQkq - 0 1", &zobrist).unwrap();
+        let san_of = |s| san(&board, board.parse_move(s).unwrap(), &zobrist);
+        assert_eq!(san_of("e1c1"), "O-O-O");
+        assert_eq!(san_of("b7a8q"), "bxa8=Q+");
+        assert_eq!(san_of("g1f3"), "Nf3");
+        assert_eq!(san_of("a1d1"), "Rd1");
+        assert_eq!(san_of("f2f8"), "Rf8+");
+
+        // Rooks are told apart by file where possible, and by rank otherwise.
+        let board = Board::from_fen("4k3/8/8/8/R7/8/4K3/R6R w - - 0 1", &zobrist).unwrap();
+        let san_of = |s| san(&board, board.parse_move(s).unwrap(), &zobrist);
+        assert_eq!(san_of("a1d1"), "Rad1");
+        assert_eq!(san_of("a4a2"), "R4a2");
+
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &zobrist).unwrap();
+        assert_eq!(
+            san(&board, board.parse_move("a1a8").unwrap(), &zobrist),
+            "Ra8#"
+        );
+    }
+
+    #[test]
+    fn rules_end_games() {
+        let zobrist = Zobrist::new();
+        let adjudicator = DrawAdjudicator::default();
+        let over = |fen| {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            game_over(&board, &[board.hash()], 0, &adjudicator)
+        };
+        assert_eq!(
+            over("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"),
+            Some((GameResult::WhiteWins, "checkmate"))
+        );
+        assert_eq!(
+            over("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            Some((GameResult::Draw, "stalemate"))
+        );
+        assert_eq!(
+            over("7k/8/6K1/8/8/8/8/6N1 b - - 0 1"),
+            Some((GameResult::Draw, "insufficient material"))
+        );
+        assert_eq!(over("7k/8/6K1/8/8/8/8/5NN1 b - - 0 1"), None);
+
+        let board = Board::startpos(&zobrist);
+        let hash = board.hash();
+        assert_eq!(
+            game_over(&board, &[hash, hash, hash], 0, &adjudicator),
+            Some((GameResult::Draw, "threefold repetition"))
+        );
+        assert_eq!(
+            game_over(&board, &[hash], 100, &adjudicator),
+            Some((GameResult::Draw, "fifty move rule"))
+        );
+    }
+
+    #[test]
+    fn match_statistics() {
+        let mut stats = MatchStats::default();
+        assert_eq!(stats.elo(), Some(0.0));
+        assert!((stats.los() - 0.5).abs() < 1e-9);
+
+        stats.record(GameResult::WhiteWins, Colour::White);
+        stats.record(GameResult::WhiteWins, Colour::Black);
+        stats.record(GameResult::BlackWins, Colour::Black);
+        stats.record(GameResult::Draw, Colour::White);
+        assert_eq!(
+            stats,
+            MatchStats {
+                wins: 2,
+                losses: 1,
+                draws: 1
+            }
+        );
+        // 62.5% is about +89 Elo.
+        assert_eq!(stats.elo().map(f64::round), Some(89.0));
+        assert!((stats.los() - 0.718_149).abs() < 1e-5);
+
+        stats.losses = 0;
+        stats.draws = 0;
+        assert_eq!(stats.elo(), None);
+    }
+
+    #[test]
+    fn pgn_output() {
+        let game = Game {
+            players: ["A".to_string(), "B".to_string()],
+            fen: None,
+            moves: ["f3", "e5", "g4", "Qh4#"].map(String::from).to_vec(),
+            result: GameResult::BlackWins,
+            reason: "checkmate".to_string(),
+        };
+        let tc = parse_match_tc("10+0.1").unwrap();
+        let pgn = game.to_pgn(3, tc);
+        assert!(pgn.contains("[Round \"3\"]\n[White \"A\"]\n[Black \"B\"]\n[Result \"0-1\"]\n"));
+        assert!(pgn.contains("[TimeControl \"10+0.1\"]\n"));
+        assert!(pgn.ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n"));
+
+        assert!(matches!(
+            parse_match_tc("60"),
+            Some(TimeMode::Incremental { base, increment }) if base == 60.0 && increment == 0.0
+        ));
+        assert!(parse_match_tc("fast").is_none());
+        assert!(parse_match_tc("0+1").is_none());
+
+        // A game from a position with black to move is numbered on from it.
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 3 40";
+        let game = Game {
+            fen: Some(fen.to_string()),
+            moves: ["Kd7", "e4"].map(String::from).to_vec(),
+            result: GameResult::Draw,
+            ..game
+        };
+        let pgn = game.to_pgn(1, tc);
+        assert!(pgn.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n")));
+        assert!(pgn.ends_with("\n\n40... Kd7 41. e4 1/2-1/2\n\n"));
+    }
+
+    #[test]
+    fn openings_file() {
+        let zobrist = Zobrist::new();
+        let file = "\
+# Positions, with and without move counters, and move lines
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1
+r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5; id \"Ruy\";
+
+1. e4 c5 2.Nf3 d6 *
+d2d4 g8f6 c2c4 e7e6
+";
+        let openings = read_openings(file.as_bytes(), &zobrist).unwrap();
+        assert_eq!(openings.len(), 4);
+        assert_eq!(
+            openings[0].fen.as_deref(),
+            Some("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+        assert_eq!(
+            openings[1].fen.as_deref(),
+            Some("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1")
+        );
+        assert!(openings[1].moves.is_empty());
+        assert_eq!(openings[2].fen, None);
+        assert_eq!(openings[2].moves, ["e2e4", "c7c5", "g1f3", "d7d6"]);
+        assert_eq!(openings[3].moves, ["d2d4", "g8f6", "c2c4", "e7e6"]);
+
+        let mut opening = openings[3].clone();
+        opening.truncate(2);
+        assert_eq!(opening.moves, ["d2d4", "g8f6"]);
+        assert_eq!(Opening::builtin().len(), OPENINGS.len());
+
+        // Bad lines are reported by number.
+        let err = read_openings("e2e4\ne2e4 e2e4\n".as_bytes(), &zobrist).unwrap_err();
+        assert!(err.to_string().starts_with("line 2: e2e4"), "{err}");
+        assert!(Opening::parse("8/8/8/8/8/8/8/8 w - -", &zobrist).is_err());
+    }
+}
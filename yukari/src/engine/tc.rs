@@ -1,5 +1,22 @@
+use std::fmt::Display;
 use std::str::FromStr;
 
+use crate::SearchParams;
+
+/// Largest fraction by which the clock situation can shorten or lengthen a search
+const MAX_CLOCK_ADJUSTMENT: f32 = 0.3;
+/// Score below which we count as losing, and worth spending a clock lead on
+const LOSING_SCORE: i32 = -100;
+/// Moves' worth of time held back until a classical period's time arrives, so the last move
+/// of a period is not played on an empty clock
+const PERIOD_RESERVE_MOVES: u32 = 1;
+/// Share of its budget a search gains per unit of best move instability
+const INSTABILITY_EXTENSION: f32 = 0.3;
+/// Largest multiple of its budget an unsettled search can be extended to
+const MAX_INSTABILITY_FACTOR: f32 = 2.0;
+/// Least time a search is given in seconds, however little the clock and overhead leave
+const MIN_SEARCH_TIME: f32 = 0.005;
+
 // Time control represents the current time left on our clock, and the time
 #[derive(Clone, Copy, Debug)]
 pub struct TimeControl {
@@ -9,6 +26,14 @@ pub struct TimeControl {
     mode: TimeMode,
     /// Number of moves made so far.
     move_number: u32,
+    /// Time remaining on the opponent's clock in seconds, once the GUI has told us
+    opponent_remaining: Option<f32>,
+    /// Score of our last search, from our point of view
+    last_score: Option<i32>,
+    /// Time held back from every search, in seconds
+    overhead: f32,
+    /// With an increment, a move gets the clock and one increment divided by this
+    increment_moves: f32,
 }
 
 impl TimeControl {
@@ -22,47 +47,164 @@ impl TimeControl {
             },
             mode,
             move_number: 0,
+            opponent_remaining: None,
+            last_score: None,
+            overhead: SearchParams::DEFAULT.move_overhead_ms as f32 / 1000.0,
+            increment_moves: SearchParams::DEFAULT.increment_moves as f32,
         }
     }
 
+    /// The mode the clock is operating in
+    #[must_use]
+    pub const fn mode(&self) -> TimeMode {
+        self.mode
+    }
+
+    /// Take the time overhead and increment share from `params`
+    pub fn set_params(&mut self, params: &SearchParams) {
+        self.overhead = params.move_overhead_ms as f32 / 1000.0;
+        self.increment_moves = params.increment_moves as f32;
+    }
+
     /// Set the time using a centisecond value
     pub fn set_remaining(&mut self, centiseconds: f32) {
         self.remaining = centiseconds / 100.0;
     }
 
+    /// Set the opponent's time using a centisecond value
+    pub fn set_opponent_remaining(&mut self, centiseconds: f32) {
+        self.opponent_remaining = Some(centiseconds / 100.0);
+    }
+
+    /// Record the score of our last search, which decides how to use a lead on the clock
+    pub fn set_last_score(&mut self, score: i32) {
+        self.last_score = Some(score);
+    }
+
     /// Increment the move number.
     pub fn increment_moves(&mut self) {
         self.move_number += 1;
     }
 
+    /// Current time remaining on the clock in seconds
+    #[must_use]
+    pub const fn remaining(&self) -> f32 {
+        self.remaining
+    }
+
+    /// Charge a move's thinking time to the clock, then add any increment or new session's time,
+    /// as a referee keeping the players' clocks does
+    /// Returns false if the clock ran out
+    pub fn elapse(&mut self, seconds: f32) -> bool {
+        self.remaining -= seconds;
+        if self.remaining < 0.0 {
+            return false;
+        }
+        self.move_number += 1;
+        match self.mode {
+            TimeMode::St(secs) => self.remaining = secs as f32,
+            TimeMode::Incremental { base: _, increment } => self.remaining += increment,
+            TimeMode::Classical { base, mps } => {
+                if self.move_number.is_multiple_of(mps) {
+                    self.remaining += base;
+                }
+            }
+        }
+        true
+    }
+
+    /// Number of moves left to play in the current classical period, including this one.
+    /// The next period's time is added once the last of them is made.
+    #[must_use]
+    pub const fn moves_to_go(&self) -> Option<u32> {
+        match self.mode {
+            TimeMode::Classical { mps, .. } if mps > 0 => Some(mps - self.move_number % mps),
+            _ => None,
+        }
+    }
+
+    /// How much to scale the search time by for the clock situation, between
+    /// `1 - MAX_CLOCK_ADJUSTMENT` and `1 + MAX_CLOCK_ADJUSTMENT`.
+    ///
+    /// When we have more time than the opponent, play faster to keep them short of time;
+    /// unless we are losing, when the time is better spent setting problems.
+    /// The bigger our share of the lead, the bigger the adjustment.
+    fn clock_factor(&self) -> f32 {
+        let Some(theirs) = self.opponent_remaining else {
+            return 1.0;
+        };
+        if matches!(self.mode, TimeMode::St(_)) || self.remaining <= theirs {
+            return 1.0;
+        }
+        let lead = ((self.remaining - theirs.max(0.0)) / self.remaining).clamp(0.0, 1.0);
+        let adjustment = MAX_CLOCK_ADJUSTMENT * lead;
+        if self.last_score.is_some_and(|score| score < LOSING_SCORE) {
+            1.0 + adjustment
+        } else {
+            1.0 - adjustment
+        }
+    }
+
     /// Compute the time to search.
     #[must_use]
     pub fn search_time(&self) -> f32 {
-        match self.mode {
-            TimeMode::St(secs) => (secs as f32) - 0.02,
+        let remaining = self.remaining - self.overhead;
+        let time = match self.mode {
+            TimeMode::St(secs) => (secs as f32) - self.overhead,
             TimeMode::Incremental { base: _, increment } => {
-                let remaining = self.remaining - 0.02;
-                remaining.min((remaining + increment) / 30.0)
+                remaining.min((remaining + increment) / self.increment_moves)
             }
-            TimeMode::Classical { base: _, mps } => {
-                let remaining = self.remaining - 0.02;
-                let mps = mps as i32;
-                let move_number = self.move_number as i32;
-                let mut movesleft = mps - move_number;
-
-                // Add the moves per session to get a positive number.
-                while movesleft <= 0 {
-                    movesleft += mps;
-                }
-
+            TimeMode::Classical { .. } => {
+                let movesleft = self.moves_to_go().unwrap_or(1) + PERIOD_RESERVE_MOVES;
                 remaining / (movesleft as f32)
             }
+        };
+        let time = if matches!(self.mode, TimeMode::St(_)) {
+            time
+        } else {
+            (time * self.clock_factor()).min(self.max_search_time())
+        };
+        time.max(MIN_SEARCH_TIME)
+    }
+
+    /// The most one search may take from the clock: all of it, less the overhead, except in a
+    /// classical period, where the `PERIOD_RESERVE_MOVES` reserve is kept for after the period.
+    fn max_search_time(&self) -> f32 {
+        let remaining = self.remaining - self.overhead;
+        match self.moves_to_go() {
+            Some(moves) => remaining * (moves as f32) / ((moves + PERIOD_RESERVE_MOVES) as f32),
+            None => remaining,
         }
     }
+
+    /// Compute the time to search when the best move is as unsettled as `instability`, as
+    /// measured by `best_move_instability`.
+    ///
+    /// Every unit of instability buys another `INSTABILITY_EXTENSION` of the search time, up
+    /// to `MAX_INSTABILITY_FACTOR` times it, and never past the same ceiling as the search
+    /// time. With a fixed time per move there is nothing to extend into.
+    #[must_use]
+    pub fn extended_search_time(&self, instability: f32) -> f32 {
+        let time = self.search_time();
+        if matches!(self.mode, TimeMode::St(_)) {
+            return time;
+        }
+        let factor = INSTABILITY_EXTENSION
+            .mul_add(instability, 1.0)
+            .min(MAX_INSTABILITY_FACTOR);
+        (time * factor).min(self.max_search_time()).max(time)
+    }
+
+    /// Update how unsettled the best move is after an iteration: older changes count half as
+    /// much each iteration, so only a best move that changed recently extends the search.
+    #[must_use]
+    pub fn best_move_instability(instability: f32, best_move_changed: bool) -> f32 {
+        instability / 2.0 + f32::from(u8::from(best_move_changed))
+    }
 }
 
 /// Time controls can be operating in several modes which have different interpretations
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TimeMode {
     /// St mode has a fixed seconds per move
     St(u32),
@@ -92,20 +234,25 @@ impl FromStr for TimeMode {
         // First part of the strin
         let cmd = parts.next().unwrap();
         let args = parts.collect::<Vec<_>>();
+        // A GUI can leave arguments out, so a missing one is an error rather than a panic
+        let arg = |i: usize| args.get(i).copied().ok_or(());
         match cmd {
             "st" => {
                 // Parse out seconds per move
-                let secs = u32::from_str(args[0]).map_err(|_| ())?;
+                let secs = u32::from_str(arg(0)?).map_err(|_| ())?;
                 Ok(Self::St(secs))
             }
             "level" => {
                 // Figure out if the mode is incremental or classical
-                let mps = u32::from_str(args[0]).map_err(|_| ())?;
-                let base = Self::parse_time(args[1]).ok_or(())?;
+                let mps = u32::from_str(arg(0)?).map_err(|_| ())?;
+                let base = Self::parse_time(arg(1)?).ok_or(())?;
                 if mps == 0 {
                     // Incremental
                     // In incremental we need the increment to add after each move
-                    let inc = f32::from_str(args[2]).map_err(|_| ())?;
+                    let inc = f32::from_str(arg(2)?)
+                        .ok()
+                        .filter(|inc| inc.is_finite() && *inc >= 0.0)
+                        .ok_or(())?;
                     Ok(Self::Incremental {
                         base,
                         increment: inc,
@@ -121,16 +268,337 @@ impl FromStr for TimeMode {
     }
 }
 
+/// Formats the time control as the xboard command that sets it
+impl Display for TimeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // `level` takes the base time in minutes, or minutes:seconds
+        let minutes = |base: f32| {
+            let whole = (base / 60.0).floor();
+            format!("{}:{}", whole, 60.0f32.mul_add(-whole, base))
+        };
+        match self {
+            Self::St(secs) => write!(f, "st {secs}"),
+            Self::Incremental { base, increment } => {
+                write!(f, "level 0 {} {increment}", minutes(*base))
+            }
+            Self::Classical { base, mps } => write!(f, "level {mps} {} 0", minutes(*base)),
+        }
+    }
+}
+
 impl TimeMode {
     /// Parses a time that might be in min or min:sec format
     fn parse_time(s: &str) -> Option<f32> {
-        if let Some(sep) = s.find(':') {
+        let time = if let Some(sep) = s.find(':') {
             let min_part = f32::from_str(&s[0..sep]).ok()?;
             let sec_part = f32::from_str(&s[sep + 1..]).ok()?;
-            Some(60.0f32.mul_add(min_part, sec_part))
+            60.0f32.mul_add(min_part, sec_part)
         } else {
             let min = f32::from_str(s).ok()?;
-            Some(60.0 * min)
+            60.0 * min
+        };
+        // Neither NaN nor a negative clock makes sense to plan with
+        (time.is_finite() && time >= 0.0).then_some(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{TimeControl, TimeMode};
+    use crate::SearchParams;
+
+    #[test]
+    fn commands_round_trip() {
+        for command in [
+            "st 5",
+            "level 0 0:10 0.1",
+            "level 40 5:0 0",
+            "level 0 1:30 2",
+        ] {
+            let mode = TimeMode::from_str(command).unwrap();
+            assert_eq!(mode.to_string(), command);
         }
+        assert_eq!(
+            TimeMode::from_str("level 0 2 1").unwrap().to_string(),
+            "level 0 2:0 1"
+        );
+    }
+
+    #[test]
+    fn malformed_commands_rejected() {
+        for command in [
+            "st",
+            "st abc",
+            "st -5",
+            "level",
+            "level 40",
+            "level 40 foo 0",
+            "level x 5 0",
+            "level 0 5",
+            "level 0 5 bar",
+            "level 0 -5 0",
+            "level 0 nan 0",
+            "level 0 5 inf",
+        ] {
+            assert!(TimeMode::from_str(command).is_err(), "{command} parsed");
+        }
+    }
+
+    #[test]
+    fn tuned_allocation() {
+        let mode = TimeMode::Incremental {
+            base: 60.0,
+            increment: 1.0,
+        };
+        // The default parameters hold back 30ms and plan for 30 moves.
+        let mut clock = TimeControl::new(mode);
+        clock.set_params(&SearchParams::DEFAULT);
+        assert!((clock.search_time() - (61.0 - 0.03) / 30.0).abs() < 1e-4);
+
+        clock.set_params(&SearchParams {
+            move_overhead_ms: 500,
+            increment_moves: 20,
+            ..SearchParams::DEFAULT
+        });
+        assert!((clock.search_time() - (61.0 - 0.5) / 20.0).abs() < 1e-4);
+
+        let mut clock = TimeControl::new(TimeMode::St(5));
+        clock.set_params(&SearchParams {
+            move_overhead_ms: 500,
+            ..SearchParams::DEFAULT
+        });
+        assert!((clock.search_time() - 4.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn large_overheads() {
+        let modes = [
+            TimeMode::St(1),
+            TimeMode::St(30),
+            TimeMode::Incremental {
+                base: 60.0,
+                increment: 0.0,
+            },
+            TimeMode::Incremental {
+                base: 60.0,
+                increment: 2.0,
+            },
+            TimeMode::Classical {
+                base: 300.0,
+                mps: 40,
+            },
+        ];
+        for mode in modes {
+            for overhead in [0, 300, 1000, 5000] {
+                for centiseconds in [0.0, 50.0, 600.0, 6000.0] {
+                    let mut clock = TimeControl::new(mode);
+                    clock.set_params(&SearchParams {
+                        move_overhead_ms: overhead,
+                        ..SearchParams::DEFAULT
+                    });
+                    if !matches!(mode, TimeMode::St(_)) {
+                        clock.set_remaining(centiseconds);
+                    }
+                    let time = clock.search_time();
+                    let extended = clock.extended_search_time(10.0);
+                    assert!(time >= 0.005, "{mode} {overhead} {centiseconds}: {time}");
+                    assert!(
+                        extended >= time,
+                        "{mode} {overhead} {centiseconds}: {extended}"
+                    );
+                    // The overhead stays in hand, unless that leaves less than the least search.
+                    let spare = clock.remaining() - overhead as f32 / 1000.0;
+                    assert!(time <= spare.max(0.005) + 1e-6, "{mode} {overhead}: {time}");
+                    assert!(
+                        extended <= spare.max(time) + 1e-6,
+                        "{mode} {overhead}: {extended}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsettled_searches_get_more_time() {
+        let clock = TimeControl::new(TimeMode::Incremental {
+            base: 60.0,
+            increment: 1.0,
+        });
+        let budget = clock.search_time();
+        // The best move after each iteration.
+        let instability = |best_moves: &[&str]| {
+            best_moves.windows(2).fold(0.0, |instability, pair| {
+                TimeControl::best_move_instability(instability, pair[0] != pair[1])
+            })
+        };
+
+        let stable = instability(&["e2e4"; 10]);
+        assert!((clock.extended_search_time(stable) - budget).abs() < 1e-6);
+        // Changes early on are forgotten by the time the search is deep.
+        let settled = instability(&[
+            "d2d4", "e2e4", "d2d4", "e2e4", "e2e4", "e2e4", "e2e4", "e2e4",
+        ]);
+        assert!(clock.extended_search_time(settled) < budget * 1.05);
+
+        let flipped = instability(&["e2e4", "e2e4", "e2e4", "e2e4", "d2d4"]);
+        assert!(clock.extended_search_time(flipped) > budget * 1.25);
+        let flipping = instability(&["e2e4", "d2d4", "e2e4", "d2d4", "e2e4", "d2d4"]);
+        assert!(clock.extended_search_time(flipping) > clock.extended_search_time(flipped));
+        assert!(clock.extended_search_time(flipping) / budget <= 2.0 + 1e-6);
+
+        // Fixed time per move and a nearly empty clock leave nothing to extend into.
+        let clock = TimeControl::new(TimeMode::St(5));
+        assert!((clock.extended_search_time(flipping) - clock.search_time()).abs() < 1e-6);
+        let mut clock = TimeControl::new(TimeMode::Incremental {
+            base: 60.0,
+            increment: 0.0,
+        });
+        clock.set_remaining(3.0);
+        assert!(clock.extended_search_time(100.0) <= 0.03);
+    }
+
+    #[test]
+    fn referee_clock() {
+        let mut clock = TimeControl::new(TimeMode::Incremental {
+            base: 10.0,
+            increment: 0.5,
+        });
+        assert!(clock.elapse(2.0));
+        assert!((clock.remaining() - 8.5).abs() < 1e-6);
+        assert!(!clock.elapse(9.0));
+
+        // A new session's time arrives after every `mps` moves.
+        let mut clock = TimeControl::new(TimeMode::Classical { base: 10.0, mps: 2 });
+        assert!(clock.elapse(4.0));
+        assert!(clock.elapse(4.0));
+        assert!((clock.remaining() - 12.0).abs() < 1e-6);
+
+        let mut clock = TimeControl::new(TimeMode::St(1));
+        assert!(clock.elapse(0.9));
+        assert!((clock.remaining() - 1.0).abs() < 1e-6);
+        assert!(!clock.elapse(1.1));
+    }
+
+    #[test]
+    fn opponent_time_adjustment() {
+        let modes = [
+            TimeMode::St(5),
+            TimeMode::Incremental {
+                base: 60.0,
+                increment: 1.0,
+            },
+            TimeMode::Classical {
+                base: 300.0,
+                mps: 40,
+            },
+        ];
+        // Our time and theirs, in centiseconds.
+        let clocks = [
+            (6000.0, 6000.0),
+            (6000.0, 500.0),
+            (6000.0, 0.0),
+            (500.0, 6000.0),
+            (1.0, 6000.0),
+            (0.0, 0.0),
+        ];
+        for mode in modes {
+            for (ours, theirs) in clocks {
+                for score in [None, Some(0), Some(-500)] {
+                    let mut clock = TimeControl::new(mode);
+                    clock.set_remaining(ours);
+                    let unadjusted = clock.search_time();
+                    clock.set_opponent_remaining(theirs);
+                    if let Some(score) = score {
+                        clock.set_last_score(score);
+                    }
+                    let time = clock.search_time();
+                    let (lower, upper) = (unadjusted * 0.7, unadjusted * 1.3);
+                    assert!(time >= 0.0, "{mode} {ours} {theirs}: {time}");
+                    assert!(
+                        time >= lower - 1e-6 && time <= upper + 1e-6,
+                        "{mode} {ours} {theirs}: {time} against {unadjusted}"
+                    );
+                    if matches!(mode, TimeMode::St(_)) || ours <= theirs {
+                        assert!((time - unadjusted).abs() < 1e-6);
+                    }
+                }
+            }
+        }
+
+        // An opponent in a time scramble makes us play faster, unless we are losing.
+        let mode = TimeMode::Incremental {
+            base: 60.0,
+            increment: 0.0,
+        };
+        let mut clock = TimeControl::new(mode);
+        let normal = clock.search_time();
+        clock.set_opponent_remaining(500.0);
+        let scramble = clock.search_time();
+        assert!(scramble < normal);
+        clock.set_last_score(-300);
+        assert!(clock.search_time() > normal);
+    }
+
+    #[test]
+    fn classical_period_boundary() {
+        // 40 moves in 5 minutes.
+        let mode = TimeMode::Classical {
+            base: 300.0,
+            mps: 40,
+        };
+        let at_move = |moves: u32, centiseconds: f32| {
+            let mut clock = TimeControl::new(mode);
+            for _ in 0..moves {
+                clock.increment_moves();
+            }
+            clock.set_remaining(centiseconds);
+            clock
+        };
+
+        // The last move of the period still keeps time in hand for the move after it.
+        let clock = at_move(39, 1000.0);
+        assert_eq!(clock.moves_to_go(), Some(1));
+        let last = clock.search_time();
+        assert!(last > 0.0 && last < clock.remaining() / 2.0 + 1e-6);
+
+        // The 41st move starts a new period, with the next session's time on the clock.
+        let clock = at_move(40, 1000.0 + 30_000.0);
+        assert_eq!(clock.moves_to_go(), Some(40));
+        let first = clock.search_time();
+        assert!((first - (310.0 - 0.03) / 41.0).abs() < 1e-3);
+
+        let clock = at_move(41, 1000.0 + 30_000.0 - 100.0);
+        assert_eq!(clock.moves_to_go(), Some(39));
+        assert!(clock.search_time() > first * 0.99);
+
+        // Periods repeat for the whole game.
+        assert_eq!(at_move(79, 1000.0).moves_to_go(), Some(1));
+        assert_eq!(at_move(80, 1000.0).moves_to_go(), Some(40));
+        assert_eq!(TimeControl::new(TimeMode::St(5)).moves_to_go(), None);
+    }
+
+    #[test]
+    fn unsettled_searches_keep_period_reserve() {
+        // 2 moves in 10 seconds, with the best move changing at every iteration.
+        let mode = TimeMode::Classical { base: 10.0, mps: 2 };
+        let flipping = (0..20).fold(0.0, |instability, _| {
+            TimeControl::best_move_instability(instability, true)
+        });
+
+        let mut clock = TimeControl::new(mode);
+        let budget = clock.search_time();
+        assert!(clock.extended_search_time(flipping) > budget * 1.25);
+        // Both moves of the period together still leave the reserve on the clock.
+        assert!(clock.elapse(clock.extended_search_time(flipping)));
+        assert_eq!(clock.moves_to_go(), Some(1));
+        let last = clock.extended_search_time(flipping);
+        assert!(last <= (clock.remaining() - 0.03) / 2.0 + 1e-6);
+
+        // Nor does a lead on the clock spend it.
+        clock.set_opponent_remaining(1.0);
+        clock.set_last_score(-300);
+        assert!(clock.search_time() <= (clock.remaining() - 0.03) / 2.0 + 1e-6);
     }
 }
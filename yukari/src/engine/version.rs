@@ -0,0 +1,22 @@
+/// The engine version, from the crate manifest
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit the engine was built from, or `unknown` outside a git checkout
+pub const GIT_HASH: &str = env!("YUKARI_GIT_HASH");
+
+/// The name reported to interfaces, in the xboard `myname` feature and UCI `id name`
+#[must_use]
+pub fn engine_name() -> String {
+    format!("Yukari {VERSION}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{engine_name, GIT_HASH};
+
+    #[test]
+    fn name_has_package_version() {
+        assert!(engine_name().contains(env!("CARGO_PKG_VERSION")));
+        assert!(!GIT_HASH.is_empty());
+    }
+}
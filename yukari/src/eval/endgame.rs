@@ -0,0 +1,100 @@
+//! Terms for the deep endgame, where the kings are the strongest pieces left.
+//!
+//! With little material besides kings and pawns, the piece-square tables alone do not know
+//! that kings belong near the passed pawns, or that a pawn the enemy king cannot catch will
+//! queen. These terms say so, and only apply once almost every piece is gone.
+
+use yukari_movegen::{Board, Colour, Square};
+
+use super::{relative_rank, EvalParams};
+
+/// Highest game phase counted as the deep endgame: a minor piece each at most.
+const DEEP_ENDGAME_PHASE: u8 = 2;
+
+/// Endgame bonus per step the king is closer to the centre than the edge.
+pub(super) const KING_CENTRE_EG: i32 = 4;
+/// Endgame bonus per square our king is closer than theirs to the square in front of one
+/// of our passed pawns.
+pub(super) const PASSER_KING_EG: i32 = 6;
+/// Endgame bonus for a passed pawn the enemy king cannot catch, when the enemy has nothing
+/// else to stop it with.
+pub(super) const UNSTOPPABLE_PASSER_EG: i32 = 400;
+
+/// The deep endgame terms of a position, from white's point of view; zero while more
+/// material is left.
+pub(super) fn endgame(board: &Board, params: &EvalParams) -> i32 {
+    if board.phase() > DEEP_ENDGAME_PHASE {
+        return 0;
+    }
+    side_endgame(board, Colour::White, params) - side_endgame(board, Colour::Black, params)
+}
+
+/// The deep endgame terms for one side.
+fn side_endgame(board: &Board, colour: Colour, params: &EvalParams) -> i32 {
+    let king = board.king_square(colour);
+    let their_king = board.king_square(!colour);
+    let mut score = params.king_centre_eg * (3 - centre_distance(king));
+
+    // Only the king can stop a pawn when there is nothing but pawns to help it.
+    let their_pieces = board.pieces_of_colour(!colour) & !(board.pawns() | board.kings());
+    let mut unstoppable = false;
+    for pawn in board.pawns() & board.pieces_of_colour(colour) {
+        let square = board.square_of_piece(pawn);
+        if !is_passed(board, square, colour) {
+            continue;
+        }
+        let Some(stop) = square.relative_north(colour) else {
+            continue;
+        };
+        let distance = |king: Square| i32::from(stop.distance(king));
+        score += params.passer_king_eg * (distance(their_king) - distance(king));
+
+        if their_pieces.empty() {
+            unstoppable |= outside_square(board, square, colour);
+        }
+    }
+    // One unstoppable pawn wins the race; a second adds nothing.
+    if unstoppable {
+        score += params.unstoppable_passer_eg;
+    }
+    score
+}
+
+/// The number of king moves from `square` to the nearest of the four centre squares.
+fn centre_distance(square: Square) -> i32 {
+    let square = square.into_inner();
+    let centre = |x: u8| if x < 4 { 3 - x } else { x - 4 };
+    i32::from(centre(square >> 3).max(centre(square & 7)))
+}
+
+/// Check whether no enemy pawn stands in front of the pawn on `square`, on its file or the
+/// files next to it.
+fn is_passed(board: &Board, square: Square, colour: Colour) -> bool {
+    let file = square.into_inner() & 7;
+    let rank = relative_rank(square, colour);
+    (board.pawns() & board.pieces_of_colour(!colour))
+        .into_iter()
+        .all(|pawn| {
+            let theirs = board.square_of_piece(pawn);
+            (theirs.into_inner() & 7).abs_diff(file) > 1 || relative_rank(theirs, colour) <= rank
+        })
+}
+
+/// The rule of the square: check whether the enemy king is too far away to catch the pawn
+/// on `square` before it queens.
+fn outside_square(board: &Board, square: Square, colour: Colour) -> bool {
+    let file = square.into_inner() & 7;
+    let promotion = match colour {
+        Colour::White => 56 + file,
+        Colour::Black => file,
+    };
+    let promotion = Square::try_from(promotion).expect("promotion square out of bounds");
+    // A pawn on its starting rank can push two squares at once.
+    let pawn_moves = i32::from((7 - relative_rank(square, colour)).min(5));
+    let mut king_moves = i32::from(promotion.distance(board.king_square(!colour)));
+    // The king gains a tempo if it is its turn.
+    if board.side() != colour {
+        king_moves -= 1;
+    }
+    pawn_moves < king_moves
+}
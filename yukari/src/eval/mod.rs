@@ -0,0 +1,1253 @@
+//! Static evaluation of positions.
+//!
+//! The evaluation is a tapered PeSTO-style piece-square evaluation with king tropism, mobility,
+//! rooks on open files and the seventh rank, the bishop pair and minor pieces valued by pawn
+//! count, deep endgame king activity and passed pawn races, endgame scaling and fifty-move
+//! fading. [`evaluate`] scores a position from scratch; [`EvalState`] keeps the piece-square
+//! part and the piece counts up to date move by move for the search.
+//! Every weight lives in [`EvalParams`], so a tuner can score positions with its own weights.
+
+mod endgame;
+
+use yukari_movegen::{Board, Colour, Move, MoveType, Piece, Square};
+
+use crate::search::MAX_EVAL;
+use endgame::{endgame, KING_CENTRE_EG, PASSER_KING_EG, UNSTOPPABLE_PASSER_EG};
+
+// CREDIT: These tables come from PeSTO by Ronald Friedrich.
+
+const MAT_MG: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+
+const MAT_EG: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+#[rustfmt::skip]
+const PST_MG: [[i32; 64]; 6] = [
+    // Pawns
+    [
+        0,   0,   0,   0,   0,   0,  0,   0,
+        98, 134,  61,  95,  68, 126, 34, -11,
+        -6,   7,  26,  31,  65,  56, 25, -20,
+       -14,  13,   6,  21,  23,  12, 17, -23,
+       -27,  -2,  -5,  12,  17,   6, 10, -25,
+       -26,  -4,  -4, -10,   3,   3, 33, -12,
+       -35,  -1, -20, -23, -15,  24, 38, -22,
+         0,   0,   0,   0,   0,   0,  0,   0,
+    ],
+    // Knights
+    [
+        -167, -89, -34, -49,  61, -97, -15, -107,
+        -73, -41,  72,  36,  23,  62,   7,  -17,
+        -47,  60,  37,  65,  84, 129,  73,   44,
+         -9,  17,  19,  53,  37,  69,  18,   22,
+        -13,   4,  16,  13,  28,  19,  21,   -8,
+        -23,  -9,  12,  10,  19,  17,  25,  -16,
+        -29, -53, -12,  -3,  -1,  18, -14,  -19,
+       -105, -21, -58, -33, -17, -28, -19,  -23,
+    ],
+    // Bishops
+    [
+        -29,   4, -82, -37, -25, -42,   7,  -8,
+        -26,  16, -18, -13,  30,  59,  18, -47,
+        -16,  37,  43,  40,  35,  50,  37,  -2,
+         -4,   5,  19,  50,  37,  37,   7,  -2,
+         -6,  13,  13,  26,  34,  12,  10,   4,
+          0,  15,  15,  15,  14,  27,  18,  10,
+          4,  15,  16,   0,   7,  21,  33,   1,
+        -33,  -3, -14, -21, -13, -12, -39, -21,
+    ],
+    // Rooks
+    [
+        32,  42,  32,  51, 63,  9,  31,  43,
+        27,  32,  58,  62, 80, 67,  26,  44,
+        -5,  19,  26,  36, 17, 45,  61,  16,
+       -24, -11,   7,  26, 24, 35,  -8, -20,
+       -36, -26, -12,  -1,  9, -7,   6, -23,
+       -45, -25, -16, -17,  3,  0,  -5, -33,
+       -44, -16, -20,  -9, -1, 11,  -6, -71,
+       -19, -13,   1,  17, 16,  7, -37, -26,
+    ],
+    // Queens
+    [
+        -28,   0,  29,  12,  59,  44,  43,  45,
+        -24, -39,  -5,   1, -16,  57,  28,  54,
+        -13, -17,   7,   8,  29,  56,  47,  57,
+        -27, -27, -16, -16,  -1,  17,  -2,   1,
+         -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+        -14,   2, -11,  -2,  -5,   2,  14,   5,
+        -35,  -8,  11,   2,   8,  15,  -3,   1,
+         -1, -18,  -9,  10, -15, -25, -31, -50,
+    ],
+    // Kings
+    [
+        -65,  23,  16, -15, -56, -34,   2,  13,
+        29,  -1, -20,  -7,  -8,  -4, -38, -29,
+        -9,  24,   2, -16, -20,   6,  22, -22,
+       -17, -20, -12, -27, -30, -25, -14, -36,
+       -49,  -1, -27, -39, -46, -44, -33, -51,
+       -14, -14, -22, -46, -44, -30, -15, -27,
+         1,   7,  -8, -64, -43, -16,   9,   8,
+       -15,  36,  12, -54,   8, -28,  24,  14,
+    ]
+];
+
+#[rustfmt::skip]
+const PST_EG: [[i32; 64]; 6] = [
+    // Pawns
+    [
+        0,   0,   0,   0,   0,   0,   0,   0,
+        178, 173, 158, 134, 147, 132, 165, 187,
+         94, 100,  85,  67,  56,  53,  82,  84,
+         32,  24,  13,   5,  -2,   4,  17,  17,
+         13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+          4,   7,  -6,   1,   0,  -5,  -1,  -8,
+         13,   8,   8,  10,  13,   0,   2,  -7,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Knights
+    [
+        -58, -38, -13, -28, -31, -27, -63, -99,
+        -25,  -8, -25,  -2,  -9, -25, -24, -52,
+        -24, -20,  10,   9,  -1,  -9, -19, -41,
+        -17,   3,  22,  22,  22,  11,   8, -18,
+        -18,  -6,  16,  25,  16,  17,   4, -18,
+        -23,  -3,  -1,  15,  10,  -3, -20, -22,
+        -42, -20, -10,  -5,  -2, -20, -23, -44,
+        -29, -51, -23, -15, -22, -18, -50, -64,
+    ],
+    // Bishops
+    [
+        -14, -21, -11,  -8, -7,  -9, -17, -24,
+        -8,  -4,   7, -12, -3, -13,  -4, -14,
+         2,  -8,   0,  -1, -2,   6,   0,   4,
+        -3,   9,  12,   9, 14,  10,   3,   2,
+        -6,   3,  13,  19,  7,  10,  -3,  -9,
+       -12,  -3,   8,  10, 13,   3,  -7, -15,
+       -14, -18,  -7,  -1,  4,  -9, -15, -27,
+       -23,  -9, -23,  -5, -9, -16,  -5, -17,
+    ],
+    // Rooks
+    [
+        13, 10, 18, 15, 12,  12,   8,   5,
+        11, 13, 13, 11, -3,   3,   8,   3,
+         7,  7,  7,  5,  4,  -3,  -5,  -3,
+         4,  3, 13,  1,  2,   1,  -1,   2,
+         3,  5,  8,  4, -5,  -6,  -8, -11,
+        -4,  0, -5, -1, -7, -12,  -8, -16,
+        -6, -6,  0,  2, -9,  -9, -11,  -3,
+        -9,  2,  3, -1, -5, -13,   4, -20,
+    ],
+    // Queens
+    [
+        -9,  22,  22,  27,  27,  19,  10,  20,
+        -17,  20,  32,  41,  58,  25,  30,   0,
+        -20,   6,   9,  49,  47,  35,  19,   9,
+          3,  22,  24,  45,  57,  40,  57,  36,
+        -18,  28,  19,  47,  31,  34,  39,  23,
+        -16, -27,  15,   6,   9,  17,  10,   5,
+        -22, -23, -30, -16, -16, -23, -36, -32,
+        -33, -28, -22, -43,  -5, -32, -20, -41,
+    ],
+    // Kings
+    [
+        -74, -35, -18, -18, -11,  15,   4, -17,
+        -12,  17,  14,  17,  17,  38,  23,  11,
+         10,  17,  23,  15,  20,  45,  44,  13,
+         -8,  22,  24,  27,  26,  33,  26,   3,
+        -18,  -4,  21,  24,  27,  23,   9, -11,
+        -19,  -3,  11,  21,  23,  16,   7,  -9,
+        -27, -11,   4,  13,  14,   4,  -5, -17,
+        -53, -34, -21, -11, -28, -14, -24, -43
+    ]
+];
+
+/// Total phase of the starting position; a phase of zero is a bare endgame.
+const PHASE_MAX: i32 = 24;
+
+/// Middlegame bonus per square closer than the maximum distance to the enemy king.
+const TROPISM_MG: [i32; 6] = [0, 3, 1, 1, 4, 0];
+
+/// Bonus per square attacked, by piece type.
+const MOBILITY_MG: [i32; 6] = [0, 4, 3, 2, 1, 0];
+const MOBILITY_EG: [i32; 6] = [0, 4, 3, 4, 2, 0];
+
+/// Bonus for a rook on a file without pawns.
+const ROOK_OPEN_MG: i32 = 40;
+const ROOK_OPEN_EG: i32 = 15;
+/// Bonus for a rook on a file with only enemy pawns.
+const ROOK_SEMI_OPEN_MG: i32 = 20;
+const ROOK_SEMI_OPEN_EG: i32 = 8;
+/// Bonus for a rook on the seventh rank that hems in the enemy king or attacks pawns there.
+const ROOK_SEVENTH_MG: i32 = 20;
+const ROOK_SEVENTH_EG: i32 = 35;
+
+const BISHOP_PAIR_MG: i32 = 25;
+const BISHOP_PAIR_EG: i32 = 50;
+
+/// Pawn count at which knights and bishops are worth their material value and nothing more.
+const MINOR_PAWNS_NEUTRAL: i32 = 5;
+const KNIGHT_PAWN_ADJUST: i32 = 4;
+const BISHOP_PAWN_ADJUST: i32 = 3;
+
+/// Endgame scale factors, out of `SCALE_NORMAL`.
+const SCALE_NORMAL: i32 = 64;
+const SCALE_OPPOSITE_BISHOPS: i32 = 32;
+const SCALE_ROOK_ONE_WING: i32 = 40;
+const SCALE_PAWNLESS_MINOR_UP: i32 = 4;
+
+/// The weights of the evaluation, indexed by piece type (pawn to king).
+///
+/// Piece-square tables are indexed by square from white's point of view, with a8 first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    /// Middlegame material values
+    pub mat_mg: [i32; 6],
+    /// Endgame material values
+    pub mat_eg: [i32; 6],
+    /// Middlegame piece-square tables
+    pub pst_mg: [[i32; 64]; 6],
+    /// Endgame piece-square tables
+    pub pst_eg: [[i32; 64]; 6],
+    /// Middlegame bonus per square closer than the maximum distance to the enemy king
+    pub tropism_mg: [i32; 6],
+    /// Middlegame bonus per square attacked
+    pub mobility_mg: [i32; 6],
+    /// Endgame bonus per square attacked
+    pub mobility_eg: [i32; 6],
+    /// Middlegame bonus for a rook on a file without pawns
+    pub rook_open_mg: i32,
+    /// Endgame bonus for a rook on a file without pawns
+    pub rook_open_eg: i32,
+    /// Middlegame bonus for a rook on a file with only enemy pawns
+    pub rook_semi_open_mg: i32,
+    /// Endgame bonus for a rook on a file with only enemy pawns
+    pub rook_semi_open_eg: i32,
+    /// Middlegame bonus for a rook on the seventh rank with the enemy king or pawns to attack
+    pub rook_seventh_mg: i32,
+    /// Endgame bonus for a rook on the seventh rank with the enemy king or pawns to attack
+    pub rook_seventh_eg: i32,
+    /// Deep endgame bonus per step the king is closer to the centre than the edge
+    pub king_centre_eg: i32,
+    /// Deep endgame bonus per square our king is closer than theirs to a passed pawn's path
+    pub passer_king_eg: i32,
+    /// Deep endgame bonus for a passed pawn the enemy king cannot catch
+    pub unstoppable_passer_eg: i32,
+    /// Middlegame bonus for having two or more bishops
+    pub bishop_pair_mg: i32,
+    /// Endgame bonus for having two or more bishops
+    pub bishop_pair_eg: i32,
+    /// Bonus per knight for each of our pawns above five, and penalty for each below
+    pub knight_pawn_adjust: i32,
+    /// Bonus per bishop for each of our pawns below five, and penalty for each above
+    pub bishop_pawn_adjust: i32,
+    /// Scale, out of 64, of endings with opposite-coloured bishops and only pawns besides
+    pub scale_opposite_bishops: i32,
+    /// Scale, out of 64, of rook endings a pawn up with every pawn on one wing
+    pub scale_rook_one_wing: i32,
+    /// Scale, out of 64, of pawnless endings a single minor piece up
+    pub scale_pawnless_minor_up: i32,
+}
+
+impl EvalParams {
+    /// The weights the engine plays with.
+    pub const DEFAULT: Self = Self {
+        mat_mg: MAT_MG,
+        mat_eg: MAT_EG,
+        pst_mg: PST_MG,
+        pst_eg: PST_EG,
+        tropism_mg: TROPISM_MG,
+        mobility_mg: MOBILITY_MG,
+        mobility_eg: MOBILITY_EG,
+        rook_open_mg: ROOK_OPEN_MG,
+        rook_open_eg: ROOK_OPEN_EG,
+        rook_semi_open_mg: ROOK_SEMI_OPEN_MG,
+        rook_semi_open_eg: ROOK_SEMI_OPEN_EG,
+        rook_seventh_mg: ROOK_SEVENTH_MG,
+        rook_seventh_eg: ROOK_SEVENTH_EG,
+        king_centre_eg: KING_CENTRE_EG,
+        passer_king_eg: PASSER_KING_EG,
+        unstoppable_passer_eg: UNSTOPPABLE_PASSER_EG,
+        bishop_pair_mg: BISHOP_PAIR_MG,
+        bishop_pair_eg: BISHOP_PAIR_EG,
+        knight_pawn_adjust: KNIGHT_PAWN_ADJUST,
+        bishop_pawn_adjust: BISHOP_PAWN_ADJUST,
+        scale_opposite_bishops: SCALE_OPPOSITE_BISHOPS,
+        scale_rook_one_wing: SCALE_ROOK_ONE_WING,
+        scale_pawnless_minor_up: SCALE_PAWNLESS_MINOR_UP,
+    };
+
+    /// Every weight as one flat vector, in a fixed order, for a tuner to adjust.
+    ///
+    /// [`EvalParams::from_weights`] turns the vector back into params, so a tuner never keeps
+    /// a copy of the tables of its own that could drift from the ones the engine plays with.
+    #[must_use]
+    pub fn to_weights(&self) -> Vec<i32> {
+        self.clone().weights_mut().map(|weight| *weight).collect()
+    }
+
+    /// Params from a vector laid out like the one [`EvalParams::to_weights`] gives, or `None`
+    /// if it is the wrong length.
+    #[must_use]
+    pub fn from_weights(weights: &[i32]) -> Option<Self> {
+        let mut params = Self::DEFAULT;
+        {
+            let mut slots = params.weights_mut();
+            for &weight in weights {
+                *slots.next()? = weight;
+            }
+            if slots.next().is_some() {
+                return None;
+            }
+        }
+        Some(params)
+    }
+
+    /// Every weight in the order of [`EvalParams::to_weights`].
+    fn weights_mut(&mut self) -> impl Iterator<Item = &mut i32> {
+        self.mat_mg
+            .iter_mut()
+            .chain(self.mat_eg.iter_mut())
+            .chain(self.pst_mg.iter_mut().flatten())
+            .chain(self.pst_eg.iter_mut().flatten())
+            .chain(self.tropism_mg.iter_mut())
+            .chain(self.mobility_mg.iter_mut())
+            .chain(self.mobility_eg.iter_mut())
+            .chain([
+                &mut self.rook_open_mg,
+                &mut self.rook_open_eg,
+                &mut self.rook_semi_open_mg,
+                &mut self.rook_semi_open_eg,
+                &mut self.rook_seventh_mg,
+                &mut self.rook_seventh_eg,
+                &mut self.king_centre_eg,
+                &mut self.passer_king_eg,
+                &mut self.unstoppable_passer_eg,
+                &mut self.bishop_pair_mg,
+                &mut self.bishop_pair_eg,
+                &mut self.knight_pawn_adjust,
+                &mut self.bishop_pawn_adjust,
+                &mut self.scale_opposite_bishops,
+                &mut self.scale_rook_one_wing,
+                &mut self.scale_pawnless_minor_up,
+            ])
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Evaluate a position from scratch, in centipawns from white's point of view.
+///
+/// This is the full evaluation, mobility included, that quiescence stand-pat uses.
+#[must_use]
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    EvalState::eval(board, params).get_with_mobility(board, Colour::White, params)
+}
+
+/// Halfmove clock past which scores fade toward the fifty-move draw.
+const FIFTY_MOVE_FADE: u32 = 60;
+
+/// Centipawns a score gives up to the halfmove clock by the time it reaches `FIFTY_MOVE_FADE`.
+const FIFTY_MOVE_DRIFT: i32 = 20;
+
+/// Shrink a score toward zero as the halfmove clock grows, so the search prefers making
+/// progress to shuffling in a won position, and shuffling toward the fifty-move draw to
+/// committing to anything in a lost one.
+///
+/// Before `FIFTY_MOVE_FADE` the score drifts by at most `FIFTY_MOVE_DRIFT`, well under a
+/// pawn, so giving up material to reset the clock never pays. Past it the draw is close
+/// enough that the score fades to nothing at the draw. Neither changes the sign of a score.
+fn fifty_move_scale(board: &Board, score: i32) -> i32 {
+    let clock = board.halfmove_clock().min(100);
+    let drift = FIFTY_MOVE_DRIFT * clock.min(FIFTY_MOVE_FADE) as i32 / FIFTY_MOVE_FADE as i32;
+    let score = score.signum() * (score.abs() - drift).max(0);
+    if clock <= FIFTY_MOVE_FADE {
+        return score;
+    }
+    score * (100 - clock) as i32 / (100 - FIFTY_MOVE_FADE) as i32
+}
+
+/// Returns true if a square is a light square.
+const fn is_light_square(square: Square) -> bool {
+    let square = square.into_inner();
+    ((square >> 3) + (square & 7)) & 1 == 1
+}
+
+/// Scale down the score of material configurations which are drawish despite a material edge.
+fn endgame_scale(board: &Board, score: i32, params: &EvalParams) -> i32 {
+    let strong = if score >= 0 {
+        Colour::White
+    } else {
+        Colour::Black
+    };
+    let white = board.pieces_of_colour(Colour::White);
+    let black = board.pieces_of_colour(Colour::Black);
+
+    // Pieces other than kings and pawns are the only ones that matter for the configuration.
+    let minors_majors = board.knights() | board.bishops() | board.rooks() | board.queens();
+    let pawns = board.pawns();
+
+    // Opposite-coloured bishops, with nothing but pawns besides.
+    if minors_majors == board.bishops()
+        && (board.bishops() & white).count_ones() == 1
+        && (board.bishops() & black).count_ones() == 1
+    {
+        let mut bishops = board.bishops().into_iter();
+        let (Some(first), Some(second)) = (bishops.next(), bishops.next()) else {
+            return SCALE_NORMAL;
+        };
+        if is_light_square(board.square_of_piece(first))
+            != is_light_square(board.square_of_piece(second))
+        {
+            return params.scale_opposite_bishops;
+        }
+    }
+
+    // Rook endings where the stronger side is a pawn up, with all pawns on one wing.
+    if minors_majors == board.rooks()
+        && (board.rooks() & white).count_ones() == 1
+        && (board.rooks() & black).count_ones() == 1
+    {
+        let strong_pawns = (pawns & board.pieces_of_colour(strong)).count_ones();
+        let weak_pawns = (pawns & board.pieces_of_colour(!strong)).count_ones();
+        if strong_pawns <= weak_pawns + 1 {
+            let mut queenside = false;
+            let mut kingside = false;
+            for pawn in pawns {
+                if board.square_of_piece(pawn).into_inner() & 7 < 4 {
+                    queenside = true;
+                } else {
+                    kingside = true;
+                }
+            }
+            if !(queenside && kingside) {
+                return params.scale_rook_one_wing;
+            }
+        }
+    }
+
+    // Without pawns, a single extra minor piece is rarely enough to win (KRBvKR, KBvK, ...).
+    if pawns.empty() {
+        let strong_pieces = board.pieces_of_colour(strong);
+        let weak_pieces = board.pieces_of_colour(!strong);
+        let minors = board.knights() | board.bishops();
+        let (rooks, queens) = (board.rooks(), board.queens());
+        if (minors & strong_pieces).count_ones() == (minors & weak_pieces).count_ones() + 1
+            && (rooks & strong_pieces).count_ones() == (rooks & weak_pieces).count_ones()
+            && (queens & strong_pieces).count_ones() == (queens & weak_pieces).count_ones()
+        {
+            return params.scale_pawnless_minor_up;
+        }
+    }
+
+    SCALE_NORMAL
+}
+
+/// Reward pieces for being close to the enemy king, from white's point of view.
+fn king_tropism(board: &Board, params: &EvalParams) -> i32 {
+    let white_king = board.king_square(Colour::White);
+    let black_king = board.king_square(Colour::Black);
+    let mut score = 0;
+    for piece in board.pieces() {
+        let weight = params.tropism_mg[board.piece_from_bit(piece) as usize];
+        if weight == 0 {
+            continue;
+        }
+        let square = board.square_of_piece(piece);
+        if piece.colour() == Colour::White {
+            score += weight * (7 - i32::from(square.distance(black_king)));
+        } else {
+            score -= weight * (7 - i32::from(square.distance(white_king)));
+        }
+    }
+    score
+}
+
+/// Reward pieces for attacking many squares, from white's point of view, as middlegame and endgame scores.
+fn mobility(board: &Board, params: &EvalParams) -> (i32, i32) {
+    let white = board.mobility(Colour::White);
+    let black = board.mobility(Colour::Black);
+    let (mut mg, mut eg) = (0, 0);
+    for piece in 0..6 {
+        // Mobility counts are bounded by the 64 squares times the 16 pieces of a side.
+        let squares = white[piece] as i32 - black[piece] as i32;
+        mg += params.mobility_mg[piece] * squares;
+        eg += params.mobility_eg[piece] * squares;
+    }
+    (mg, eg)
+}
+
+/// Rank of `square` counted from `colour`'s side of the board, from 0 to 7.
+const fn relative_rank(square: Square, colour: Colour) -> u8 {
+    let rank = square.into_inner() >> 3;
+    match colour {
+        Colour::White => rank,
+        Colour::Black => 7 - rank,
+    }
+}
+
+/// Reward rooks on open and semi-open files and on the seventh rank, from white's point of
+/// view, as middlegame and endgame scores.
+fn rooks(board: &Board, params: &EvalParams) -> (i32, i32) {
+    if board.rooks().empty() {
+        return (0, 0);
+    }
+
+    // The files holding each side's pawns, and whether each side has a pawn on its own
+    // second rank, where the enemy's seventh rank is.
+    let mut pawn_files = [0_u8; 2];
+    let mut pawns_at_home = [false; 2];
+    for pawn in board.pawns() {
+        let colour = pawn.colour();
+        let square = board.square_of_piece(pawn);
+        pawn_files[colour as usize] |= 1 << (square.into_inner() & 7);
+        pawns_at_home[colour as usize] |= relative_rank(square, colour) == 1;
+    }
+
+    let (mut mg, mut eg) = (0, 0);
+    for rook in board.rooks() {
+        let colour = rook.colour();
+        let square = board.square_of_piece(rook);
+        let file = 1 << (square.into_inner() & 7);
+        let (mut rook_mg, mut rook_eg) = (0, 0);
+        if pawn_files[colour as usize] & file == 0 {
+            if pawn_files[!colour as usize] & file == 0 {
+                rook_mg += params.rook_open_mg;
+                rook_eg += params.rook_open_eg;
+            } else {
+                rook_mg += params.rook_semi_open_mg;
+                rook_eg += params.rook_semi_open_eg;
+            }
+        }
+        if relative_rank(square, colour) == 6
+            && (relative_rank(board.king_square(!colour), colour) == 7
+                || pawns_at_home[!colour as usize])
+        {
+            rook_mg += params.rook_seventh_mg;
+            rook_eg += params.rook_seventh_eg;
+        }
+        mg += colour.sign() * rook_mg;
+        eg += colour.sign() * rook_eg;
+    }
+    (mg, eg)
+}
+
+/// The incrementally updated part of the evaluation: material and piece-square scores, and
+/// how many pieces of each type each side has.
+///
+/// The game phase is tracked by the board itself; see [`Board::phase`].
+///
+/// Build one for a position with [`EvalState::eval`], then derive the state of each child
+/// position with [`EvalState::update_eval`] before making the move. Updating must give the same
+/// state as evaluating the child from scratch, provided the same params are used throughout.
+/// The remaining terms depend on the whole board and are computed when the score is read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalState {
+    pst_mg: i32,
+    pst_eg: i32,
+    counts: [[u8; 6]; 2],
+}
+
+impl EvalState {
+    /// An empty board's state.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pst_mg: 0,
+            pst_eg: 0,
+            counts: [[0; 6]; 2],
+        }
+    }
+
+    /// Compute the state of a position from scratch.
+    #[must_use]
+    pub fn eval(board: &Board, params: &EvalParams) -> Self {
+        let mut score = Self::new();
+
+        for piece in board.pieces() {
+            let square = board.square_of_piece(piece);
+            score.add_piece(board.piece_from_bit(piece), square, piece.colour(), params);
+        }
+
+        score
+    }
+
+    /// How many pieces of type `piece` `colour` has.
+    #[must_use]
+    pub fn count(&self, colour: Colour, piece: Piece) -> u8 {
+        self.counts[usize::from(colour)][piece as usize]
+    }
+
+    /// The score of the position from `colour`'s point of view, without mobility.
+    #[must_use]
+    pub fn get(&self, board: &Board, colour: Colour, params: &EvalParams) -> i32 {
+        self.blend(board, colour, 0, 0, params)
+    }
+
+    /// Evaluate including mobility.
+    ///
+    /// Mobility scans the attack set of every square, which roughly doubles the cost of an
+    /// evaluation, and cannot be updated incrementally. So only quiescence stand-pat pays for it;
+    /// pruning decisions use the cheaper `get`, which can be off by the mobility difference.
+    #[must_use]
+    pub fn get_with_mobility(&self, board: &Board, colour: Colour, params: &EvalParams) -> i32 {
+        let (mg, eg) = mobility(board, params);
+        self.blend(board, colour, mg, eg, params)
+    }
+
+    /// Blend middlegame and endgame scores by phase, adding extra terms to each.
+    fn blend(
+        &self,
+        board: &Board,
+        colour: Colour,
+        extra_mg: i32,
+        extra_eg: i32,
+        params: &EvalParams,
+    ) -> i32 {
+        let (rooks_mg, rooks_eg) = rooks(board, params);
+        let (minors_mg, minors_eg) = self.minors(params);
+        let mg = self.pst_mg + king_tropism(board, params) + rooks_mg + minors_mg + extra_mg;
+        let eg = self.pst_eg + endgame(board, params) + rooks_eg + minors_eg + extra_eg;
+        let phase = i32::from(board.phase());
+        let score = ((mg * phase) + (eg * (PHASE_MAX - phase))) / PHASE_MAX;
+        let score = score * endgame_scale(board, score, params) / SCALE_NORMAL;
+        let score = fifty_move_scale(board, score);
+        let score = score.clamp(-MAX_EVAL, MAX_EVAL);
+        score * colour.sign()
+    }
+
+    /// The bishop pair and the pawn count adjustments to knights and bishops, from white's
+    /// point of view, as middlegame and endgame scores.
+    ///
+    /// Knights gain as the board fills with pawns and bishops as it opens up.
+    fn minors(&self, params: &EvalParams) -> (i32, i32) {
+        let side = |colour| {
+            let count = |piece| i32::from(self.count(colour, piece));
+            let (knights, bishops) = (count(Piece::Knight), count(Piece::Bishop));
+            let pawns = count(Piece::Pawn) - MINOR_PAWNS_NEUTRAL;
+            let adjust = knights * pawns * params.knight_pawn_adjust
+                - bishops * pawns * params.bishop_pawn_adjust;
+            if bishops >= 2 {
+                (
+                    adjust + params.bishop_pair_mg,
+                    adjust + params.bishop_pair_eg,
+                )
+            } else {
+                (adjust, adjust)
+            }
+        };
+        let (white_mg, white_eg) = side(Colour::White);
+        let (black_mg, black_eg) = side(Colour::Black);
+        (white_mg - black_mg, white_eg - black_eg)
+    }
+
+    /// The middlegame and endgame value of a piece on a square, from white's point of view.
+    fn value(piece: Piece, square: Square, colour: Colour, params: &EvalParams) -> (i32, i32) {
+        let piece = piece as usize;
+        if colour == Colour::White {
+            let square = square.flip().into_inner() as usize;
+            (
+                params.pst_mg[piece][square] + params.mat_mg[piece],
+                params.pst_eg[piece][square] + params.mat_eg[piece],
+            )
+        } else {
+            let square = square.into_inner() as usize;
+            (
+                -params.pst_mg[piece][square] - params.mat_mg[piece],
+                -params.pst_eg[piece][square] - params.mat_eg[piece],
+            )
+        }
+    }
+
+    fn add_piece(&mut self, piece: Piece, square: Square, colour: Colour, params: &EvalParams) {
+        let (mg, eg) = Self::value(piece, square, colour, params);
+        self.pst_mg += mg;
+        self.pst_eg += eg;
+        self.counts[usize::from(colour)][piece as usize] += 1;
+    }
+
+    fn remove_piece(&mut self, piece: Piece, square: Square, colour: Colour, params: &EvalParams) {
+        let (mg, eg) = Self::value(piece, square, colour, params);
+        self.pst_mg -= mg;
+        self.pst_eg -= eg;
+        self.counts[usize::from(colour)][piece as usize] -= 1;
+    }
+
+    fn move_piece(
+        &mut self,
+        piece: Piece,
+        from_square: Square,
+        to_square: Square,
+        colour: Colour,
+        params: &EvalParams,
+    ) {
+        let (from_mg, from_eg) = Self::value(piece, from_square, colour, params);
+        let (to_mg, to_eg) = Self::value(piece, to_square, colour, params);
+        self.pst_mg += to_mg - from_mg;
+        self.pst_eg += to_eg - from_eg;
+    }
+
+    /// The state after making `m` on `board`, which must still be the position before the move.
+    #[must_use]
+    pub fn update_eval(mut self, board: &Board, m: Move, params: &EvalParams) -> Self {
+        let side = board.side();
+        let from_piece = board.piece_from_square(m.from).unwrap();
+        match m.kind {
+            MoveType::Normal | MoveType::DoublePush => {
+                self.move_piece(from_piece, m.from, m.dest, side, params);
+            }
+            MoveType::Capture => {
+                let dest_piece = board.piece_from_square(m.dest).unwrap();
+                self.remove_piece(dest_piece, m.dest, !side, params);
+                self.move_piece(from_piece, m.from, m.dest, side, params);
+            }
+            MoveType::Castle => {
+                if m.dest > m.from {
+                    let rook_from = m.dest.east().unwrap();
+                    let rook_dest = m.dest.west().unwrap();
+                    self.move_piece(Piece::Rook, rook_from, rook_dest, side, params);
+                } else {
+                    let rook_from = m.dest.west().unwrap().west().unwrap();
+                    let rook_dest = m.dest.east().unwrap();
+                    self.move_piece(Piece::Rook, rook_from, rook_dest, side, params);
+                }
+                self.move_piece(from_piece, m.from, m.dest, side, params);
+            }
+            MoveType::EnPassant => {
+                let dest_piece = board.ep().unwrap().relative_south(side).unwrap();
+                self.remove_piece(Piece::Pawn, dest_piece, !side, params);
+                self.move_piece(from_piece, m.from, m.dest, side, params);
+            }
+            MoveType::Promotion => {
+                self.remove_piece(Piece::Pawn, m.from, side, params);
+                self.add_piece(m.prom.unwrap(), m.dest, side, params);
+            }
+            MoveType::CapturePromotion => {
+                let dest_piece = board.piece_from_square(m.dest).unwrap();
+                self.remove_piece(dest_piece, m.dest, !side, params);
+                self.remove_piece(Piece::Pawn, m.from, side, params);
+                self.add_piece(m.prom.unwrap(), m.dest, side, params);
+            }
+        }
+        self
+    }
+}
+
+impl Default for EvalState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        endgame, evaluate, king_tropism, mobility, rooks, EvalParams, EvalState, PHASE_MAX,
+    };
+    use yukari_movegen::{Board, Colour, MoveList, Piece, Zobrist, BENCH_FENS};
+
+    /// Return the scaled and unscaled evaluation of a position, from white's point of view.
+    fn eval(fen: &str) -> (i32, i32) {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let state = EvalState::eval(&board, &EvalParams::DEFAULT);
+        let (rooks_mg, rooks_eg) = rooks(&board, &EvalParams::DEFAULT);
+        let (minors_mg, minors_eg) = state.minors(&EvalParams::DEFAULT);
+        let mg = state.pst_mg + king_tropism(&board, &EvalParams::DEFAULT) + rooks_mg + minors_mg;
+        let phase = i32::from(board.phase());
+        let eg = state.pst_eg + endgame(&board, &EvalParams::DEFAULT) + rooks_eg + minors_eg;
+        let raw = ((mg * phase) + (eg * (PHASE_MAX - phase))) / PHASE_MAX;
+        (state.get(&board, Colour::White, &EvalParams::DEFAULT), raw)
+    }
+
+    #[test]
+    fn opposite_bishops_scaled() {
+        // Bishops on e3 and d5 are on opposite colours; on e4 and d5 they are not.
+        let (opposite, raw) = eval("8/4k3/8/3b4/8/4B3/3PK3/8 w - - 0 1");
+        assert!(raw > 80, "pawn-up position evaluates as {raw}");
+        assert!(opposite > 0);
+        assert!(
+            opposite * 3 / 2 < raw,
+            "opposite bishops {opposite} not scaled relative to {raw}"
+        );
+
+        let (same, raw) = eval("8/4k3/8/3b4/4B3/8/3PK3/8 w - - 0 1");
+        assert_eq!(same, raw);
+    }
+
+    #[test]
+    fn rook_one_wing_scaled() {
+        let (one_wing, raw) = eval("8/5pk1/6p1/8/8/5PPP/6K1/r3R3 w - - 0 1");
+        assert!(one_wing > 0);
+        assert!(one_wing < raw);
+
+        let (both_wings, raw) = eval("8/5pk1/6p1/8/8/P4PP1/6K1/r3R3 w - - 0 1");
+        assert_eq!(both_wings, raw);
+    }
+
+    #[test]
+    fn king_tropism_symmetric() {
+        let zobrist = Zobrist::new();
+        let fens = [
+            (
+                "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+                "rnb1k1nr/pppp1ppp/5q2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4",
+            ),
+            (
+                "6k1/5ppp/8/3N4/8/8/5PPP/3Q2K1 w - - 0 1",
+                "3q2k1/5ppp/8/8/3n4/8/5PPP/6K1 b - - 0 1",
+            ),
+        ];
+        for (fen, mirrored) in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let mirrored = Board::from_fen(mirrored, &zobrist).unwrap();
+            assert_eq!(
+                king_tropism(&board, &EvalParams::DEFAULT),
+                -king_tropism(&mirrored, &EvalParams::DEFAULT)
+            );
+            assert_eq!(
+                EvalState::eval(&board, &EvalParams::DEFAULT).get(
+                    &board,
+                    Colour::White,
+                    &EvalParams::DEFAULT
+                ),
+                -EvalState::eval(&mirrored, &EvalParams::DEFAULT).get(
+                    &mirrored,
+                    Colour::White,
+                    &EvalParams::DEFAULT
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn king_tropism_attacking_queen() {
+        let zobrist = Zobrist::new();
+        let far = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/Q5K1 w - - 0 1", &zobrist).unwrap();
+        let near = Board::from_fen("6k1/5ppp/8/8/3Q4/8/5PPP/6K1 w - - 0 1", &zobrist).unwrap();
+        assert!(
+            king_tropism(&near, &EvalParams::DEFAULT) > king_tropism(&far, &EvalParams::DEFAULT)
+        );
+    }
+
+    #[test]
+    fn opposite_bishops_halved() {
+        // The same pawn-up ending, with knights in place of the bishops, is not scaled.
+        let (opposite, _) = eval("8/4k3/8/3b4/8/4B3/3PK3/8 w - - 0 1");
+        let (knights, _) = eval("8/4k3/8/3n4/8/4N3/3PK3/8 w - - 0 1");
+        assert!(
+            (opposite - knights / 2).abs() <= 10,
+            "{opposite} is not about half of {knights}"
+        );
+    }
+
+    #[test]
+    fn pawnless_minor_up_scaled() {
+        let (scaled, raw) = eval("8/4k3/8/3r4/8/4B3/4K3/3R4 w - - 0 1");
+        assert!(raw > 250, "minor piece up evaluates as {raw}");
+        assert!(scaled.abs() < 50, "{scaled} is not close to a draw");
+
+        // With pawns on the board, the extra piece should win.
+        let (scaled, raw) = eval("8/4kp2/8/3r4/8/4B3/4KP2/3R4 w - - 0 1");
+        assert_eq!(scaled, raw);
+    }
+
+    #[test]
+    fn rule_of_the_square() {
+        // The king on a8 is one step outside the square of the e5 pawn: it only catches the
+        // pawn if it moves first.
+        let (_, outside) = eval("k7/8/8/4P3/8/8/8/7K w - - 0 1");
+        let (_, inside) = eval("k7/8/8/4P3/8/8/8/7K b - - 0 1");
+        assert!(
+            outside > inside + 300,
+            "unstoppable pawn {outside} not far ahead of catchable pawn {inside}"
+        );
+
+        // A bishop can stop the pawn whoever is to move.
+        let (_, white) = eval("kb6/8/8/4P3/8/8/8/7K w - - 0 1");
+        let (_, black) = eval("kb6/8/8/4P3/8/8/8/7K b - - 0 1");
+        assert_eq!(white, black);
+    }
+
+    #[test]
+    fn king_escorts_passer() {
+        let (_, near) = eval("8/8/5k2/8/8/8/3KP3/8 w - - 0 1");
+        let (_, far) = eval("8/8/5k2/8/8/8/4P3/7K w - - 0 1");
+        assert!(
+            near > far,
+            "king next to its pawn {near} not ahead of {far}"
+        );
+
+        // With the queens on, the king stays home.
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen("3qk3/8/8/8/8/8/3KP3/3Q4 w - - 0 1", &zobrist).unwrap();
+        assert_eq!(endgame(&board, &EvalParams::DEFAULT), 0);
+    }
+
+    #[test]
+    fn rooks_on_files() {
+        let zobrist = Zobrist::new();
+        let rooks = |fen: &str| {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            rooks(&board, &EvalParams::DEFAULT)
+        };
+        let closed = rooks("7k/4p3/8/8/8/8/4P3/4R1K1 w - - 0 1");
+        let semi_open = rooks("7k/4p3/8/8/8/8/5P2/4R1K1 w - - 0 1");
+        let open = rooks("7k/5p2/8/8/8/8/5P2/4R1K1 w - - 0 1");
+        assert_eq!(closed, (0, 0));
+        assert!(semi_open.0 > closed.0 && semi_open.1 > closed.1);
+        assert!(open.0 > semi_open.0 && open.1 > semi_open.1);
+
+        // Each side's rooks count against the other's.
+        assert_eq!(rooks("4r1k1/5p2/8/8/8/8/5P2/4RK2 w - - 0 1"), (0, 0));
+    }
+
+    #[test]
+    fn rook_on_seventh() {
+        let zobrist = Zobrist::new();
+        let rooks = |fen: &str| {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            rooks(&board, &EvalParams::DEFAULT)
+        };
+        // The same open file, with and without something to do on the seventh rank.
+        let idle = rooks("8/1R6/8/5k2/8/8/8/4K3 w - - 0 1");
+        let king = rooks("5k2/1R6/8/8/8/8/8/4K3 w - - 0 1");
+        let pawns = rooks("8/1R4p1/8/5k2/8/8/8/4K3 w - - 0 1");
+        assert!(king.0 > idle.0 && king.1 > idle.1);
+        assert!(pawns.0 > idle.0 && pawns.1 > idle.1);
+
+        // A black rook on the second rank is on its seventh.
+        assert_eq!(rooks("4k3/8/8/8/8/8/1r6/5K2 w - - 0 1"), (-king.0, -king.1));
+    }
+
+    #[test]
+    fn mobility_rewards_active_pieces() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        assert_eq!(mobility(&board, &EvalParams::DEFAULT), (0, 0));
+
+        // A centralised knight attacks eight squares, a cornered one two.
+        let centre = Board::from_fen("4k3/8/8/3N4/8/8/8/4K3 w - - 0 1", &zobrist).unwrap();
+        let corner = Board::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1", &zobrist).unwrap();
+        let (centre_mg, _) = mobility(&centre, &EvalParams::DEFAULT);
+        let (corner_mg, _) = mobility(&corner, &EvalParams::DEFAULT);
+        assert_eq!(centre_mg - corner_mg, 6 * 4);
+
+        let state = EvalState::eval(&centre, &EvalParams::DEFAULT);
+        assert!(
+            state.get_with_mobility(&centre, Colour::White, &EvalParams::DEFAULT)
+                > state.get(&centre, Colour::White, &EvalParams::DEFAULT)
+        );
+        assert!(
+            state.get_with_mobility(&centre, Colour::Black, &EvalParams::DEFAULT)
+                < state.get(&centre, Colour::Black, &EvalParams::DEFAULT)
+        );
+    }
+
+    #[test]
+    fn scores_fade_near_fifty_moves() {
+        // Two pawns up, but nothing is happening.
+        let (fresh, _) = eval("4k3/8/8/8/8/8/PP6/4K3 w - - 0 40");
+        assert!(fresh > 150);
+        let (slow, _) = eval("4k3/8/8/8/8/8/PP6/4K3 w - - 30 55");
+        assert_eq!(slow, fresh - 10);
+        let (late, _) = eval("4k3/8/8/8/8/8/PP6/4K3 w - - 60 70");
+        assert_eq!(late, fresh - 20);
+        // The drift costs less than the pawn that would reset the clock.
+        let (pawn_down, _) = eval("4k3/8/8/8/8/8/P7/4K3 w - - 0 70");
+        assert!(late > pawn_down, "{late} against {pawn_down}");
+        let (later, _) = eval("4k3/8/8/8/8/8/PP6/4K3 w - - 80 80");
+        assert_eq!(later, late / 2);
+        let (last, _) = eval("4k3/8/8/8/8/8/PP6/4K3 w - - 95 88");
+        assert!((0..=fresh / 8).contains(&last));
+    }
+
+    /// Mirror a position vertically, swapping the colours of every piece and the side to move.
+    fn mirror_fen(fen: &str) -> String {
+        let mut fields = fen.split(' ');
+        let swap_case = |c: char| {
+            if c.is_ascii_uppercase() {
+                c.to_ascii_lowercase()
+            } else {
+                c.to_ascii_uppercase()
+            }
+        };
+        let placement = fields.next().unwrap().split('/').rev().collect::<Vec<_>>();
+        let placement = placement
+            .join("/")
+            .chars()
+            .map(swap_case)
+            .collect::<String>();
+        let side = if fields.next() == Some("w") { "b" } else { "w" };
+        let castling = fields.next().unwrap();
+        let castling = if castling == "-" {
+            castling.to_string()
+        } else {
+            let black = castling.chars().filter(char::is_ascii_lowercase);
+            let white = castling.chars().filter(char::is_ascii_uppercase);
+            black.chain(white).map(swap_case).collect()
+        };
+        let ep = fields
+            .next()
+            .unwrap()
+            .chars()
+            .map(|c| match c {
+                '3' => '6',
+                '6' => '3',
+                c => c,
+            })
+            .collect();
+        let mut mirrored = vec![placement, side.to_string(), castling, ep];
+        mirrored.extend(fields.map(ToString::to_string));
+        mirrored.join(" ")
+    }
+
+    #[test]
+    fn golden_scores() {
+        let zobrist = Zobrist::new();
+        let params = EvalParams::default();
+        let positions = [
+            // The starting position is balanced.
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                -30..=30,
+            ),
+            // White is up a clean rook.
+            (
+                "rnbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQq - 0 1",
+                400..=700,
+            ),
+            // Black is up a clean knight.
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKB1R w KQkq - 0 1",
+                -450..=-250,
+            ),
+            // Trading off a knight each keeps the balance.
+            (
+                "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1",
+                -30..=30,
+            ),
+            // A queen up in the endgame is winning regardless of the side to move.
+            ("4k3/8/8/8/8/8/8/3QK3 b - - 0 1", 800..=1200),
+            // Bare kings are a dead draw.
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", -30..=30),
+        ];
+        for (fen, range) in positions {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let score = evaluate(&board, &params);
+            assert!(range.contains(&score), "{fen} evaluates as {score}");
+        }
+    }
+
+    #[test]
+    fn mirror_symmetry() {
+        let zobrist = Zobrist::new();
+        let params = EvalParams::default();
+        for fen in BENCH_FENS {
+            let mirrored = mirror_fen(fen);
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let mirror = Board::from_fen(&mirrored, &zobrist).unwrap();
+            assert_eq!(
+                evaluate(&board, &params),
+                -evaluate(&mirror, &params),
+                "{fen} and {mirrored} do not mirror"
+            );
+        }
+    }
+
+    #[test]
+    fn pst_blend() {
+        // After 1. e4 e5 2. Nf3 only the moved pieces differ from the start position, so the
+        // piece-square part changes by their table differences.
+        let zobrist = Zobrist::new();
+        let params = EvalParams::DEFAULT;
+        let start = Board::startpos(&zobrist);
+        let board = Board::from_moves(&zobrist, &["e2e4", "e7e5", "g1f3"])
+            .unwrap()
+            .0;
+        // Table indices, a8 first: white squares as they are, black ones mirrored.
+        let (e2, e4, g1, f3) = (52, 36, 62, 45);
+        let (e7, e5) = (52, 36);
+        let (pawn, knight) = (0, 1);
+        let change = |table: &[[i32; 64]; 6]| {
+            table[pawn][e4] - table[pawn][e2] + table[knight][f3]
+                - table[knight][g1]
+                - (table[pawn][e5] - table[pawn][e7])
+        };
+
+        let before = EvalState::eval(&start, &params);
+        let after = EvalState::eval(&board, &params);
+        assert_eq!(after.pst_mg - before.pst_mg, change(&params.pst_mg));
+        assert_eq!(after.pst_eg - before.pst_eg, change(&params.pst_eg));
+
+        // Nothing is traded, so the middlegame score counts in full.
+        assert_eq!(i32::from(board.phase()), PHASE_MAX);
+        let (_, raw) = eval(&board.to_fen(2));
+        let mg = after.pst_mg + king_tropism(&board, &params) + rooks(&board, &params).0;
+        assert_eq!(raw, mg);
+    }
+
+    #[test]
+    fn weights_round_trip() {
+        let zobrist = Zobrist::new();
+        let weights = EvalParams::DEFAULT.to_weights();
+        assert_eq!(weights.len(), 2 * 6 + 2 * 6 * 64 + 3 * 6 + 16);
+        let params = EvalParams::from_weights(&weights).unwrap();
+        assert_eq!(params, EvalParams::DEFAULT);
+        for fen in BENCH_FENS {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert_eq!(
+                evaluate(&board, &params),
+                evaluate(&board, &EvalParams::DEFAULT),
+                "{fen}"
+            );
+        }
+
+        // The vector is laid out like the params: material first, pawns first.
+        let mut richer = weights.clone();
+        richer[0] += 100;
+        let richer = EvalParams::from_weights(&richer).unwrap();
+        assert_eq!(richer.mat_mg[0], EvalParams::DEFAULT.mat_mg[0] + 100);
+        assert_eq!(
+            weights.last(),
+            Some(&EvalParams::DEFAULT.scale_pawnless_minor_up)
+        );
+
+        assert!(EvalParams::from_weights(&weights[1..]).is_none());
+        assert!(EvalParams::from_weights(&[weights.as_slice(), &[0]].concat()).is_none());
+    }
+
+    #[test]
+    fn incremental_opens_files() {
+        let zobrist = Zobrist::new();
+        let params = EvalParams::default();
+        // Pawn captures that open or half-open a rook's file, including en passant.
+        let positions = [
+            ("4k3/8/8/3p4/4P3/8/8/4R1K1 w - - 0 1", "e4d5"),
+            ("3rk3/8/8/3p4/4P3/8/8/6K1 w - - 0 1", "e4d5"),
+            ("4k3/8/8/3pP3/8/8/8/3R2K1 w - d6 0 1", "e5d6"),
+            ("4r1k1/8/8/8/3p4/2P1P3/8/6K1 b - - 0 1", "d4e3"),
+            ("4k3/8/8/8/8/1p6/P7/R5K1 w - - 0 1", "a2b3"),
+        ];
+        for (fen, m) in positions {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let m = board.parse_move(m).unwrap();
+            let child = board.make(m, &zobrist);
+            let state = EvalState::eval(&board, &params).update_eval(&board, m, &params);
+            let scratch = EvalState::eval(&child, &params);
+            assert_eq!(state, scratch, "{fen} after {m}");
+            assert_eq!(
+                state.get_with_mobility(&child, Colour::White, &params),
+                evaluate(&child, &params),
+                "{fen} after {m}"
+            );
+            assert_ne!(
+                rooks(&board, &params),
+                rooks(&child, &params),
+                "{fen} after {m} does not change the rook terms"
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_matches_scratch() {
+        let zobrist = Zobrist::new();
+        let params = EvalParams::default();
+        for fen in BENCH_FENS {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let state = EvalState::eval(&board, &params);
+            let mut moves = MoveList::new();
+            board.generate(&mut moves);
+            for m in moves {
+                let child = board.make(m, &zobrist);
+                assert_eq!(
+                    state.clone().update_eval(&board, m, &params),
+                    EvalState::eval(&child, &params),
+                    "{fen} after {m}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bishop_pair() {
+        let zobrist = Zobrist::new();
+        let params = EvalParams::default();
+        let pair = Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/2B1KB2 w - - 0 1", &zobrist).unwrap();
+        let state = EvalState::eval(&pair, &params);
+        assert_eq!(state.count(Colour::White, Piece::Bishop), 2);
+        assert_eq!(state.count(Colour::Black, Piece::Bishop), 0);
+        assert_eq!(state.count(Colour::Black, Piece::Pawn), 4);
+        // Four pawns each, one short of neutral, so each bishop gains a little too.
+        let adjust = 2 * params.bishop_pawn_adjust;
+        assert_eq!(
+            state.minors(&params),
+            (
+                params.bishop_pair_mg + adjust,
+                params.bishop_pair_eg + adjust
+            )
+        );
+
+        // Knights do better than bishops with a full set of pawns.
+        let closed = EvalState::eval(
+            &Board::from_fen(
+                "1n2kb2/pppppppp/8/8/8/8/PPPPPPPP/1N2KB2 w - - 0 1",
+                &zobrist,
+            )
+            .unwrap(),
+            &params,
+        );
+        assert_eq!(closed.minors(&params), (0, 0));
+        let closed = EvalState::eval(
+            &Board::from_fen(
+                "1n2kn2/pppppppp/8/8/8/8/PPPPPPPP/1B2KB2 w - - 0 1",
+                &zobrist,
+            )
+            .unwrap(),
+            &params,
+        );
+        let (mg, eg) = closed.minors(&params);
+        assert_eq!(mg - params.bishop_pair_mg, eg - params.bishop_pair_eg);
+        assert_eq!(
+            mg - params.bishop_pair_mg,
+            -6 * (params.knight_pawn_adjust + params.bishop_pawn_adjust)
+        );
+    }
+
+    #[test]
+    fn incremental_promotes_to_bishop() {
+        let zobrist = Zobrist::new();
+        let params = EvalParams::default();
+        // Underpromotions that make or complete a bishop pair, with and without a capture,
+        // including taking the opponent's second bishop to break their pair.
+        let positions = [
+            ("4k3/1P6/8/8/8/8/8/2B1K3 w - - 0 1", "b7b8b"),
+            ("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8b"),
+            ("r3k3/1P6/8/8/8/8/8/2B1K3 w - - 0 1", "b7a8b"),
+            ("2b1k3/1P6/8/8/8/8/8/2B1K3 w - - 0 1", "b7c8b"),
+            ("4k3/8/8/8/8/8/6p1/4KB1b b - - 0 1", "g2f1b"),
+            ("4k3/8/8/8/8/8/1p6/4K3 b - - 0 1", "b2b1b"),
+        ];
+        for (fen, m) in positions {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let m = board.parse_move(m).unwrap();
+            let child = board.make(m, &zobrist);
+            let before = EvalState::eval(&board, &params);
+            let state = before.clone().update_eval(&board, m, &params);
+            let scratch = EvalState::eval(&child, &params);
+            assert_eq!(state, scratch, "{fen} after {m}");
+            assert_eq!(
+                state.get(&child, Colour::White, &params),
+                scratch.get(&child, Colour::White, &params),
+                "{fen} after {m}"
+            );
+            assert_ne!(
+                before.minors(&params),
+                state.minors(&params),
+                "{fen} after {m} does not change the minor piece terms"
+            );
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use yukari_movegen::{Board, Colour, Move, MoveList, Piece, Square};
+
+/// Bytes every learn file starts with.
+const MAGIC: [u8; 4] = *b"YKLN";
+/// Version of the record layout; files of any other version are refused.
+const VERSION: u16 = 1;
+/// Bytes in the header: the magic and the version.
+const HEADER_LEN: usize = 6;
+/// Bytes in a record: hash, from, dest, promotion, score, depth and result.
+const RECORD_LEN: usize = 17;
+
+/// What a game came to, from the point of view of the side to move in a learned position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The side to move went on to lose.
+    Loss,
+    /// The game was drawn.
+    Draw,
+    /// The side to move went on to win.
+    Win,
+}
+
+impl Outcome {
+    /// The outcome for `side` of a game that ended with `result`, given the way xboard gives
+    /// it (`1-0`, `0-1` or `1/2-1/2`, then an optional comment); `None` for an unfinished game.
+    #[must_use]
+    pub fn from_result(result: &str, side: Colour) -> Option<Self> {
+        let white = match result.split_whitespace().next()? {
+            "1-0" => Self::Win,
+            "0-1" => Self::Loss,
+            "1/2-1/2" => Self::Draw,
+            _ => return None,
+        };
+        Some(match (side, white) {
+            (Colour::White, outcome) | (Colour::Black, outcome @ Self::Draw) => outcome,
+            (Colour::Black, Self::Win) => Self::Loss,
+            (Colour::Black, Self::Loss) => Self::Win,
+        })
+    }
+}
+
+/// A root position the engine played, with the move it chose there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LearnEntry {
+    /// Square the move was played from
+    pub from: Square,
+    /// Square the move was played to
+    pub dest: Square,
+    /// Piece a pawn promoted to, if any
+    pub prom: Option<Piece>,
+    /// Score of the search that chose the move, from the side to move's point of view
+    pub score: i32,
+    /// Depth of the search that chose the move
+    pub depth: u8,
+    /// How the game went on to end
+    pub outcome: Outcome,
+}
+
+impl LearnEntry {
+    /// Record `m`, chosen by a search to `depth` that scored it `score`.
+    #[must_use]
+    pub fn new(m: Move, score: i32, depth: i32, outcome: Outcome) -> Self {
+        Self {
+            from: m.from,
+            dest: m.dest,
+            prom: m.prom,
+            score,
+            depth: u8::try_from(depth.max(0)).unwrap_or(u8::MAX),
+            outcome,
+        }
+    }
+
+    /// The record as it is stored on disk.
+    fn to_bytes(self, hash: u64) -> [u8; RECORD_LEN] {
+        let mut bytes = [0; RECORD_LEN];
+        bytes[0..8].copy_from_slice(&hash.to_le_bytes());
+        bytes[8] = self.from.into_inner();
+        bytes[9] = self.dest.into_inner();
+        bytes[10] = match self.prom {
+            None => 0,
+            Some(Piece::Knight) => 1,
+            Some(Piece::Bishop) => 2,
+            Some(Piece::Rook) => 3,
+            Some(_) => 4,
+        };
+        bytes[11..15].copy_from_slice(&self.score.to_le_bytes());
+        bytes[15] = self.depth;
+        bytes[16] = match self.outcome {
+            Outcome::Loss => 0,
+            Outcome::Draw => 1,
+            Outcome::Win => 2,
+        };
+        bytes
+    }
+
+    /// Read a record back, or `None` if it is corrupt.
+    fn from_bytes(bytes: &[u8; RECORD_LEN]) -> Option<(u64, Self)> {
+        let hash = u64::from_le_bytes(bytes[0..8].try_into().ok()?);
+        let prom = match bytes[10] {
+            0 => None,
+            1 => Some(Piece::Knight),
+            2 => Some(Piece::Bishop),
+            3 => Some(Piece::Rook),
+            4 => Some(Piece::Queen),
+            _ => return None,
+        };
+        let outcome = match bytes[16] {
+            0 => Outcome::Loss,
+            1 => Outcome::Draw,
+            2 => Outcome::Win,
+            _ => return None,
+        };
+        let entry = Self {
+            from: Square::try_from(bytes[8]).ok()?,
+            dest: Square::try_from(bytes[9]).ok()?,
+            prom,
+            score: i32::from_le_bytes(bytes[11..15].try_into().ok()?),
+            depth: bytes[15],
+            outcome,
+        };
+        Some((hash, entry))
+    }
+}
+
+/// Root positions from earlier games, by hash, kept in a file between runs.
+///
+/// The file is a header followed by fixed-size records, and only ever grows: each game's
+/// positions are appended once it ends. When a position appears more than once, the deepest
+/// search of it is kept, and the latest of equally deep ones.
+#[derive(Clone, Debug, Default)]
+pub struct LearnTable {
+    entries: HashMap<u64, LearnEntry>,
+}
+
+impl LearnTable {
+    /// An empty table.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the table kept at `path`; a file that does not exist yet is an empty table.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, or is not a learn file of this version.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match File::open(path) {
+            Ok(file) => Self::read_from(BufReader::new(file)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Read a table from the contents of a learn file.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails, or `io::ErrorKind::InvalidData` if the contents are
+    /// not a learn file of this version.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        if header[0..4] != MAGIC {
+            return Err(invalid("not a learn file"));
+        }
+        if u16::from_le_bytes([header[4], header[5]]) != VERSION {
+            return Err(invalid("unsupported learn file version"));
+        }
+
+        let mut table = Self::new();
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+        // A game cut off while being written leaves a partial record, which is ignored.
+        for record in contents.chunks_exact(RECORD_LEN) {
+            let (hash, entry) = record
+                .try_into()
+                .ok()
+                .and_then(LearnEntry::from_bytes)
+                .ok_or_else(|| invalid("corrupt learn record"))?;
+            table.insert(hash, entry);
+        }
+        Ok(table)
+    }
+
+    /// Append one game's positions to the file at `path`, creating it if needed, and learn
+    /// them.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn append(&mut self, path: &Path, records: &[(u64, LearnEntry)]) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = file.metadata()?.len();
+        // Drop a partial record left by an interrupted write, so new records line up.
+        let partial = len.saturating_sub(HEADER_LEN as u64) % RECORD_LEN as u64;
+        if len > HEADER_LEN as u64 && partial != 0 {
+            file.set_len(len - partial)?;
+        }
+        let mut writer = BufWriter::new(file);
+        if len == 0 {
+            writer.write_all(&MAGIC)?;
+            writer.write_all(&VERSION.to_le_bytes())?;
+        }
+        for &(hash, entry) in records {
+            writer.write_all(&entry.to_bytes(hash))?;
+            self.insert(hash, entry);
+        }
+        writer.flush()
+    }
+
+    /// Learn a position, keeping whichever of it and any earlier entry was searched deeper.
+    pub fn insert(&mut self, hash: u64, entry: LearnEntry) {
+        let kept = self.entries.entry(hash).or_insert(entry);
+        if entry.depth >= kept.depth {
+            *kept = entry;
+        }
+    }
+
+    /// The entry for a position, if it has been learned.
+    #[must_use]
+    pub fn get(&self, hash: u64) -> Option<&LearnEntry> {
+        self.entries.get(&hash)
+    }
+
+    /// The number of positions learned.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no position has been learned.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The learned move for `board` and its entry, if the position was learned and the move
+    /// is legal in it; a hash collision with another position gives no move.
+    #[must_use]
+    pub fn probe(&self, board: &Board) -> Option<(Move, LearnEntry)> {
+        let entry = *self.get(board.hash())?;
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        moves
+            .into_iter()
+            .find(|m| m.from == entry.from && m.dest == entry.dest && m.prom == entry.prom)
+            .map(|m| (m, entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{LearnEntry, LearnTable, Outcome, HEADER_LEN, RECORD_LEN};
+    use yukari_movegen::{Board, Colour, Zobrist};
+
+    #[test]
+    fn file_round_trip() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let e4 = board.parse_move("e2e4").unwrap();
+        let child = board.make(e4, &zobrist);
+        let e5 = child.parse_move("e7e5").unwrap();
+        let records = [
+            (board.hash(), LearnEntry::new(e4, 20, 9, Outcome::Win)),
+            (child.hash(), LearnEntry::new(e5, -20, 8, Outcome::Loss)),
+        ];
+
+        let path = std::env::temp_dir().join(format!("yukari-learn-{}.bin", std::process::id()));
+        let mut table = LearnTable::new();
+        table.append(&path, &records[..1]).unwrap();
+        table.append(&path, &records[1..]).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let loaded = LearnTable::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(bytes.len(), HEADER_LEN + 2 * RECORD_LEN);
+        assert_eq!(loaded.len(), 2);
+        for (hash, entry) in records {
+            assert_eq!(loaded.get(hash), Some(&entry));
+            assert_eq!(table.get(hash), Some(&entry));
+        }
+        assert!(loaded.probe(&board) == Some((e4, records[0].1)));
+        assert!(loaded.probe(&child) == Some((e5, records[1].1)));
+
+        // A missing file is nothing learned yet.
+        assert!(LearnTable::load(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn deepest_search_kept() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let m = |s: &str| board.parse_move(s).unwrap();
+        let mut table = LearnTable::new();
+        table.insert(1, LearnEntry::new(m("e2e4"), 20, 9, Outcome::Draw));
+        table.insert(1, LearnEntry::new(m("d2d4"), 25, 7, Outcome::Win));
+        assert_eq!(table.get(1).unwrap().depth, 9);
+        table.insert(1, LearnEntry::new(m("c2c4"), 15, 9, Outcome::Win));
+        assert_eq!(
+            table.get(1),
+            Some(&LearnEntry::new(m("c2c4"), 15, 9, Outcome::Win))
+        );
+    }
+
+    #[test]
+    fn foreign_files_refused() {
+        let invalid = |bytes: &[u8]| {
+            LearnTable::read_from(bytes).map(|_| ()).unwrap_err().kind()
+                == io::ErrorKind::InvalidData
+        };
+        assert!(invalid(b"PGN1\x01\x00"));
+        assert!(invalid(b"YKLN\x02\x00"));
+        // A record with a promotion piece that does not exist.
+        let mut record = b"YKLN\x01\x00".to_vec();
+        record.extend_from_slice(&[0; RECORD_LEN]);
+        record[HEADER_LEN + 10] = 9;
+        assert!(invalid(&record));
+
+        // A partial record at the end is dropped.
+        let mut partial = b"YKLN\x01\x00".to_vec();
+        partial.extend_from_slice(&[0; RECORD_LEN + 3]);
+        assert_eq!(LearnTable::read_from(&partial[..]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn outcomes() {
+        let outcome = Outcome::from_result;
+        assert_eq!(
+            outcome("1-0 {White mates}", Colour::White),
+            Some(Outcome::Win)
+        );
+        assert_eq!(
+            outcome("1-0 {White mates}", Colour::Black),
+            Some(Outcome::Loss)
+        );
+        assert_eq!(outcome("0-1", Colour::Black), Some(Outcome::Win));
+        assert_eq!(
+            outcome("1/2-1/2 {Stalemate}", Colour::Black),
+            Some(Outcome::Draw)
+        );
+        assert_eq!(outcome("* {Game aborted}", Colour::White), None);
+        assert_eq!(outcome("", Colour::White), None);
+    }
+
+    #[test]
+    fn collisions_give_no_move() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let other = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1", &zobrist).unwrap();
+        let e4 = board.parse_move("e2e4").unwrap();
+        let mut table = LearnTable::new();
+        // Pretend the bare kings hash the same as the start position.
+        table.insert(other.hash(), LearnEntry::new(e4, 20, 9, Outcome::Draw));
+        assert!(table.probe(&other).is_none());
+    }
+}
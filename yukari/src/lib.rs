@@ -1,8 +1,19 @@
 #![warn(clippy::imprecise_flops, clippy::suboptimal_flops)]
 
 pub mod engine;
-mod eval;
+pub mod eval;
+mod learn;
 mod search;
+mod tablebase;
+mod tt;
+mod tune;
 
+pub use learn::{LearnEntry, LearnTable, Outcome};
 pub use search::is_repetition_draw;
-pub use search::Search;
+pub use search::{
+    choose_root_move, mate_in, NodeCounts, RootMove, Search, SearchInfo, SearchParams,
+    SearchProgress, SearchStats,
+};
+pub use tablebase::{choose_tablebase_move, TablebaseMove};
+pub use tt::{Bound, TranspositionTable, DEFAULT_HASH_MB};
+pub use tune::{EpdFile, MemoryPositions, PositionSource};
@@ -1,20 +1,66 @@
-use std::io::{self};
+use rand::{rngs::StdRng, SeedableRng};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
 use std::time::{Duration, Instant};
 use tinyvec::ArrayVec;
-use yukari::engine::{TimeControl, TimeMode};
-use yukari::{self, is_repetition_draw, Search};
-use yukari_movegen::{Board, Move, Piece, Square, Zobrist};
+use yukari::engine::{
+    engine_name, epd_position, error_json, parse_match_tc, read_openings, run_match, Analysis,
+    Comms, DrawAdjudicator, MatchConfig, Opening, OpeningOrder, Options, TimeControl, TimeMode,
+    GIT_HASH,
+};
+use yukari::{
+    self, choose_root_move, Bound, LearnEntry, LearnTable, Outcome, RootMove, Search, SearchStats,
+    TranspositionTable,
+};
+use yukari_movegen::{Board, Colour, Move, MoveList, Piece, Square, Zobrist, BENCH_FENS};
 
 #[derive(Clone, Copy, Debug)]
 enum Mode {
     /// In normal mode (which is more properly probably called thinking mode), we respond
-    /// to incoming moves by updating our state and then we will reply with a chosen move
+    /// to incoming moves by updating our state and then we will reply with a chosen move.
+    /// `new` enters this mode playing black; `go` enters it by moving for the side on move,
+    /// and `playother` enters it without moving, to reply once the side on move has played.
     Normal,
     /// In force mode we just update our internal state, not responding with a move.
     /// xboard itself seems to use this to relay past game moves to the engine
-    Force, // TODO: Update doc comment
-           // TODO: Analyze mode also exists
+    Force,
+    /// In analyze mode we search the current position until the GUI sends something, then
+    /// start again on the position that leaves, never playing a move ourselves.
+    Analyze,
+}
+
+/// How much better than every other move (in centipawns) a move must be to be an easy move
+const EASY_MOVE_MARGIN: i32 = 200;
+/// Minimum depth before a move can be judged easy
+const EASY_MOVE_DEPTH: i32 = 4;
+/// Number of consecutive iterations the best move must survive before it can be judged easy
+const EASY_MOVE_STABILITY: u32 = 3;
+/// Moves relayed in force mode before `go` that make a warm-up search worthwhile
+const WARMUP_FORCED_MOVES: u32 = 8;
+/// Number of positions the warm-up searches, ending with the current one
+const WARMUP_POSITIONS: usize = 4;
+/// Depth of each warm-up search
+const WARMUP_DEPTH: i32 = 5;
+/// Share of the move's time budget the whole warm-up may use
+const WARMUP_TIME_SHARE: f32 = 0.01;
+/// How often analysis reports its progress with a `stat01` line
+const STAT01_INTERVAL: Duration = Duration::from_secs(1);
+/// Commands after which the GUI no longer wants the move we are searching for
+const GAME_ENDING: [&str; 4] = ["new", "force", "result", "quit"];
+
+/// Whether a line tells us the GUI cannot ping: a protocol version from before features, or the
+/// GUI rejecting the feature.
+fn rules_out_ping(line: &str) -> bool {
+    match line.trim().split_once(' ') {
+        Some(("protover", version)) => version.parse::<u32>().map_or(true, |version| version < 2),
+        Some(("rejected", feature)) => feature == "ping",
+        _ => false,
+    }
 }
 
 /// The main engine state
@@ -25,6 +71,45 @@ pub struct Yukari {
     mode: Mode,
     zobrist: Zobrist,
     keystack: Vec<u64>,
+    /// The position before each move of the game, oldest first, in step with the keystack
+    history: Vec<Board>,
+    /// Moves made in force mode since the last search
+    forced_moves: u32,
+    /// User-settable options
+    options: Options,
+    /// Whether to pick randomly between near-equal root moves
+    random: bool,
+    /// Random source for random mode, kept across games so consecutive games differ
+    rng: StdRng,
+    /// Game history for offering and accepting draws
+    adjudicator: DrawAdjudicator,
+    /// The principal variation of our last search, starting with the move we played
+    last_pv: ArrayVec<[Move; 32]>,
+    /// Search results kept across moves, so each search builds on the last
+    tt: TranspositionTable,
+    /// Set by the input thread to make the current search move now
+    stop: Arc<AtomicBool>,
+    /// Set by the input thread when the GUI pauses the game, which also stops the search
+    paused: Arc<AtomicBool>,
+    /// Whether a search for our move was paused, so it must be searched again on resume
+    interrupted: bool,
+    /// Thinking time already used on the current move before a pause
+    spent: Duration,
+    /// Set while analyzing, so the input thread stops the analysis for any command
+    analyzing: Arc<AtomicBool>,
+    /// Game-ending commands the input thread has read and the main loop has not handled yet,
+    /// counted when the GUI cannot ping, so could take a move we send late for one in the next
+    /// game; a search that ends with any pending is abandoned, its move unplayed
+    abandoning: Arc<AtomicUsize>,
+    /// Lines the input thread has read that the main loop has not handled yet
+    queued: Arc<AtomicUsize>,
+    /// Positions from earlier games and the moves we played in them, when learning is on
+    learn: LearnTable,
+    /// The positions we searched this game, by hash, with the side to move there, the move we
+    /// played, its score and the depth searched, to be learned once the result is known
+    played: Vec<(u64, Colour, Move, i32, i32)>,
+    /// Where replies to the GUI go, flushed as they are sent
+    comms: Comms,
 }
 
 impl Yukari {
@@ -32,6 +117,7 @@ impl Yukari {
     #[must_use]
     pub fn new() -> Self {
         let zobrist = Zobrist::new();
+        let options = Options::default();
         Self {
             // Using startpos fixes knights
             board: Board::startpos(&zobrist),
@@ -41,23 +127,166 @@ impl Yukari {
             mode: Mode::Normal,
             zobrist,
             keystack: Vec::new(),
+            history: Vec::new(),
+            forced_moves: 0,
+            random: false,
+            rng: StdRng::from_entropy(),
+            adjudicator: DrawAdjudicator::new(options.draw_moves, options.draw_margin),
+            last_pv: ArrayVec::new(),
+            tt: TranspositionTable::new(options.hash_mb),
+            options,
+            stop: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            interrupted: false,
+            spent: Duration::ZERO,
+            analyzing: Arc::new(AtomicBool::new(false)),
+            abandoning: Arc::new(AtomicUsize::new(0)),
+            queued: Arc::new(AtomicUsize::new(0)),
+            learn: LearnTable::new(),
+            played: Vec::new(),
+            comms: Comms::stdout(),
+        }
+    }
+
+    /// Reset the game state for a new game, keeping engine settings and the random state.
+    /// The zobrist keys and the transposition table allocation are kept; the table is only emptied.
+    /// As xboard expects of `new`, random mode is turned off; a GUI that wants it sends `random`
+    /// again for every game.
+    pub fn reset(&mut self) {
+        self.board = Board::startpos(&self.zobrist);
+        self.tc = TimeControl::new(TimeMode::St(0));
+        self.tc.set_params(&self.options.search);
+        self.mode = Mode::Normal;
+        self.random = false;
+        self.keystack.clear();
+        self.history.clear();
+        self.forced_moves = 0;
+        self.adjudicator.clear();
+        self.last_pv.set_len(0);
+        self.tt.clear();
+        self.paused.store(false, Ordering::Relaxed);
+        self.interrupted = false;
+        self.spent = Duration::ZERO;
+        // A game abandoned without a result teaches nothing
+        self.played.clear();
+    }
+
+    /// Make do without a feature the GUI rejected, telling the user if we cannot.
+    pub fn reject_feature(&mut self, feature: &str) {
+        match feature {
+            // The input thread has seen this already, and abandons searches the GUI moves on from
+            "ping" => {}
+            // Without setboard the GUI sets positions up with edit, which we do not support
+            "setboard" => {
+                self.comms.send(format_args!(
+                    "tellusererror {} cannot set up positions without setboard",
+                    engine_name()
+                ));
+            }
+            _ => self
+                .comms
+                .send(format_args!("# feature {feature} rejected")),
+        }
+    }
+
+    /// Reseed the random source so random mode plays reproducibly
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Sets an option from the GUI, reporting failures back to it
+    pub fn set_option(&mut self, name: &str, value: &str) {
+        let hash_mb = self.options.hash_mb;
+        if let Err(err) = self.options.apply(name, value) {
+            self.comms.send(format_args!("Error ({err}): {name}"));
+            return;
+        }
+        self.adjudicator
+            .set_limits(self.options.draw_moves, self.options.draw_margin);
+        if self.options.hash_mb != hash_mb {
+            self.tt.resize(self.options.hash_mb);
+        }
+        self.tc.set_params(&self.options.search);
+        if self.options.learn && matches!(name, "Learn" | "LearnFile") {
+            self.load_learning();
+        }
+    }
+
+    /// Load the positions learned in earlier games from the learn file
+    fn load_learning(&mut self) {
+        let path = &self.options.learn_path;
+        match LearnTable::load(path) {
+            Ok(learn) => {
+                self.comms.send(format_args!(
+                    "# {} learned positions in {}",
+                    learn.len(),
+                    path.display()
+                ));
+                self.learn = learn;
+            }
+            Err(err) => self
+                .comms
+                .send(format_args!("# learn file {}: {err}", path.display())),
+        }
+    }
+
+    /// Claim the result of a game that has just ended, and learn from it
+    fn claim_result(&mut self, result: &str) {
+        self.comms.send(result);
+        self.finish_game(result);
+    }
+
+    /// Learn the positions we played this game now that it ended with `result`, given the
+    /// way the `result` command gives it
+    pub fn finish_game(&mut self, result: &str) {
+        let played = std::mem::take(&mut self.played);
+        if !self.options.learn || played.is_empty() {
+            return;
+        }
+        let records = played
+            .into_iter()
+            .filter_map(|(hash, side, m, score, depth)| {
+                let outcome = Outcome::from_result(result, side)?;
+                Some((hash, LearnEntry::new(m, score, depth, outcome)))
+            })
+            .collect::<Vec<_>>();
+        if let Err(err) = self.learn.append(&self.options.learn_path, &records) {
+            self.comms.send(format_args!(
+                "# learn file {}: {err}",
+                self.options.learn_path.display()
+            ));
         }
     }
 
     /// Sets the game board from FEN notation
-    /// # Panics
-    /// Panics when invalid FEN is input.
+    ///
+    /// A FEN that does not parse is reported and leaves the previous board in place.
     pub fn set_board(&mut self, s: &str) {
-        self.board = Board::from_fen(s, &self.zobrist).unwrap();
+        self.board = match Board::from_fen(s, &self.zobrist) {
+            Ok(board) => board,
+            Err(err) => {
+                self.comms.send("tellusererror Illegal position");
+                self.comms.send(format_args!("Error ({err}): setboard {s}"));
+                return;
+            }
+        };
         self.keystack.clear();
+        self.history.clear();
+        self.forced_moves = 0;
+        self.adjudicator.clear();
+        self.last_pv.set_len(0);
     }
 
     /// Parses the two xboard time control setup commands and sets that as our controls
-    /// # Panics
-    /// Panics when invalid time controls are passed in
+    ///
+    /// A malformed command is reported and leaves the previous controls in place.
     pub fn parse_tc(&mut self, s: &str) {
-        let mode = TimeMode::from_str(s).unwrap();
+        let Ok(mode) = TimeMode::from_str(s) else {
+            self.comms.send(format_args!("# bad time control: {s}"));
+            return;
+        };
         self.tc = TimeControl::new(mode);
+        self.tc.set_params(&self.options.search);
     }
 
     /// Update with a new remaining time directly from the GUI
@@ -66,146 +295,333 @@ impl Yukari {
         self.tc.set_remaining(csec);
     }
 
+    /// Update with the opponent's remaining time from the GUI
+    /// Expects a value in centiseconds
+    pub fn set_opponent_remaining(&mut self, csec: f32) {
+        self.tc.set_opponent_remaining(csec);
+    }
+
     /// Generates valid moves for current posiition then finds the attempted
     /// move in the list
     #[must_use]
     pub fn find_move(&self, from: Square, dest: Square, prom: Option<Piece>) -> Option<Move> {
-        let moves: [Move; 256] = [Move::default(); 256];
-        let mut moves = ArrayVec::from(moves);
-        moves.set_len(0);
+        let mut moves = MoveList::new();
         self.board.generate(&mut moves);
         moves
             .into_iter()
             .find(|&m| m.from == from && m.dest == dest && m.prom == prom)
     }
 
+    /// Make a move on the board, recording it for draw adjudication along with our score if it is our move
+    pub fn make_move(&mut self, m: Move, score: Option<i32>) {
+        let irreversible = self.board.piece_from_square(m.from) == Some(Piece::Pawn)
+            || self.board.piece_from_square(m.dest).is_some();
+        self.adjudicator.push(score, irreversible);
+        self.keystack.push(self.board.hash());
+        self.history.push(self.board.clone());
+        if matches!(self.mode, Mode::Force) {
+            self.forced_moves += 1;
+        }
+        self.board = self.board.make(m, &self.zobrist);
+    }
+
+    /// The result to claim if the position just reached ends the game by the rules
+    #[must_use]
+    pub fn game_result(&self) -> Option<&'static str> {
+        if self.board.is_checkmate() {
+            return Some(match self.board.side() {
+                Colour::White => "0-1 {Black mates}",
+                Colour::Black => "1-0 {White mates}",
+            });
+        }
+        if self.board.is_stalemate() {
+            return Some("1/2-1/2 {Stalemate}");
+        }
+        if self.board.insufficient_material() {
+            return Some("1/2-1/2 {Insufficient material}");
+        }
+        // The keystack holds every earlier position, so two matches make this the third occurrence.
+        let hash = self.board.hash();
+        let earlier = self.keystack.iter().filter(|&&key| key == hash).count();
+        (earlier >= 2).then_some("1/2-1/2 {Draw by repetition}")
+    }
+
+    /// The move we expect the opponent to reply with, if it is legal in the current position
+    #[must_use]
+    pub fn hint(&self) -> Option<Move> {
+        let m = *self.last_pv.get(1)?;
+        self.find_move(m.from, m.dest, m.prom)
+    }
+
+    /// Fill the transposition table after a line of moves relayed in force mode.
+    ///
+    /// A GUI relaying a book line leaves the table cold, which makes the first search's time
+    /// usage erratic. So once enough moves were forced, the last few positions of the game are
+    /// searched shallowly first, within a small share of the time budget for the move.
+    pub fn warm_up(&mut self) {
+        if std::mem::take(&mut self.forced_moves) < WARMUP_FORCED_MOVES {
+            return;
+        }
+        let budget = self.tc.search_time() * WARMUP_TIME_SHARE;
+        let stop_after = Instant::now() + Duration::from_secs_f32(budget);
+        let first = self.history.len().saturating_sub(WARMUP_POSITIONS - 1);
+        let positions = self.history[first..].iter().chain([&self.board]);
+        for (index, board) in (first..).zip(positions) {
+            let mut s = Search::new(Some(stop_after), &self.zobrist, &mut self.tt);
+            s.set_stop_flag(Arc::clone(&self.stop));
+            s.tune_params(self.options.search);
+            let mut keystack = self.keystack[..index].to_vec();
+            let mut pv = ArrayVec::new();
+            for depth in 1..=WARMUP_DEPTH {
+                s.search_root(board, depth, &mut pv, &mut keystack);
+                if s.stopped() || Instant::now() >= stop_after {
+                    return;
+                }
+            }
+        }
+    }
+
     /// Real search, falls back to dumb search in extreme time constraints
-    pub fn search(&mut self, best_pv: &mut ArrayVec<[Move; 32]>) {
+    /// Returns the score of the last completed depth
+    ///
+    /// A search the GUI pauses gives up its result; searching again after the pause picks up
+    /// the table and only the time the paused search had left.
+    pub fn search(&mut self, best_pv: &mut ArrayVec<[Move; 32]>) -> i32 {
         let start = Instant::now();
-        let stop_after = start + Duration::from_secs_f32(self.tc.search_time());
-        let mut s = Search::new(Some(stop_after), &self.zobrist);
+        let budget = Duration::from_secs_f32(self.tc.search_time()).saturating_sub(self.spent);
+        let mut stop_after = start + budget;
+        let mut s = Search::new(Some(stop_after), &self.zobrist, &mut self.tt);
+        let margin = if self.random {
+            self.options.random_margin
+        } else {
+            0
+        };
+        s.set_root_margin(margin);
+        s.set_stop_flag(Arc::clone(&self.stop));
+        s.set_multi_pv(self.options.multi_pv);
+        s.set_use_nullmove(self.options.null_move);
+        s.set_use_futility(self.options.futility);
+        s.tune_params(self.options.search);
+        // Try the move we played here in an earlier game first
+        if self.options.learn {
+            if let Some((m, entry)) = self.learn.probe(&self.board) {
+                self.comms.send(format_args!(
+                    "# learned {m} score {} depth {}",
+                    entry.score, entry.depth
+                ));
+                s.set_root_hint(Some(m));
+            }
+        }
+        // Root move scores from the last completed depth
+        let mut root_moves: Vec<RootMove> = Vec::new();
         // clone another to use inside the loop
         // Use a seperate backing data to record the current move set
         let mut depth = 1;
         let mut pv: ArrayVec<[Move; 32]> = ArrayVec::new();
-        while depth < 20 {
+        let mut best_score = 0;
+        let mut best_depth = 0;
+        // How many iterations in a row have agreed on the best move
+        let mut stability = 0;
+        // How often the best move changed lately, which earns the search more time
+        let mut instability = 0.0;
+        while depth < self.options.search.depth_limit {
+            let best_move_changes = s.stats().best_move_changes;
             pv.set_len(0);
             // FIXME: We want to search one depth without time controls
             let score = s.search_root(&self.board, depth, &mut pv, &mut self.keystack);
             // If we have bailed out stop the loop
-            if Instant::now() >= stop_after {
+            if s.stopped() || Instant::now() >= stop_after {
+                // An interrupted first iteration still leaves a move to play
+                if best_pv.is_empty() {
+                    best_pv.clone_from(&pv);
+                }
                 break;
             }
+            // A best move that keeps changing is not easy, and is worth more time
+            let changed = s.stats().best_move_changes > best_move_changes;
+            if changed || best_pv.is_empty() {
+                stability = 0;
+            } else {
+                stability += 1;
+            }
+            instability = TimeControl::best_move_instability(instability, changed);
+            let extended = Duration::from_secs_f32(self.tc.extended_search_time(instability));
+            stop_after = start + extended.saturating_sub(self.spent);
+            s.set_stop_after(Some(stop_after));
             // If we have a pv that's not just empty from bailing out use that as our best moves
             best_pv.clone_from(&pv);
+            best_score = score;
+            best_depth = depth;
+            root_moves.clear();
+            root_moves.extend_from_slice(s.root_moves());
             let now = Instant::now().duration_since(start);
-            print!(
-                "{} {:.2} {} {} ",
-                depth,
-                score,
-                now.as_millis() / 10,
-                s.nodes() + s.qnodes()
-            );
-            for m in &pv {
-                print!("{m} ");
+            print_thinking(&mut self.comms, &s, depth, now);
+            // With only one legal move, searching deeper cannot change our choice
+            if s.root_moves().len() == 1 {
+                self.comms.send("# Only move");
+                break;
+            }
+            // Don't spend the full time on an easy move, once it has had a third of it
+            let elapsed = now.as_secs_f32();
+            if depth >= EASY_MOVE_DEPTH
+                && stability >= EASY_MOVE_STABILITY
+                && elapsed >= self.tc.search_time() / 3.0
+            {
+                if let Some(&best) = pv.first() {
+                    if s.is_easy_move(best, score, EASY_MOVE_MARGIN) {
+                        self.comms.send("# Easy move");
+                        break;
+                    }
+                }
             }
-            println!();
             depth += 1;
         }
-        println!(
+        if self.paused.load(Ordering::Relaxed) {
+            self.spent += start.elapsed();
+            self.stop.store(false, Ordering::Relaxed);
+            return best_score;
+        }
+        self.spent = Duration::ZERO;
+        if self.random {
+            if let Some(m) = choose_root_move(&root_moves, margin, &mut self.rng) {
+                if best_pv.first() != Some(&m) {
+                    best_pv.set_len(0);
+                    best_pv.push(m);
+                }
+            }
+        }
+        self.comms.send(format_args!(
             "# QS: {:.3}%",
             (100 * s.qnodes()) as f64 / (s.nodes() as f64 + s.qnodes() as f64)
-        );
-        println!(
-            "# Branching factor: {:.3}",
-            ((s.nodes() + s.qnodes()) as f64).powf(1.0 / f64::from(depth))
-        );
+        ));
+        // Only completed iterations count, or an interrupted one would flatter the figure
+        if best_depth > 0 {
+            self.comms.send(format_args!(
+                "# Branching factor: {:.3}",
+                ((s.nodes() + s.qnodes()) as f64).powf(1.0 / f64::from(best_depth))
+            ));
+        }
+        self.comms
+            .send(format_args!("# Seldepth: {}", s.seldepth()));
+        self.comms.send(format_args!("# stats: {}", s.stats()));
+        // A move-now request only applies to the search it interrupted
+        self.stop.store(false, Ordering::Relaxed);
+        self.last_pv.clone_from(best_pv);
+        if let Some(&m) = best_pv.first() {
+            let hash = self.board.hash();
+            self.played
+                .push((hash, self.board.side(), m, best_score, best_depth));
+        }
+        self.forced_moves = 0;
         self.tc.increment_moves();
+        self.tc.set_last_score(best_score);
+        best_score
     }
 
-    fn bench(&self) {
-        let fens = [
-            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
-            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
-            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
-            "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
-            "rq3rk1/ppp2ppp/1bnpN3/3N2B1/4P3/7P/PPPQ1PP1/2KR3R b - - 0 14",
-            "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4PpP1/1BNP4/PPP2P1P/3R1RK1 b - g3 0 14",
-            "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
-            "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
-            "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
-            "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
-            "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
-            "r1bq1r1k/b1p1npp1/p2p3p/1p6/3PP3/1B2NN2/PP3PPP/R2Q1RK1 w - - 1 16",
-            "3r1rk1/p5pp/bpp1pp2/8/q1PP1P2/b3P3/P2NQRPP/1R2B1K1 b - - 6 22",
-            "r1q2rk1/2p1bppp/2Pp4/p6b/Q1PNp3/4B3/PP1R1PPP/2K4R w - - 2 18",
-            "4k2r/1pb2ppp/1p2p3/1R1p4/3P4/2r1PN2/P4PPP/1R4K1 b - - 3 22",
-            "3q2k1/pb3p1p/4pbp1/2r5/PpN2N2/1P2P2P/5PP1/Q2R2K1 b - - 4 26",
-            "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
-            "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
-            "2K5/p7/7P/5pR1/8/5k2/r7/8 w - - 0 1",
-            "8/6pk/1p6/8/PP3p1p/5P2/4KP1q/3Q4 w - - 0 1",
-            "7k/3p2pp/4q3/8/4Q3/5Kp1/P6b/8 w - - 0 1",
-            "8/2p5/8/2kPKp1p/2p4P/2P5/3P4/8 w - - 0 1",
-            "8/1p3pp1/7p/5P1P/2k3P1/8/2K2P2/8 w - - 0 1",
-            "8/pp2r1k1/2p1p3/3pP2p/1P1P1P1P/P5KR/8/8 w - - 0 1",
-            "8/3p4/p1bk3p/Pp6/1Kp1PpPp/2P2P1P/2P5/5B2 b - - 0 1",
-            "5k2/7R/4P2p/5K2/p1r2P1p/8/8/8 b - - 0 1",
-            "6k1/6p1/P6p/r1N5/5p2/7P/1b3PP1/4R1K1 w - - 0 1",
-            "1r3k2/4q3/2Pp3b/3Bp3/2Q2p2/1p1P2P1/1P2KP2/3N4 w - - 0 1",
-            "6k1/4pp1p/3p2p1/P1pPb3/R7/1r2P1PP/3B1P2/6K1 w - - 0 1",
-            "8/3p3B/5p2/5P2/p7/PP5b/k7/6K1 w - - 0 1",
-            "5rk1/q6p/2p3bR/1pPp1rP1/1P1Pp3/P3B1Q1/1K3P2/R7 w - - 93 90",
-            "4rrk1/1p1nq3/p7/2p1P1pp/3P2bp/3Q1Bn1/PPPB4/1K2R1NR w - - 40 21",
-            "r3k2r/3nnpbp/q2pp1p1/p7/Pp1PPPP1/4BNN1/1P5P/R2Q1RK1 w kq - 0 16",
-            "3Qb1k1/1r2ppb1/pN1n2q1/Pp1Pp1Pr/4P2p/4BP2/4B1R1/1R5K b - - 11 40",
-            "4k3/3q1r2/1N2r1b1/3ppN2/2nPP3/1B1R2n1/2R1Q3/3K4 w - - 5 1",
-            // 5-man positions
-            "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",  // Kc2 - mate
-            "8/8/8/5N2/8/p7/8/2NK3k w - - 0 1",   // Na2 - mate
-            "8/3k4/8/8/8/4B3/4KB2/2B5 w - - 0 1", // draw
-            // 6-man positions
-            "8/8/1P6/5pr1/8/4R3/7k/2K5 w - - 0 1", // Re5 - mate
-            "8/2p4P/8/kr6/6R1/8/8/1K6 w - - 0 1",  // Ka2 - mate
-            "8/8/3P3k/8/1p6/8/1P6/1K3n2 b - - 0 1", // Nd2 - draw
-            // 7-man positions
-            "8/R7/2q5/8/6k1/8/1P5p/K6R w - - 0 124", // Draw
-            // Mate and stalemate positions
-            "6k1/3b3r/1p1p4/p1n2p2/1PPNpP1q/P3Q1p1/1R1RB1P1/5K2 b - - 0 1",
-            "r2r1n2/pp2bk2/2p1p2p/3q4/3PN1QP/2P3R1/P4PP1/5RK1 w - - 0 1",
-            "8/8/8/8/8/6k1/6p1/6K1 w - -",
-            "7k/7P/6K1/8/3B4/8/8/8 b - -",
-        ];
+    /// Search the current position until the GUI sends a command or the depth limit is hit,
+    /// printing thinking lines as each depth completes and `stat01` lines while searching.
+    pub fn analyze(&mut self) {
+        // The input thread only stops the search for lines it reads from now on, so a line
+        // that arrived just before must be handled first.
+        self.analyzing.store(true, Ordering::SeqCst);
+        if self.queued.load(Ordering::SeqCst) == 0 {
+            let start = Instant::now();
+            let mut s = Search::new(None, &self.zobrist, &mut self.tt);
+            s.set_stop_flag(Arc::clone(&self.stop));
+            s.set_multi_pv(self.options.multi_pv);
+            s.set_use_nullmove(self.options.null_move);
+            s.set_use_futility(self.options.futility);
+            s.tune_params(self.options.search);
+            let mut comms = self.comms.clone();
+            s.set_progress(STAT01_INTERVAL, move |progress| comms.send(progress));
+            let mut pv = ArrayVec::new();
+            for depth in 1..self.options.search.depth_limit {
+                s.search_root(&self.board, depth, &mut pv, &mut self.keystack);
+                if s.stopped() {
+                    break;
+                }
+                print_thinking(&mut self.comms, &s, depth, start.elapsed());
+            }
+        }
+        self.analyzing.store(false, Ordering::SeqCst);
+        self.stop.store(false, Ordering::Relaxed);
+    }
+
+    /// Search for our move and play it, unless the GUI paused the game meanwhile.
+    /// If the game is already over, there is nothing to search: give the result instead.
+    pub fn reply(&mut self) {
+        if let Some(result) = self.game_result() {
+            self.claim_result(result);
+            return;
+        }
+        let mut pv = ArrayVec::new();
+        let score = self.search(&mut pv);
+        self.interrupted = self.paused.load(Ordering::Relaxed);
+        if self.interrupted || self.abandoning.load(Ordering::SeqCst) > 0 {
+            return;
+        }
+        // Choose the top move. A search stopped before it finished the first root move has no
+        // PV, and playing some legal move beats playing none.
+        let m = match pv.first() {
+            Some(&m) => m,
+            None => {
+                let mut moves = MoveList::new();
+                self.board.generate(&mut moves);
+                // The game is not over, so there is a move to play
+                let Some(&m) = moves.first() else {
+                    return;
+                };
+                m
+            }
+        };
+        // We must actually make the move locally too
+        self.make_move(m, Some(score));
+        // Offer a draw along with the move if the game is going nowhere
+        if self.adjudicator.is_drawish() {
+            self.comms.send("offer draw");
+        }
+        self.comms.send(format_args!("move {m}"));
+        if let Some(result) = self.game_result() {
+            self.claim_result(result);
+        }
+    }
 
+    fn bench(&mut self) {
         let mut nodes = 0;
+        let mut stats = SearchStats::default();
         let start = Instant::now();
-        for fen in fens {
+        for fen in BENCH_FENS {
             let zobrist = Zobrist::new();
-            let board = Board::from_fen(fen, &zobrist).unwrap();
-            let mut s = Search::new(None, &zobrist);
+            let board = match Board::from_fen(fen, &zobrist) {
+                Ok(board) => board,
+                Err(err) => {
+                    self.comms.send(format_args!("Error ({err}): {fen}"));
+                    continue;
+                }
+            };
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
             let start = Instant::now();
             let mut keystack = Vec::new();
             let mut pv = ArrayVec::new();
-            pv.set_len(0);
             let score = s.search_root(&board, 8, &mut pv, &mut keystack);
             let now = Instant::now().duration_since(start);
-            print!(
-                "10 {score:.2} {} {} ",
+            let pv = pv.iter().map(|m| format!("{m} ")).collect::<String>();
+            self.comms.send(format_args!(
+                "10 {score:.2} {} {} {pv}",
                 now.as_millis() / 10,
                 s.nodes() + s.qnodes()
-            );
-            for m in pv {
-                print!("{m} ");
-            }
-            println!();
+            ));
             nodes += s.nodes() + s.qnodes();
+            stats += *s.stats();
         }
         let now = Instant::now().duration_since(start);
-        println!(
+        self.comms.send(format_args!(
             "{nodes} nodes in {:.3}s = {:.0} nodes/s",
             now.as_secs_f64(),
             (nodes as f64) / now.as_secs_f64()
-        );
+        ));
+        self.comms.send(stats);
     }
 }
 
@@ -215,54 +631,321 @@ impl Default for Yukari {
     }
 }
 
+/// Print a thinking line for each PV of a completed depth, best first, with the nodes of that
+/// iteration and the extended seldepth and NPS fields before the tab that starts the PV. A
+/// score that is only a bound is marked the way other engines mark fail highs and lows, since
+/// xboard has no syntax for it.
+fn print_thinking(comms: &mut Comms, s: &Search, depth: i32, elapsed: Duration) {
+    for line in s.lines() {
+        let marker = match line.bound {
+            Bound::Exact => "",
+            Bound::Lower => "++ ",
+            Bound::Upper => "-- ",
+        };
+        let pv = line.pv.iter().map(|m| format!("{m} ")).collect::<String>();
+        comms.send(format_args!(
+            "{} {:.2} {} {} {} {}\t{marker}{pv}",
+            depth,
+            line.score,
+            elapsed.as_millis() / 10,
+            s.iteration_nodes().total(),
+            s.seldepth(),
+            s.nps()
+        ));
+    }
+}
+
+/// A command-line argument that is missing or malformed.
+fn usage_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Analyse every position in a FEN or EPD file, or on standard input without `--file`, one
+/// result line per position:
+/// `analyze [--file positions.epd] [--depth N] [--time SECONDS | --movetime MS] [--json]`
+fn analyze(mut args: impl Iterator<Item = String>, zobrist: &Zobrist) -> io::Result<()> {
+    let mut file = None;
+    let mut depth = 8;
+    let mut time = None;
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" => file = args.next(),
+            "--depth" => {
+                let value = args.next().and_then(|depth| depth.parse().ok());
+                depth = value.ok_or_else(|| usage_error("--depth expects an integer argument"))?;
+            }
+            "--time" => {
+                let value = args.next().and_then(|time| time.parse().ok());
+                let value = value.filter(|&time: &f64| time.is_finite() && time >= 0.0);
+                let value =
+                    value.ok_or_else(|| usage_error("--time expects a number of seconds"))?;
+                time = Some(Duration::from_secs_f64(value));
+            }
+            "--movetime" => {
+                let value = args.next().and_then(|time| time.parse().ok());
+                let value = value
+                    .ok_or_else(|| usage_error("--movetime expects a number of milliseconds"))?;
+                time = Some(Duration::from_millis(value));
+            }
+            "--json" => json = true,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown analyze argument {arg}"),
+                ))
+            }
+        }
+    }
+    let input: Box<dyn BufRead> = match file {
+        Some(file) => Box::new(BufReader::new(File::open(file)?)),
+        None => Box::new(io::stdin().lock()),
+    };
+
+    let mut tt = TranspositionTable::default();
+    let mut stdout = io::stdout().lock();
+    for line in input.lines() {
+        let fen = epd_position(&line?);
+        if fen.is_empty() || fen.starts_with('#') {
+            continue;
+        }
+        // Positions are analysed independently, so results do not depend on file order.
+        tt.clear();
+        match (Analysis::run(&fen, depth, time, zobrist, &mut tt), json) {
+            (Ok(analysis), true) => writeln!(stdout, "{}", analysis.to_json())?,
+            (Ok(analysis), false) => {
+                write!(
+                    stdout,
+                    "{fen}: depth {} score {} nodes {} time {} pv",
+                    analysis.depth,
+                    analysis.score,
+                    analysis.nodes,
+                    analysis.time.as_millis()
+                )?;
+                for m in &analysis.pv {
+                    write!(stdout, " {m}")?;
+                }
+                writeln!(stdout)?;
+            }
+            (Err(err), true) => writeln!(stdout, "{}", error_json(&fen, &err))?,
+            (Err(err), false) => writeln!(stdout, "{fen}: error: {err}")?,
+        }
+        // Results should show up as they are found, even when piped.
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// Play a match against another xboard engine, writing the games as PGN:
+/// `selfplay [--games N] [--tc BASE+INC] [--opponent PATH] [--pgn FILE]
+/// [--openings [FILE.epd]] [--opening-order random|sequential] [--opening-plies N]`
+///
+/// `--openings` alone uses the built-in openings; with a file, each line is a FEN or EPD
+/// position or a line of moves from the start position.
+fn selfplay(args: impl Iterator<Item = String>, zobrist: &Zobrist) -> io::Result<()> {
+    let this = std::env::current_exe()?;
+    let mut config = MatchConfig {
+        engines: [this.clone(), this],
+        games: 10,
+        tc: parse_match_tc("10+0.1").expect("default time control is valid"),
+        openings: Vec::new(),
+        opening_order: OpeningOrder::Sequential,
+    };
+    let mut plies = None;
+    let mut pgn = PathBuf::from("selfplay.pgn");
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => {
+                let value = args.next().and_then(|games| games.parse().ok());
+                config.games =
+                    value.ok_or_else(|| usage_error("--games expects an integer argument"))?;
+            }
+            "--tc" => {
+                let value = args.next().and_then(|tc| parse_match_tc(&tc));
+                let message = "--tc expects BASE+INC in seconds, like 10+0.1";
+                config.tc = value.ok_or_else(|| usage_error(message))?;
+            }
+            "--opponent" => {
+                let value = args.next().map(PathBuf::from);
+                config.engines[1] =
+                    value.ok_or_else(|| usage_error("--opponent expects an engine path"))?;
+            }
+            "--pgn" => {
+                let value = args.next().map(PathBuf::from);
+                pgn = value.ok_or_else(|| usage_error("--pgn expects a file name"))?;
+            }
+            "--openings" => {
+                config.openings = match args.next_if(|file| !file.starts_with("--")) {
+                    Some(file) => read_openings(BufReader::new(File::open(file)?), zobrist)?,
+                    None => Opening::builtin(),
+                };
+            }
+            "--opening-order" => {
+                config.opening_order = match args.next().as_deref() {
+                    Some("sequential") => OpeningOrder::Sequential,
+                    Some("random") => OpeningOrder::Random,
+                    _ => return Err(usage_error("--opening-order expects random or sequential")),
+                };
+            }
+            "--opening-plies" => {
+                let value = args.next().and_then(|plies| plies.parse().ok());
+                let message = "--opening-plies expects an integer argument";
+                plies = Some(value.ok_or_else(|| usage_error(message))?);
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown selfplay argument {arg}"),
+                ))
+            }
+        }
+    }
+
+    if let Some(plies) = plies {
+        for opening in &mut config.openings {
+            opening.truncate(plies);
+        }
+    }
+    let mut pgn = File::create(pgn)?;
+    run_match(&config, &mut io::stdout().lock(), &mut pgn)?;
+    Ok(())
+}
+
 fn main() -> io::Result<()> {
     let mut engine = Yukari::new();
 
-    for arg in std::env::args() {
-        if arg == "bench" {
-            engine.bench();
-            return Ok(());
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "bench" => {
+                engine.bench();
+                return Ok(());
+            }
+            "analyze" => return analyze(args, &engine.zobrist),
+            "selfplay" => return selfplay(args, &engine.zobrist),
+            // The git hash is only for debug output, in the `#` line sent in reply to `xboard`
+            "--version" => {
+                println!("{}", engine_name());
+                return Ok(());
+            }
+            // Fix the random seed, so random mode games can be reproduced
+            "seed" => {
+                let seed = args.next().and_then(|seed| seed.parse().ok());
+                engine.seed(seed.ok_or_else(|| usage_error("seed expects an integer argument"))?);
+            }
+            // Hold back time for a slow connection to the GUI, as the Move Overhead option does
+            "--move-overhead" => {
+                let value = args.next().filter(|value| value.parse::<u32>().is_ok());
+                let message = "--move-overhead expects a number of milliseconds";
+                engine.set_option("Move Overhead", &value.ok_or_else(|| usage_error(message))?);
+            }
+            _ => {}
         }
     }
 
-    let mut line = String::new();
-    loop {
-        line.clear();
-        let count = io::stdin().read_line(&mut line)?;
-        if count == 0 {
-            println!("# got zero read");
-            continue;
+    // Read input on its own thread, so that `?` and `pause` can stop a search in progress,
+    // and any command can stop analysis
+    let (lines, input) = mpsc::channel();
+    let stop = Arc::clone(&engine.stop);
+    let paused = Arc::clone(&engine.paused);
+    let analyzing = Arc::clone(&engine.analyzing);
+    let queued = Arc::clone(&engine.queued);
+    let abandoning = Arc::clone(&engine.abandoning);
+    thread::spawn(move || {
+        let mut unsynced = false;
+        for line in io::stdin().lines() {
+            let Ok(line) = line else { break };
+            queued.fetch_add(1, Ordering::SeqCst);
+            let cmd = line.split_whitespace().next().unwrap_or("");
+            unsynced |= rules_out_ping(&line);
+            match line.trim() {
+                "?" => stop.store(true, Ordering::Relaxed),
+                "pause" => {
+                    paused.store(true, Ordering::Relaxed);
+                    stop.store(true, Ordering::Relaxed);
+                }
+                _ if unsynced && GAME_ENDING.contains(&cmd) => {
+                    abandoning.fetch_add(1, Ordering::SeqCst);
+                    stop.store(true, Ordering::Relaxed);
+                }
+                // Progress is reported without being asked for
+                "." => {}
+                _ if analyzing.load(Ordering::SeqCst) => stop.store(true, Ordering::Relaxed),
+                _ => {}
+            }
+            if lines.send(line).is_err() {
+                break;
+            }
         }
+    });
+
+    loop {
+        let Ok(line) = input.recv() else {
+            engine.comms.send("# got zero read");
+            return Ok(());
+        };
+        engine.queued.fetch_sub(1, Ordering::SeqCst);
         let trimmed = line.trim();
         let (cmd, args) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+        // Once the last game-ending command is handled, no search is being abandoned, so the stop
+        // must not carry over to the next
+        let abandoning = &engine.abandoning;
+        if GAME_ENDING.contains(&cmd)
+            && abandoning.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                == Ok(1)
+        {
+            engine.stop.store(false, Ordering::Relaxed);
+        }
 
         #[allow(clippy::match_same_arms)]
         match cmd {
-            // Identification for engines that auto switch between protocols
-            "xboard" => {}
+            // Identification for engines that auto switch between protocols, as debug output
+            // that pins down the exact build
+            "xboard" => engine
+                .comms
+                .send(format_args!("# {} ({GIT_HASH})", engine_name())),
+            // Features came with v2, so play along without them
+            "protover" if rules_out_ping(trimmed) => {
+                engine
+                    .comms
+                    .send(format_args!("# protocol version {args} has no features"));
+            }
             // This is where we send our features
             "protover" => {
-                // v1 won't send this anyway and we need v2
-                assert_eq!(args, "2");
                 // Do features individually
-                println!("feature myname=\"Yukari 20072021\"");
+                engine
+                    .comms
+                    .send(format_args!("feature myname=\"{}\"", engine_name()));
                 // No signals support
-                println!("feature sigint=0 sigterm=0");
-                // Don't currently understand enough to reuse the engine for next game
-                println!("feature reuse=0");
+                engine.comms.send("feature sigint=0 sigterm=0");
+                // `new` resets the game and clears the transposition table, so the engine can be reused
+                engine.comms.send("feature reuse=1");
                 // Ping feature helps with race conditions
-                println!("feature ping=1");
+                engine.comms.send("feature ping=1");
                 // We would rather get FEN updates of the board than white/black
-                println!("feature colors=0 setboard=1");
+                engine.comms.send("feature colors=0 setboard=1");
                 // Technically needed to support those # <msg> lines
-                println!("feature debug=1");
+                engine.comms.send("feature debug=1");
+                // Let the GUI size the transposition table; smp and egtpath wait for threads and tablebases
+                engine.comms.send("feature memory=1");
+                // We can stop thinking when the GUI pauses the game, and pick it up again on resume
+                engine.comms.send("feature pause=1");
+                // Analysis reports its progress with stat01 lines
+                engine.comms.send("feature analyze=1");
+                // Advertise our options, which come back as option commands
+                for feature in engine.options.xboard_features() {
+                    engine.comms.send(feature);
+                }
                 // Communicate that feature reporting is done
-                println!("feature done=1");
+                engine.comms.send("feature done=1");
             }
             // Directly update the engine's board from a FEN
             "setboard" => engine.set_board(args),
-            // Reset the entire state of the engine
-            "new" => engine = Yukari::new(),
+            // Reset the game state of the engine
+            "new" => engine.reset(),
+            // The game is over, however it ended; learn from it
+            "result" => engine.finish_game(args),
             // Parse our two time controls from the whole commmand lines
             // TODO: This is rather xboard specific
             "level" | "st" => engine.parse_tc(trimmed),
@@ -272,104 +955,261 @@ fn main() -> io::Result<()> {
             "quit" => {
                 break;
             }
-            // Feature replies are just ignored since we don't turn anything off yet
-            // TODO: Handle rejects we can't tolerate and abort early
-            "accepted" | "rejected" => {}
+            // Features we send are all ones we can do without, bar setboard
+            "accepted" => {}
+            "rejected" => engine.reject_feature(args),
             // Ping expects a response with the correct tag once the commands prior to the ping are done
             // That ends up being some GPU fence level synchronization nonsense if it were to send more than one
             // so for now we just "handle it" by replying with pong immediately. For now this "works" because
             // the engine is single threaded such that moves can never be passed by other commands
-            "ping" => println!("pong {args}"),
-            // TODO: Should support randomization so we don't always play the same game
-            // we can't todo!() because we cannot turn off getting this message
-            "random" => {}
+            "ping" => engine.comms.send(format_args!("pong {args}")),
+            // Toggle choosing randomly between near-equal moves, so we don't always play the same game
+            "random" => engine.random = !engine.random,
             // We don't implement games against computer players games differently
             "computer" => {}
             // This report gives us info about what time we have left right now directly
             // the value is in centiseconds
-            "time" => engine.set_remaining(f32::from_str(args).unwrap()),
-            // TODO: Should we care? Right now we don't have any logic to handle opponent time seperate
-            "otim" => {}
+            "time" => match f32::from_str(args) {
+                Ok(time) => engine.set_remaining(time),
+                Err(_) => engine
+                    .comms
+                    .send(format_args!("Error (bad time): {trimmed}")),
+            },
+            // The opponent's time left, also in centiseconds, lets us press an opponent short of time
+            "otim" => match f32::from_str(args) {
+                Ok(time) => engine.set_opponent_remaining(time),
+                Err(_) => engine
+                    .comms
+                    .send(format_args!("Error (bad time): {trimmed}")),
+            },
+            // Tell the user which move we expect them to play; with no idea, say nothing
+            "hint" => {
+                if let Some(m) = engine.hint() {
+                    engine.comms.send(format_args!("Hint: {m}"));
+                }
+            }
+            // We don't have an opening book yet, so there are no book moves to show
+            "bk" => {
+                engine.comms.send(" no book");
+                engine.comms.send("");
+            }
+            // Nothing is played until the GUI resumes the game
+            "go" if engine.paused.load(Ordering::Relaxed) => {
+                engine
+                    .comms
+                    .send(format_args!("Error (game paused): {trimmed}"));
+            }
             "go" => {
                 engine.mode = Mode::Normal;
+                engine.warm_up();
                 // When we get go we should make a move immediately
-                let pv: [Move; 32] = [Move::default(); 32];
-                let mut pv = ArrayVec::from(pv);
-                pv.set_len(0);
-                engine.search(&mut pv);
-                // Choose the top move
-                let m = pv[0];
-                // We must actually make the move locally too
-                engine.board = engine.board.make(m, &engine.zobrist);
-                println!("move {m}");
-                if is_repetition_draw(&engine.keystack, engine.board.hash()) {
-                    println!("1/2-1/2 {{Draw by repetition}}");
-                }
-                engine.keystack.push(engine.board.hash());
+                engine.reply();
             }
             "force" => engine.mode = Mode::Force,
+            // Analysis starts once this command is handled, and goes on between commands
+            "analyze" => engine.mode = Mode::Analyze,
+            "exit" => engine.mode = Mode::Force,
+            // Progress is reported every so often anyway
+            "." => {}
+            // Play the side not on move: wait for the opponent's move, then reply to it
+            "playother" => engine.mode = Mode::Normal,
+            // Set one of the options we advertised
+            "option" => {
+                let (name, value) = args.split_once('=').unwrap_or((args, ""));
+                engine.set_option(name, value);
+            }
+            // Move now; the input thread has already stopped the search, if there was one. With
+            // no search running the stop is stale, and must not cut short the next one
+            "?" => engine.stop.store(false, Ordering::Relaxed),
+            // The input thread has stopped any search and marked us paused; that search is
+            // over by now, so the stop must not carry over to the next one
+            "pause" => engine.stop.store(false, Ordering::Relaxed),
+            // Pick up a search that was paused, with the time it had left
+            "resume" => {
+                engine.paused.store(false, Ordering::Relaxed);
+                if std::mem::take(&mut engine.interrupted) {
+                    engine.reply();
+                }
+            }
+            // Negotiated settings, which go through the same path as options
+            "memory" => engine.set_option("Hash", args),
+            "cores" => engine.set_option("Threads", args),
+            // Only Syzygy tablebases are of interest; the spec says to ignore other kinds
+            "egtpath" => {
+                if let Some(("syzygy", path)) = args.split_once(' ') {
+                    engine.set_option("SyzygyPath", path);
+                }
+            }
+            // The opponent offers a draw, which we accept by offering one back
+            "draw" => {
+                if engine.adjudicator.is_drawish() {
+                    engine.comms.send("offer draw");
+                }
+            }
             _ => {
-                // Always ascii
+                // Always ascii, though a desynced or garbled line may be too short to be a move
                 let chars = trimmed.as_bytes();
-                if chars[1].is_ascii_digit() && chars[3].is_ascii_digit() {
-                    // This is actually a move
-                    let from = Square::from_str(&cmd[..2]).unwrap();
-                    let dest = Square::from_str(&cmd[2..4]).unwrap();
-                    let prom = if chars.len() == 5 {
-                        match chars[4] {
-                            b'n' => Some(Piece::Knight),
-                            b'b' => Some(Piece::Bishop),
-                            b'r' => Some(Piece::Rook),
-                            b'q' => Some(Piece::Queen),
-                            _ => None
+                let digit_at = |i: usize| chars.get(i).is_some_and(u8::is_ascii_digit);
+                let is_move = digit_at(1) && digit_at(3);
+                if is_move && engine.paused.load(Ordering::Relaxed) {
+                    // Nothing is played until the GUI resumes the game
+                    engine
+                        .comms
+                        .send(format_args!("Error (game paused): {trimmed}"));
+                } else if is_move {
+                    // This is actually a move, though not necessarily a legal one. If the GUI
+                    // thinks it is, our board has fallen out of step with the GUI's; leave it
+                    // alone so the GUI can take the move back or set the position up again.
+                    let m = match engine.board.parse_move(cmd) {
+                        Ok(m) => m,
+                        Err(err) => {
+                            engine
+                                .comms
+                                .send(format_args!("Illegal move ({err}): {trimmed}"));
+                            continue;
                         }
-                    } else {
-                        None
                     };
                     match engine.mode {
                         Mode::Normal => {
-                            // Find the move in the list
-                            let m = engine
-                                .find_move(from, dest, prom)
-                                .expect("Attempted move not found!?");
-                            engine.board = engine.board.make(m, &engine.zobrist);
-                            if is_repetition_draw(&engine.keystack, engine.board.hash()) {
-                                println!("1/2-1/2 {{Draw by repetition}}");
+                            engine.make_move(m, None);
+                            // There is nothing to reply to a move that ends the game.
+                            if let Some(result) = engine.game_result() {
+                                engine.claim_result(result);
+                                continue;
                             }
-                            engine.keystack.push(engine.board.hash());
                             // Find the next move to make
-                            // TODO: Cleanups
-                            let pv: [Move; 32] = [Move::default(); 32];
-                            let mut pv = ArrayVec::from(pv);
-                            pv.set_len(0);
-                            engine.search(&mut pv);
-                            // Choose the top move
-                            let m = pv[0];
-                            // We must actually make the move locally too
-                            engine.board = engine.board.make(m, &engine.zobrist);
-                            println!("move {m}");
-                            if is_repetition_draw(&engine.keystack, engine.board.hash()) {
-                                println!("1/2-1/2 {{Draw by repetition}}");
-                            }
-                            engine.keystack.push(engine.board.hash());
+                            engine.reply();
                         }
-                        Mode::Force => {
-                            let m = engine
-                                .find_move(from, dest, prom)
-                                .expect("Attempted move not found!?");
-                            engine.board = engine.board.make(m, &engine.zobrist);
-                            if is_repetition_draw(&engine.keystack, engine.board.hash()) {
-                                println!("1/2-1/2 {{Draw by repetition}}");
+                        Mode::Force | Mode::Analyze => {
+                            engine.make_move(m, None);
+                            if let Some(result) = engine.game_result() {
+                                engine.claim_result(result);
                             }
-                            engine.keystack.push(engine.board.hash());
                         }
                     }
                 } else {
                     // This may look like I chose the format, but it is a standard response
-                    println!("Error (unknown command): {trimmed}");
+                    engine
+                        .comms
+                        .send(format_args!("Error (unknown command): {trimmed}"));
                 }
             }
         }
+        if matches!(engine.mode, Mode::Analyze) {
+            engine.analyze();
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Mode, TimeMode, Yukari, WARMUP_DEPTH};
+    use std::sync::atomic::Ordering;
+    use tinyvec::ArrayVec;
+    use yukari::{Bound, Search};
+    use yukari_movegen::{Board, Colour};
+
+    /// Nodes needed to search the current position to the warm-up depth
+    fn nodes_to_warmup_depth(engine: &mut Yukari) -> u64 {
+        let mut s = Search::new(None, &engine.zobrist, &mut engine.tt);
+        let mut pv = ArrayVec::new();
+        for depth in 1..=WARMUP_DEPTH {
+            s.search_root(&engine.board, depth, &mut pv, &mut engine.keystack);
+        }
+        s.nodes() + s.qnodes()
+    }
+
+    #[test]
+    fn warm_up_after_force_mode() {
+        // Relay a book line the way Arena does, then ask for a move.
+        let mut engine = Yukari::new();
+        engine.parse_tc("st 1000");
+        engine.mode = Mode::Force;
+        let line = [
+            "e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7",
+        ];
+        for san in line {
+            let m = engine.board.parse_move(san).unwrap();
+            engine.make_move(m, None);
+        }
+        assert_eq!(engine.history.len(), line.len());
+        engine.mode = Mode::Normal;
+
+        let mut cold = engine.clone();
+        engine.warm_up();
+        assert!(engine.tt.filled() > 0);
+        assert_eq!(engine.forced_moves, 0);
+        assert!(nodes_to_warmup_depth(&mut engine) < nodes_to_warmup_depth(&mut cold));
+
+        // A second warm-up has nothing new to prepare for.
+        let filled = engine.tt.filled();
+        engine.warm_up();
+        assert_eq!(engine.tt.filled(), filled);
+    }
+
+    #[test]
+    fn stopped_search_still_moves() {
+        // Every queen can capture, so the first root move alone outlasts the first poll of the
+        // stop flag, and the search ends with no PV
+        let mut engine = Yukari::new();
+        engine.parse_tc("st 1");
+        engine.set_board("qqqqkqqq/8/8/8/8/8/8/QQQQKQQQ w - - 0 1");
+        engine.stop.store(true, Ordering::Relaxed);
+        engine.reply();
+        assert_eq!(engine.board.side(), Colour::Black);
+    }
+
+    #[test]
+    fn malformed_time_control_ignored() {
+        let mut engine = Yukari::new();
+        engine.parse_tc("level 40 5 0");
+        let mode = engine.tc.mode();
+        engine.parse_tc("level 40 foo 0");
+        assert_eq!(engine.tc.mode(), mode);
+        engine.parse_tc("st abc");
+        assert_eq!(engine.tc.mode(), mode);
+        engine.parse_tc("st");
+        assert_eq!(engine.tc.mode(), mode);
+
+        // A well-formed command after them still takes effect.
+        engine.parse_tc("st 1");
+        assert_eq!(engine.tc.mode(), TimeMode::St(1));
+    }
+
+    #[test]
+    fn no_warm_up_after_few_forced_moves() {
+        let mut engine = Yukari::new();
+        engine.parse_tc("st 1000");
+        engine.mode = Mode::Force;
+        let m = engine.board.parse_move("e4").unwrap();
+        engine.make_move(m, None);
+        engine.mode = Mode::Normal;
+        engine.warm_up();
+        assert_eq!(engine.tt.filled(), 0);
+    }
+
+    #[test]
+    fn new_game_keeps_table_allocation() {
+        let mut engine = Yukari::new();
+        let startpos_hash = engine.board.hash();
+        let capacity = engine.tt.capacity();
+
+        let m = engine.board.parse_move("e4").unwrap();
+        engine.make_move(m, None);
+        engine.mode = Mode::Force;
+        engine
+            .tt
+            .store(engine.board.hash(), 4, 30, Bound::Exact, None);
+        assert!(!engine.keystack.is_empty());
+        assert_eq!(engine.tt.filled(), 1);
+
+        engine.reset();
+        assert!(engine.board.hash() == startpos_hash);
+        assert!(engine.board.to_string() == Board::startpos(&engine.zobrist).to_string());
+        assert!(engine.keystack.is_empty());
+        assert!(matches!(engine.mode, Mode::Normal));
+        assert_eq!(engine.tt.capacity(), capacity);
+        assert_eq!(engine.tt.filled(), 0);
+    }
+}
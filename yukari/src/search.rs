@@ -1,11 +1,94 @@
-use std::time::Instant;
+use std::fmt::Display;
+use std::ops::{Add, Sub};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use rand::{seq::SliceRandom, Rng};
 use tinyvec::ArrayVec;
-use yukari_movegen::{Board, Move, Zobrist};
+use yukari_movegen::{Board, Move, MoveList, Zobrist};
 
-use crate::eval::EvalState;
+use crate::eval::{EvalParams, EvalState};
+use crate::tt::{Bound, TranspositionTable};
 
-const MATE_VALUE: i32 = 10_000;
+// Scores are in centipawns from the side to move's point of view, in two bands:
+// - ordinary scores, no further from zero than MAX_EVAL, which the evaluation is clamped to;
+// - mate scores, MATE_VALUE less the plies to mate, further from zero than MATE_THRESHOLD.
+// A huge material imbalance can therefore never be mistaken for a mate, or outscore one.
+
+/// Score of delivering mate at the root.
+pub(crate) const MATE_VALUE: i32 = 10_000;
+/// No search gets this many plies deep.
+const MAX_PLY: i32 = 1000;
+/// Scores further from zero than this are mate scores.
+const MATE_THRESHOLD: i32 = MATE_VALUE - MAX_PLY;
+/// Largest score the evaluation may give.
+pub(crate) const MAX_EVAL: i32 = MATE_THRESHOLD - 1;
+
+/// Numbers that steer the search and its time use, gathered here so they can be tuned without
+/// recompiling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchParams {
+    /// Plies the null move search is reduced by, besides the move itself
+    pub null_move_reduction: i32,
+    /// The null move search is reduced by a further ply per this many plies of depth
+    pub null_move_depth_divisor: i32,
+    /// How far (in centipawns) the evaluation one ply from the horizon must be above beta to
+    /// prune the node
+    pub futility_margin: i32,
+    /// Iterative deepening stops short of this depth
+    pub depth_limit: i32,
+    /// Time held back from every search for communication delays, in milliseconds
+    pub move_overhead_ms: i32,
+    /// With an increment, a move gets the clock and one increment divided by this
+    pub increment_moves: i32,
+}
+
+impl SearchParams {
+    /// The parameters the engine plays with.
+    pub const DEFAULT: Self = Self {
+        null_move_reduction: 3,
+        null_move_depth_divisor: 6,
+        futility_margin: 200,
+        depth_limit: 20,
+        move_overhead_ms: 30,
+        increment_moves: 30,
+    };
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// What to do at a principal variation node that no earlier search found a best move for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InternalIteration {
+    /// Nothing; search the moves in the order they were generated.
+    Off,
+    /// Search the node shallower first, and try the best move that finds first.
+    Deepening,
+    /// Search the node a ply shallower, expecting a later iteration to find a best move.
+    Reduction,
+}
+
+/// How principal variation nodes without a best move are handled.
+const INTERNAL_ITERATION: InternalIteration = InternalIteration::Deepening;
+/// Depth from which principal variation nodes without a best move are handled.
+const INTERNAL_ITERATION_DEPTH: i32 = 6;
+
+/// Depth from which root moves that keep scoring far below the best move are reduced.
+const ROOT_REDUCTION_DEPTH: i32 = 6;
+/// Plies a trailing root move is reduced by.
+const ROOT_REDUCTION: i32 = 1;
+/// How far (in centipawns) below the best move, beyond the root margin, a root move must
+/// score to count as trailing.
+const ROOT_TRAIL_MARGIN: i32 = 150;
+/// Iterations in a row a root move must trail for before it is reduced.
+const ROOT_TRAIL_ITERATIONS: u32 = 3;
+/// Fewer legal moves than this at the root are always searched in full.
+const ROOT_REDUCTION_MIN_MOVES: usize = 3;
 
 // TODO: when 50-move rule is implemented, this can be limited to searching from the last irreversible move.
 #[must_use]
@@ -13,28 +96,406 @@ pub fn is_repetition_draw(keystack: &[u64], hash: u64) -> bool {
     keystack.iter().filter(|key| **key == hash).count() >= 3
 }
 
+/// Convert a mate score into the number of moves to mate: positive if the side to move mates, negative if it is mated.
+/// Returns `None` for scores that are not mate scores.
+#[must_use]
+pub const fn mate_in(score: i32) -> Option<i32> {
+    // Mate scores count down from MATE_VALUE by one per ply.
+    if score.abs() <= MATE_THRESHOLD {
+        return None;
+    }
+    let moves = (MATE_VALUE - score.abs() + 1) / 2;
+    Some(if score > 0 { moves } else { -moves })
+}
+
+/// Convert a mate score from relative to the root to relative to the position at `ply`, for storing.
+const fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score > MATE_THRESHOLD {
+        score + ply
+    } else if score < -MATE_THRESHOLD {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// Convert a stored mate score back to relative to the root.
+const fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score > MATE_THRESHOLD {
+        score - ply
+    } else if score < -MATE_THRESHOLD {
+        score + ply
+    } else {
+        score
+    }
+}
+
+/// A root move with the score it received from the last search.
+#[derive(Clone, Copy)]
+pub struct RootMove {
+    pub m: Move,
+    pub score: i32,
+}
+
+/// One of the best lines found at the root, for reporting in MultiPV mode.
+#[derive(Clone)]
+pub struct SearchInfo {
+    /// Rank of this line, starting from 1 for the best line
+    pub multipv: usize,
+    /// Depth the line was searched to
+    pub depth: i32,
+    /// Score of the line, from the side to move's point of view
+    pub score: i32,
+    /// Whether the score is exact, or only a bound because it fell outside the root window
+    pub bound: Bound,
+    /// The moves of the line, starting with the root move
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    /// The score as a UCI `info score` value, marked when it is only a bound.
+    #[must_use]
+    pub fn uci_score(&self) -> String {
+        let score = mate_in(self.score).map_or_else(
+            || format!("cp {}", self.score),
+            |moves| format!("mate {moves}"),
+        );
+        match self.bound {
+            Bound::Exact => score,
+            Bound::Lower => format!("{score} lowerbound"),
+            Bound::Upper => format!("{score} upperbound"),
+        }
+    }
+}
+
+/// Nodes visited by a search, split between the main search and quiescence.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeCounts {
+    /// Nodes of the main search
+    pub nodes: u64,
+    /// Quiescence nodes
+    pub qnodes: u64,
+}
+
+impl NodeCounts {
+    /// All nodes, quiescence nodes included.
+    #[must_use]
+    pub const fn total(&self) -> u64 {
+        self.nodes + self.qnodes
+    }
+}
+
+impl Add for NodeCounts {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            nodes: self.nodes + rhs.nodes,
+            qnodes: self.qnodes + rhs.qnodes,
+        }
+    }
+}
+
+impl Sub for NodeCounts {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            nodes: self.nodes - rhs.nodes,
+            qnodes: self.qnodes - rhs.qnodes,
+        }
+    }
+}
+
+/// How far a search has got, in the terms of the xboard `stat01` analysis report.
+#[derive(Clone, Copy)]
+pub struct SearchProgress {
+    /// Time since the search started
+    pub elapsed: Duration,
+    /// Nodes searched, quiescence nodes included
+    pub nodes: u64,
+    /// Depth of the current iteration
+    pub depth: i32,
+    /// Root moves still to search in this iteration, not counting the current one
+    pub moves_left: usize,
+    /// Root moves in this iteration
+    pub total_moves: usize,
+    /// The root move being searched
+    pub current_move: Move,
+}
+
+impl Display for SearchProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "stat01: {} {} {} {} {} {}",
+            self.elapsed.as_millis() / 10,
+            self.nodes,
+            self.depth,
+            self.moves_left,
+            self.total_moves,
+            self.current_move
+        )
+    }
+}
+
+/// A callback for the progress of a search.
+type ProgressReport = Box<dyn FnMut(&SearchProgress)>;
+
+/// Pick uniformly between the root moves scoring within `margin` of the best root move.
+#[must_use]
+pub fn choose_root_move<R: Rng>(root_moves: &[RootMove], margin: i32, rng: &mut R) -> Option<Move> {
+    let best = root_moves.iter().map(|rm| rm.score).max()?;
+    let candidates = root_moves
+        .iter()
+        .filter(|rm| rm.score >= best - margin)
+        .collect::<Vec<_>>();
+    candidates.choose(rng).map(|rm| rm.m)
+}
+
+/// Counts of search decisions, for tuning pruning and checking move ordering.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchStats {
+    /// Null move searches tried.
+    pub nullmove_attempts: u64,
+    /// Null move searches that produced a cutoff.
+    pub nullmove_cutoffs: u64,
+    /// Frontier nodes pruned because the static evaluation was far above beta.
+    pub futility_prunes: u64,
+    /// Null-window searches of moves after the first.
+    pub scout_searches: u64,
+    /// Null-window searches that failed high and had to be searched again with the full window.
+    pub researches: u64,
+    /// Transposition table lookups.
+    pub tt_probes: u64,
+    /// Lookups that found an entry for the position.
+    pub tt_hits: u64,
+    /// Lookups whose entry ended the search of the position.
+    pub tt_cutoffs: u64,
+    /// Nodes that failed high.
+    pub beta_cutoffs: u64,
+    /// Nodes that failed high on the first move searched.
+    pub first_move_cutoffs: u64,
+    /// Positions searched one ply deeper because the side to move was in check.
+    pub check_extensions: u64,
+    /// Positions scored as draws by repetition.
+    pub repetitions: u64,
+    /// Principal variation nodes without a best move that were searched shallower first, or reduced.
+    pub internal_iterations: u64,
+    /// Completed iterations whose best move differs from the previous iteration's.
+    pub best_move_changes: u64,
+    /// Root moves searched shallower because they trailed the best move for several iterations.
+    pub root_reductions: u64,
+}
+
+/// Make the legal move `m`, checking in debug builds that the move generator got it right.
+fn make_legal(board: &Board, m: Move, zobrist: &Zobrist) -> Board {
+    let child = board.make(m, zobrist);
+    debug_assert!(!child.illegal(), "{m} is illegal in {}", board.to_fen(1));
+    child
+}
+
+/// `part` as a percentage of `whole`, or zero if `whole` is.
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    100.0 * (part as f64) / (whole as f64)
+}
+
+impl SearchStats {
+    /// The percentage of null move searches that produced a cutoff.
+    #[must_use]
+    pub fn nullmove_success(&self) -> f64 {
+        percent(self.nullmove_cutoffs, self.nullmove_attempts)
+    }
+
+    /// The percentage of null-window searches that had to be searched again.
+    #[must_use]
+    pub fn research_rate(&self) -> f64 {
+        percent(self.researches, self.scout_searches)
+    }
+
+    /// The percentage of transposition table lookups that found an entry.
+    #[must_use]
+    pub fn tt_hit_rate(&self) -> f64 {
+        percent(self.tt_hits, self.tt_probes)
+    }
+
+    /// The percentage of fail-highs that happened on the first move; the higher, the better the move ordering.
+    #[must_use]
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        percent(self.first_move_cutoffs, self.beta_cutoffs)
+    }
+}
+
+impl std::ops::AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.nullmove_attempts += other.nullmove_attempts;
+        self.nullmove_cutoffs += other.nullmove_cutoffs;
+        self.futility_prunes += other.futility_prunes;
+        self.scout_searches += other.scout_searches;
+        self.researches += other.researches;
+        self.tt_probes += other.tt_probes;
+        self.tt_hits += other.tt_hits;
+        self.tt_cutoffs += other.tt_cutoffs;
+        self.beta_cutoffs += other.beta_cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
+        self.check_extensions += other.check_extensions;
+        self.repetitions += other.repetitions;
+        self.internal_iterations += other.internal_iterations;
+        self.best_move_changes += other.best_move_changes;
+        self.root_reductions += other.root_reductions;
+    }
+}
+
+impl std::fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "null move {:.1}% of {}, futility {}, re-search {:.1}% of {}, \
+             tt hits {:.1}% of {} ({} cutoffs), first-move cutoffs {:.1}% of {}, check extensions {}, \
+             repetitions {}, internal iterations {}, best move changes {}, root reductions {}",
+            self.nullmove_success(),
+            self.nullmove_attempts,
+            self.futility_prunes,
+            self.research_rate(),
+            self.scout_searches,
+            self.tt_hit_rate(),
+            self.tt_probes,
+            self.tt_cutoffs,
+            self.first_move_cutoff_rate(),
+            self.beta_cutoffs,
+            self.check_extensions,
+            self.repetitions,
+            self.internal_iterations,
+            self.best_move_changes,
+            self.root_reductions
+        )
+    }
+}
+
 pub struct Search<'a> {
+    /// Nodes over the lifetime of the search
     nodes: u64,
     qnodes: u64,
-    nullmove_attempts: u64,
-    nullmove_success: u64,
+    /// Lifetime counts when the current move and iteration began
+    move_start: NodeCounts,
+    iteration_start: NodeCounts,
+    stats: SearchStats,
+    seldepth: i32,
     stop_after: Option<Instant>,
+    /// Set by another thread to end the search early, as if time had run out
+    stop_flag: Option<Arc<AtomicBool>>,
     zobrist: &'a Zobrist,
+    tt: &'a mut TranspositionTable,
+    /// Whether the search ran out of time, so results are no longer trustworthy
+    stopped: bool,
+    root_margin: i32,
+    /// Alpha and beta for the root; scores outside them are only bounds
+    root_window: (i32, i32),
+    root_moves: Vec<RootMove>,
+    /// For each root move, how many iterations in a row it has trailed the best move by more
+    /// than `ROOT_TRAIL_MARGIN`, and the hash of the root position the counts are for
+    root_trailing: Vec<(Move, u32)>,
+    root_trailing_hash: u64,
+    /// Index of the keystack entry for the position after the latest null move on the current line
+    null_barrier: usize,
+    /// Index of the keystack entry for the first position after the root; earlier entries are
+    /// the game history
+    root_ply: usize,
+    /// How many of the best root moves to report lines for
+    multi_pv: usize,
+    /// Whether to prune with null moves; analysis may turn this off to see the zugzwangs and
+    /// tactics the pruning would hide
+    use_nullmove: bool,
+    /// Whether to prune one ply from the horizon with a futility margin; analysis may turn this
+    /// off to see quiet moves that win back more than the margin
+    use_futility: bool,
+    /// Whether to try the best move stored for a position first; only turned off to measure
+    /// what the ordering is worth
+    use_tt_move: bool,
+    /// Best root move of the last completed iteration
+    best_move: Option<Move>,
+    /// Root move to search first, such as one remembered from an earlier game
+    root_hint: Option<Move>,
+    /// The lines found by the last call to `search_root`, best first
+    lines: Vec<SearchInfo>,
+    /// Weights for the evaluation
+    eval_params: EvalParams,
+    /// Reductions and margins for the search
+    params: SearchParams,
+    /// When the search was created, for reporting progress
+    started: Instant,
+    /// Depth of the current iteration
+    root_depth: i32,
+    /// Index of the root move being searched, and how many root moves there are
+    root_move_index: (usize, usize),
+    /// The root move being searched
+    root_move: Option<Move>,
+    /// Called with the progress of the search, at most once per `progress_interval`
+    progress_report: Option<ProgressReport>,
+    progress_interval: Duration,
+    /// When progress was last reported
+    last_progress: Instant,
 }
 
 impl<'a> Search<'a> {
     #[must_use]
-    pub const fn new(stop_after: Option<Instant>, zobrist: &'a Zobrist) -> Self {
+    pub fn new(
+        stop_after: Option<Instant>,
+        zobrist: &'a Zobrist,
+        tt: &'a mut TranspositionTable,
+    ) -> Self {
         Self {
             nodes: 0,
             qnodes: 0,
-            nullmove_attempts: 0,
-            nullmove_success: 0,
+            move_start: NodeCounts::default(),
+            iteration_start: NodeCounts::default(),
+            stats: SearchStats::default(),
+            seldepth: 0,
             stop_after,
+            stop_flag: None,
             zobrist,
+            tt,
+            stopped: false,
+            root_margin: 0,
+            root_window: (-100_000, 100_000),
+            root_moves: Vec::new(),
+            root_trailing: Vec::new(),
+            root_trailing_hash: 0,
+            null_barrier: 0,
+            root_ply: 0,
+            multi_pv: 1,
+            use_nullmove: true,
+            use_futility: true,
+            use_tt_move: true,
+            best_move: None,
+            root_hint: None,
+            lines: Vec::new(),
+            eval_params: EvalParams::default(),
+            params: SearchParams::DEFAULT,
+            started: Instant::now(),
+            root_depth: 0,
+            root_move_index: (0, 0),
+            root_move: None,
+            progress_report: None,
+            progress_interval: Duration::ZERO,
+            last_progress: Instant::now(),
         }
     }
 
+    /// Search captures until the position is quiet. At the first ply quiet checks are searched
+    /// too, and every reply to a check at the second ply, so that quiet mates are not missed.
+    ///
+    /// Like `search`, this leaves `pv` empty unless the score is exact: a cutoff clears the line
+    /// found before it, and a score no better than `alpha` never had one.
+    ///
+    /// Once the search is stopped this returns `alpha` with an empty `pv`, and the score means
+    /// nothing. The abort is reported through `stopped`, as it is for `search`, rather than
+    /// through the return type: every caller already checks the flag straight after the call,
+    /// and the nodes either side of the quiescence boundary then unwind the same way.
+    #[allow(clippy::too_many_arguments)]
     fn quiesce(
         &mut self,
         board: &Board,
@@ -42,33 +503,109 @@ impl<'a> Search<'a> {
         beta: i32,
         eval: &EvalState,
         pv: &mut ArrayVec<[Move; 32]>,
+        ply: i32,
+        qply: i32,
     ) -> i32 {
-        let eval_int = eval.get(board.side());
+        self.seldepth = self.seldepth.max(ply);
 
         pv.set_len(0);
 
+        // A capture-heavy position can spend a long time here, so the clock is checked too.
+        if self.poll_stopped() {
+            return alpha;
+        }
+
+        // Standing pat in check would hide the mates the first ply checks are looking for.
+        if qply == 1 && board.in_check() {
+            let mut moves = MoveList::new();
+            board.generate(&mut moves);
+
+            if moves.is_empty() {
+                return -(MATE_VALUE - ply);
+            }
+
+            let mut best_score = -MATE_VALUE;
+            for m in moves {
+                self.qnodes += 1;
+                let eval = eval.clone().update_eval(board, m, &self.eval_params);
+                let board = make_legal(board, m, self.zobrist);
+                let mut child_pv = ArrayVec::new();
+                let score = -self.quiesce(
+                    &board,
+                    -beta,
+                    -alpha,
+                    &eval,
+                    &mut child_pv,
+                    ply + 1,
+                    qply + 1,
+                );
+
+                if self.stopped {
+                    pv.set_len(0);
+                    return alpha;
+                }
+
+                if score >= beta {
+                    pv.set_len(0);
+                    return score;
+                }
+
+                best_score = best_score.max(score);
+                if score > alpha {
+                    alpha = score;
+                    pv.set_len(0);
+                    pv.push(m);
+                    for m in child_pv {
+                        pv.push(m);
+                    }
+                }
+            }
+
+            return best_score;
+        }
+
+        let eval_int = eval.get_with_mobility(board, board.side(), &self.eval_params);
+
         if eval_int >= beta {
-            return beta;
+            return eval_int;
         }
+        let mut best_score = eval_int;
         alpha = alpha.max(eval_int);
 
         board.generate_captures_incremental(|m| {
             self.qnodes += 1;
 
-            let eval = eval.clone().update_eval(board, m);
+            // Captures that lose material in the exchange are not worth searching.
+            if !board.see_ge(m, 0) {
+                return true;
+            }
+
+            let eval = eval.clone().update_eval(board, m, &self.eval_params);
 
             // Pre-empt stand pat by skipping moves with bad evaluation.
             // One can think of this as delta pruning, with the delta being zero.
-            if eval.get(board.side()) <= alpha {
+            if eval.get(board, board.side(), &self.eval_params) <= alpha {
                 return true;
             }
 
-            let board = board.make(m, self.zobrist);
+            let board = make_legal(board, m, self.zobrist);
             let mut child_pv = ArrayVec::new();
-            let score = -self.quiesce(&board, -beta, -alpha, &eval, &mut child_pv);
+            let score = -self.quiesce(
+                &board,
+                -beta,
+                -alpha,
+                &eval,
+                &mut child_pv,
+                ply + 1,
+                qply + 1,
+            );
 
+            if self.stopped {
+                return false;
+            }
+
+            best_score = best_score.max(score);
             if score >= beta {
-                alpha = beta;
                 return false;
             }
 
@@ -84,7 +621,59 @@ impl<'a> Search<'a> {
             true
         });
 
-        alpha
+        if self.stopped {
+            pv.set_len(0);
+            return alpha;
+        }
+
+        // After a cutoff there is nothing left to find, and the line to it is no PV.
+        if best_score >= beta {
+            pv.set_len(0);
+            return best_score;
+        }
+
+        if qply == 0 {
+            let mut moves = MoveList::new();
+            board.generate_checks(&mut moves);
+
+            for m in moves {
+                self.qnodes += 1;
+                let eval = eval.clone().update_eval(board, m, &self.eval_params);
+                let board = make_legal(board, m, self.zobrist);
+                let mut child_pv = ArrayVec::new();
+                let score = -self.quiesce(
+                    &board,
+                    -beta,
+                    -alpha,
+                    &eval,
+                    &mut child_pv,
+                    ply + 1,
+                    qply + 1,
+                );
+
+                if self.stopped {
+                    pv.set_len(0);
+                    return alpha;
+                }
+
+                if score >= beta {
+                    pv.set_len(0);
+                    return score;
+                }
+
+                best_score = best_score.max(score);
+                if score > alpha {
+                    alpha = score;
+                    pv.set_len(0);
+                    pv.push(m);
+                    for m in child_pv {
+                        pv.push(m);
+                    }
+                }
+            }
+        }
+
+        best_score
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -96,77 +685,176 @@ impl<'a> Search<'a> {
         upper_bound: i32,
         eval: &EvalState,
         pv: &mut ArrayVec<[Move; 32]>,
-        mate: i32,
+        ply: i32,
         keystack: &mut Vec<u64>,
     ) -> i32 {
+        self.seldepth = self.seldepth.max(ply);
+
+        // Only an exact score has a line to show, and that is filled in as it is found.
+        pv.set_len(0);
 
         // Check extension
         if board.in_check() {
             depth += 1;
+            self.stats.check_extensions += 1;
         }
 
         if depth <= 0 {
-            return self.quiesce(board, lower_bound, upper_bound, eval, pv);
+            return self.quiesce(board, lower_bound, upper_bound, eval, pv, ply, 0);
         }
 
-        const R: i32 = 3;
+        // Is this a repetition draw?
+        if self.is_repetition(keystack, board.hash()) {
+            self.stats.repetitions += 1;
+            pv.set_len(0);
+            return 0;
+        }
 
-        if !board.in_check() && depth >= 2 {
-            keystack.push(board.hash());
+        // Reuse an earlier search of this position, but not on the principal variation,
+        // which a cutoff would leave without moves. Its best move is tried first below.
+        let hash = board.hash();
+        let tt_entry = self.tt.probe(hash);
+        if upper_bound - lower_bound == 1 {
+            self.stats.tt_probes += 1;
+            if let Some(entry) = tt_entry {
+                self.stats.tt_hits += 1;
+                if entry.depth >= depth {
+                    let score = score_from_tt(entry.score, ply);
+                    let cutoff = match entry.bound {
+                        Bound::Exact => true,
+                        Bound::Lower => score >= upper_bound,
+                        Bound::Upper => score <= lower_bound,
+                    };
+                    if cutoff {
+                        self.stats.tt_cutoffs += 1;
+                        pv.set_len(0);
+                        return score;
+                    }
+                }
+            }
+        }
+        let original_lower_bound = lower_bound;
+
+        // Reduce more at higher depths, where the null move search is expensive anyway.
+        let r = self.params.null_move_reduction + depth / self.params.null_move_depth_divisor;
+
+        // With only pawns left, passing is often better than any real move (zugzwang),
+        // so the null move assumption breaks down.
+        let pieces = board.knights() | board.bishops() | board.rooks() | board.queens();
+        let has_pieces = !(pieces & board.pieces_of_colour(board.side())).empty();
+
+        if self.use_nullmove && !board.in_check() && depth >= 2 && has_pieces {
+            // Positions before a null move cannot repeat after it, because passing is not legal.
             let board = board.make_null(self.zobrist);
+            keystack.push(board.hash());
+            let barrier = std::mem::replace(&mut self.null_barrier, keystack.len() - 1);
             let mut child_pv = ArrayVec::new();
             let score = -self.search(
                 &board,
-                depth - 1 - R,
+                depth - 1 - r,
                 -upper_bound,
                 -upper_bound + 1,
                 eval,
                 &mut child_pv,
-                mate,
+                ply + 1,
                 keystack,
             );
+            self.null_barrier = barrier;
             keystack.pop();
 
-            self.nullmove_attempts += 1;
+            self.stats.nullmove_attempts += 1;
 
             if score >= upper_bound {
-                self.nullmove_success += 1;
-                return upper_bound;
+                self.stats.nullmove_cutoffs += 1;
+                // Passing is not legal, so a mate found after it proves nothing.
+                return if score > MATE_THRESHOLD {
+                    upper_bound
+                } else {
+                    score
+                };
             }
         }
 
-        if !board.in_check() && depth == 1 && eval.get(board.side()) - 200 >= upper_bound {
-            return upper_bound;
+        if self.use_futility && !board.in_check() && depth == 1 {
+            let margin_eval =
+                eval.get(board, board.side(), &self.eval_params) - self.params.futility_margin;
+            if margin_eval >= upper_bound {
+                self.stats.futility_prunes += 1;
+                return margin_eval;
+            }
         }
 
-        let moves: [Move; 256] = [Move::default(); 256];
-        let mut moves = ArrayVec::from(moves);
-        moves.set_len(0);
+        let mut moves = MoveList::new();
         board.generate(&mut moves);
 
         // Is this checkmate or stalemate?
         if moves.is_empty() {
             pv.set_len(0);
             if board.in_check() {
-                return -mate;
+                return -(MATE_VALUE - ply);
             }
             return 0;
         }
 
-        // Is this a repetition draw?
-        if is_repetition_draw(keystack, board.hash()) {
-            pv.set_len(0);
-            return 0;
+        // Try the best move of an earlier search of this position first. The entry may belong
+        // to another position that shares its hash bits, so its move has to be plausible here.
+        let tt_move = tt_entry
+            .and_then(|entry| entry.m)
+            .filter(|&m| self.use_tt_move && board.is_pseudo_legal(m));
+        if let Some(index) = moves.iter().position(|&m| Some(m) == tt_move) {
+            moves[..=index].rotate_right(1);
+        }
+
+        // Without a best move from an earlier search, the moves of a principal variation node
+        // are tried in no particular order, and finding the best one late is expensive.
+        if INTERNAL_ITERATION != InternalIteration::Off
+            && upper_bound - lower_bound > 1
+            && depth >= INTERNAL_ITERATION_DEPTH
+            && tt_move.is_none()
+        {
+            self.stats.internal_iterations += 1;
+            if INTERNAL_ITERATION == InternalIteration::Reduction {
+                depth -= 1;
+            } else {
+                let mut iid_pv = ArrayVec::new();
+                self.search(
+                    board,
+                    depth - 2,
+                    lower_bound,
+                    upper_bound,
+                    eval,
+                    &mut iid_pv,
+                    ply,
+                    keystack,
+                );
+                if self.stopped {
+                    pv.set_len(0);
+                    return lower_bound;
+                }
+                let best = iid_pv.first().copied();
+                if let Some(index) = moves.iter().position(|&m| Some(m) == best) {
+                    moves[..=index].rotate_right(1);
+                }
+            }
+        }
+
+        // Only move extension: a forced move costs nothing to look past.
+        // In check the check extension has already been applied.
+        if moves.len() == 1 && !board.in_check() {
+            depth += 1;
         }
 
         let mut finding_pv = true;
+        let mut best_score = -MATE_VALUE;
 
-        for m in moves {
+        for (i, m) in moves.into_iter().enumerate() {
             self.nodes += 1;
 
+            // Start loading the child's table slot while the move is made.
+            self.tt.prefetch(board.zobrist_after(m, self.zobrist));
             let mut child_pv = ArrayVec::new();
-            let eval = eval.clone().update_eval(board, m);
-            let board = board.make(m, self.zobrist);
+            let eval = eval.clone().update_eval(board, m, &self.eval_params);
+            let board = make_legal(board, m, self.zobrist);
             let mut score;
 
             // Push the move to check for repetition draws
@@ -179,7 +867,7 @@ impl<'a> Search<'a> {
                     -lower_bound,
                     &eval,
                     &mut child_pv,
-                    mate - 1,
+                    ply + 1,
                     keystack,
                 );
             } else {
@@ -190,10 +878,12 @@ impl<'a> Search<'a> {
                     -lower_bound,
                     &eval,
                     &mut child_pv,
-                    mate - 1,
+                    ply + 1,
                     keystack,
                 );
+                self.stats.scout_searches += 1;
                 if score > lower_bound {
+                    self.stats.researches += 1;
                     score = -self.search(
                         &board,
                         depth - 1,
@@ -201,7 +891,7 @@ impl<'a> Search<'a> {
                         -lower_bound,
                         &eval,
                         &mut child_pv,
-                        mate - 1,
+                        ply + 1,
                         keystack,
                     );
                 }
@@ -209,19 +899,25 @@ impl<'a> Search<'a> {
             keystack.pop();
 
             if score >= upper_bound {
+                self.stats.beta_cutoffs += 1;
+                if i == 0 {
+                    self.stats.first_move_cutoffs += 1;
+                }
                 pv.set_len(0);
-                return upper_bound;
+                if !self.stopped {
+                    let tt_score = score_to_tt(score, ply);
+                    self.tt.store(hash, depth, tt_score, Bound::Lower, Some(m));
+                }
+                return score;
             }
 
-            if self.nodes.trailing_zeros() >= 10 {
-                if let Some(time) = self.stop_after {
-                    if Instant::now() >= time {
-                        pv.set_len(0);
-                        return lower_bound;
-                    }
-                }
+            // The score of an interrupted search means nothing; callers check `stopped`.
+            if self.poll_stopped() {
+                pv.set_len(0);
+                return lower_bound;
             }
 
+            best_score = best_score.max(score);
             if score > lower_bound {
                 lower_bound = score;
                 pv.set_len(0);
@@ -232,34 +928,1378 @@ impl<'a> Search<'a> {
                 finding_pv = false;
             }
         }
-        lower_bound
+
+        if !self.stopped {
+            let (bound, best) = if best_score > original_lower_bound {
+                (Bound::Exact, pv.first().copied())
+            } else {
+                (Bound::Upper, None)
+            };
+            let score = score_to_tt(best_score, ply);
+            self.tt.store(hash, depth, score, bound, best);
+        }
+        best_score
     }
 
+    /// Search the root position, keeping the score of every root move in `root_moves`.
+    ///
+    /// Moves scoring within the root margin of the best move are searched with a window wide
+    /// enough to give them exact scores, so callers can choose between near-equal moves.
+    ///
+    /// In MultiPV mode the root is then searched again without the moves already found,
+    /// once for each further line, and every line is kept in `lines`.
     pub fn search_root(
+        &mut self,
+        board: &Board,
+        mut depth: i32,
+        pv: &mut ArrayVec<[Move; 32]>,
+        keystack: &mut Vec<u64>,
+    ) -> i32 {
+        // Check extension
+        if board.in_check() {
+            depth += 1;
+            self.stats.check_extensions += 1;
+        }
+
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        if let Some(i) = moves.iter().position(|&m| Some(m) == self.root_hint) {
+            moves[..=i].rotate_right(1);
+        }
+
+        self.root_moves.clear();
+        self.lines.clear();
+        if self.root_trailing_hash != board.hash() {
+            self.root_trailing.clear();
+            self.root_trailing_hash = board.hash();
+        }
+        self.root_depth = depth;
+        self.root_ply = keystack.len();
+        self.iteration_start = self.lifetime_nodes();
+        pv.set_len(0);
+
+        // Is this checkmate or stalemate?
+        if moves.is_empty() {
+            if board.in_check() {
+                return -MATE_VALUE;
+            }
+            return 0;
+        }
+
+        let score = self.search_root_moves(board, depth, &moves, pv, keystack);
+        if self.stopped {
+            return score;
+        }
+        let best = pv.first().copied();
+        if self.best_move.is_some() && best != self.best_move {
+            self.stats.best_move_changes += 1;
+        }
+        self.best_move = best;
+        self.update_root_trailing(score);
+        self.lines.push(SearchInfo {
+            multipv: 1,
+            depth,
+            score,
+            bound: self.root_bound(score),
+            pv: pv.to_vec(),
+        });
+
+        // Later passes must not disturb the scores of the full search.
+        let root_moves = std::mem::take(&mut self.root_moves);
+        let mut remaining = moves.to_vec();
+        while self.lines.len() < self.multi_pv {
+            let found = self.lines.last().and_then(|line| line.pv.first().copied());
+            remaining.retain(|&m| Some(m) != found);
+            if remaining.is_empty() {
+                break;
+            }
+            let mut line = ArrayVec::new();
+            let score = self.search_root_moves(board, depth, &remaining, &mut line, keystack);
+            if self.stopped || line.is_empty() {
+                break;
+            }
+            self.lines.push(SearchInfo {
+                multipv: self.lines.len() + 1,
+                depth,
+                score,
+                bound: self.root_bound(score),
+                pv: line.to_vec(),
+            });
+        }
+        self.root_moves = root_moves;
+        score
+    }
+
+    /// Count another iteration for every root move that trailed the best score, and start again
+    /// for the ones that did not.
+    fn update_root_trailing(&mut self, best_score: i32) {
+        let cutoff = best_score - self.root_margin - ROOT_TRAIL_MARGIN;
+        let trailing = self
+            .root_moves
+            .iter()
+            .map(|rm| {
+                if rm.score >= cutoff {
+                    return (rm.m, 0);
+                }
+                (rm.m, self.trailing_iterations(rm.m) + 1)
+            })
+            .collect();
+        self.root_trailing = trailing;
+    }
+
+    /// How many iterations in a row root move `m` has trailed the best move.
+    fn trailing_iterations(&self, m: Move) -> u32 {
+        self.root_trailing
+            .iter()
+            .find(|&&(trailing, _)| trailing == m)
+            .map_or(0, |&(_, iterations)| iterations)
+    }
+
+    /// Whether the position with `hash`, the last on the keystack, is a draw by repetition.
+    ///
+    /// Repeating a position from the game history is only a draw on its third occurrence, so a
+    /// winning side may repeat once; but a repeat of a position since the root is scored a draw
+    /// already, since the side that allowed it once could allow it again.
+    fn is_repetition(&self, keystack: &[u64], hash: u64) -> bool {
+        let since_root = keystack
+            .get(self.null_barrier.max(self.root_ply)..)
+            .unwrap_or_default();
+        is_repetition_draw(&keystack[self.null_barrier..], hash)
+            || since_root.iter().filter(|&&key| key == hash).count() >= 2
+    }
+
+    /// What a root score says about the true score, given the root window.
+    const fn root_bound(&self, score: i32) -> Bound {
+        let (alpha, beta) = self.root_window;
+        if score >= beta {
+            Bound::Lower
+        } else if score <= alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        }
+    }
+
+    /// Search `moves` from the root, recording each in `root_moves`, and return the best score.
+    ///
+    /// Quiet moves that do not give check and have trailed the best move for several
+    /// iterations are searched shallower first, unless there are only a few legal moves. Only
+    /// the first pass reduces, so the extra passes of MultiPV mode search every line they
+    /// report in full.
+    fn search_root_moves(
         &mut self,
         board: &Board,
         depth: i32,
+        moves: &[Move],
         pv: &mut ArrayVec<[Move; 32]>,
         keystack: &mut Vec<u64>,
     ) -> i32 {
-        let eval = EvalState::eval(board);
-        self.search(
-            board, depth, -100_000, 100_000, &eval, pv, MATE_VALUE, keystack,
-        )
+        let eval = EvalState::eval(board, &self.eval_params);
+        let (mut lower_bound, upper_bound) = self.root_window;
+        let mut best_score = -MATE_VALUE;
+        let reduce = self.lines.is_empty()
+            && depth >= ROOT_REDUCTION_DEPTH
+            && moves.len() >= ROOT_REDUCTION_MIN_MOVES;
+
+        for (i, &m) in moves.iter().enumerate() {
+            self.nodes += 1;
+            self.root_move_index = (i, moves.len());
+            self.root_move = Some(m);
+            self.report_progress();
+
+            let mut child_pv = ArrayVec::new();
+            let eval = eval.clone().update_eval(board, m, &self.eval_params);
+            let board = make_legal(board, m, self.zobrist);
+            // Anything scoring below this is too far from the best move to be interesting.
+            let floor = lower_bound - self.root_margin;
+            let mut score;
+
+            keystack.push(board.hash());
+            if self.root_moves.is_empty() {
+                score = -self.search(
+                    &board,
+                    depth - 1,
+                    -upper_bound,
+                    -lower_bound,
+                    &eval,
+                    &mut child_pv,
+                    1,
+                    keystack,
+                );
+            } else {
+                let reduction = if reduce
+                    && !m.is_capture()
+                    && !board.in_check()
+                    && self.trailing_iterations(m) >= ROOT_TRAIL_ITERATIONS
+                {
+                    self.stats.root_reductions += 1;
+                    ROOT_REDUCTION
+                } else {
+                    0
+                };
+                score = -self.search(
+                    &board,
+                    depth - 1 - reduction,
+                    -floor - 1,
+                    -floor,
+                    &eval,
+                    &mut child_pv,
+                    1,
+                    keystack,
+                );
+                // A reduced move that does better than expected gets a full search.
+                if score > floor {
+                    score = -self.search(
+                        &board,
+                        depth - 1,
+                        -upper_bound,
+                        -floor,
+                        &eval,
+                        &mut child_pv,
+                        1,
+                        keystack,
+                    );
+                }
+            }
+            keystack.pop();
+
+            self.root_moves.push(RootMove { m, score });
+
+            // A move whose search was cut short has no trustworthy score.
+            if !self.stopped {
+                best_score = best_score.max(score);
+            }
+            if score > lower_bound && !self.stopped {
+                lower_bound = score;
+                pv.set_len(0);
+                pv.push(m);
+                for m in child_pv {
+                    pv.push(m);
+                }
+            }
+
+            if self.should_stop() {
+                self.stopped = true;
+                return lower_bound;
+            }
+
+            // Failing high: the score is a lower bound, and the remaining moves cannot change that.
+            if lower_bound >= upper_bound {
+                break;
+            }
+        }
+        best_score
     }
 
+    /// Check if `best` is an easy move: the last completed iteration scored every other root
+    /// move at least `margin` below `score`.
+    ///
+    /// Moves after the first are searched with a null window, and fail low with an upper
+    /// bound on their score, so falling short of `score - margin` is enough.
+    #[must_use]
+    pub fn is_easy_move(&self, best: Move, score: i32, margin: i32) -> bool {
+        self.root_moves
+            .iter()
+            .filter(|rm| rm.m != best)
+            .all(|rm| rm.score <= score - margin)
+    }
+
+    /// Whether the search has been stopped, checking the clock and the stop flag (and
+    /// reporting progress) about once every 1024 nodes, quiescence nodes included.
+    ///
+    /// Once this returns true, every caller up to the root unwinds without using the scores
+    /// it has, which mean nothing.
+    fn poll_stopped(&mut self) -> bool {
+        if !self.stopped && (self.nodes + self.qnodes).trailing_zeros() >= 10 {
+            self.report_progress();
+            self.stopped = self.should_stop();
+        }
+        self.stopped
+    }
+
+    /// Check whether time has run out or another thread asked us to stop.
+    fn should_stop(&self) -> bool {
+        self.stop_after.is_some_and(|time| Instant::now() >= time)
+            || self
+                .stop_flag
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Report progress to the callback, if one is set and it is due.
+    fn report_progress(&mut self) {
+        if self.progress_report.is_none() {
+            return;
+        }
+        let now = Instant::now();
+        if now.duration_since(self.last_progress) < self.progress_interval {
+            return;
+        }
+        if let (Some(progress), Some(report)) = (self.progress(), &mut self.progress_report) {
+            self.last_progress = now;
+            report(&progress);
+        }
+    }
+
+    /// How far the search has got, once it has started on a root move.
+    #[must_use]
+    pub fn progress(&self) -> Option<SearchProgress> {
+        let current_move = self.root_move?;
+        let (index, total_moves) = self.root_move_index;
+        Some(SearchProgress {
+            elapsed: self.started.elapsed(),
+            nodes: self.move_nodes().total(),
+            depth: self.root_depth,
+            moves_left: total_moves - index - 1,
+            total_moves,
+            current_move,
+        })
+    }
+
+    /// Call `report` with the progress of the search during it, at most once per `interval`.
+    pub fn set_progress(
+        &mut self,
+        interval: Duration,
+        report: impl FnMut(&SearchProgress) + 'static,
+    ) {
+        self.progress_report = Some(Box::new(report));
+        self.progress_interval = interval;
+        self.last_progress = Instant::now();
+    }
+
+    /// The index of the root move being searched in the current iteration, and the move.
+    #[must_use]
+    pub const fn current_root_move(&self) -> Option<(usize, Move)> {
+        match self.root_move {
+            Some(m) => Some((self.root_move_index.0, m)),
+            None => None,
+        }
+    }
+
+    /// Move the time the search stops at, such as to give an unsettled search longer.
+    pub fn set_stop_after(&mut self, stop_after: Option<Instant>) {
+        self.stop_after = stop_after;
+    }
+
+    /// Let another thread end the search early by setting `flag`.
+    pub fn set_stop_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.stop_flag = Some(flag);
+    }
+
+    /// Whether the last search ended early, so its results are incomplete.
+    #[must_use]
+    pub const fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Set how many of the best root moves to find lines for.
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = multi_pv.max(1);
+    }
+
+    /// Turn null move pruning on or off; it is on unless turned off.
+    pub fn set_use_nullmove(&mut self, on: bool) {
+        self.use_nullmove = on;
+    }
+
+    /// Turn futility pruning on or off; it is on unless turned off.
+    pub fn set_use_futility(&mut self, on: bool) {
+        self.use_futility = on;
+    }
+
+    /// Turn trying the transposition table move first on or off; it is on unless turned off.
+    pub fn set_use_tt_move(&mut self, on: bool) {
+        self.use_tt_move = on;
+    }
+
+    /// Evaluate with different weights, such as ones being tuned.
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        self.eval_params = params;
+    }
+
+    /// Search with different reductions and margins, such as ones being tuned.
+    pub fn tune_params(&mut self, params: SearchParams) {
+        self.params = params;
+    }
+
+    /// The reductions and margins the search uses.
+    #[must_use]
+    pub const fn params(&self) -> &SearchParams {
+        &self.params
+    }
+
+    /// The best lines from the last complete call to `search_root`, best first.
+    /// There is only one unless MultiPV mode is on.
+    #[must_use]
+    pub fn lines(&self) -> &[SearchInfo] {
+        &self.lines
+    }
+
+    /// Set how far below the best move (in centipawns) a root move may score and still get an exact score.
+    pub fn set_root_margin(&mut self, margin: i32) {
+        self.root_margin = margin;
+    }
+
+    /// Search `hint` first at the root, if it is one of the legal moves there.
+    pub fn set_root_hint(&mut self, hint: Option<Move>) {
+        self.root_hint = hint;
+    }
+
+    /// Search the root with the window `alpha` to `beta` rather than an infinite one.
+    /// A score outside the window is then only a bound, and the lines say which.
+    pub fn set_root_window(&mut self, alpha: i32, beta: i32) {
+        self.root_window = (alpha, beta);
+    }
+
+    /// The root moves and their scores from the last call to `search_root`, in search order.
+    #[must_use]
+    pub fn root_moves(&self) -> &[RootMove] {
+        &self.root_moves
+    }
+
+    /// Main search nodes for the current move.
     #[must_use]
     pub const fn nodes(&self) -> u64 {
-        self.nodes
+        self.nodes - self.move_start.nodes
     }
 
+    /// Quiescence nodes for the current move.
     #[must_use]
     pub const fn qnodes(&self) -> u64 {
-        self.qnodes
+        self.qnodes - self.move_start.qnodes
+    }
+
+    /// Nodes since the search was created.
+    #[must_use]
+    pub const fn lifetime_nodes(&self) -> NodeCounts {
+        NodeCounts {
+            nodes: self.nodes,
+            qnodes: self.qnodes,
+        }
+    }
+
+    /// Nodes since the current move began, with `new` or `start_move`.
+    #[must_use]
+    pub fn move_nodes(&self) -> NodeCounts {
+        self.lifetime_nodes() - self.move_start
+    }
+
+    /// Nodes of the current or last iteration of `search_root`.
+    #[must_use]
+    pub fn iteration_nodes(&self) -> NodeCounts {
+        self.lifetime_nodes() - self.iteration_start
+    }
+
+    /// Nodes per second for the current move so far.
+    #[must_use]
+    pub fn nps(&self) -> u64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            (self.move_nodes().total() as f64 / elapsed) as u64
+        } else {
+            0
+        }
+    }
+
+    /// Begin counting nodes and time for a new move, keeping the lifetime counts.
+    pub fn start_move(&mut self) {
+        self.move_start = self.lifetime_nodes();
+        self.iteration_start = self.move_start;
+        self.started = Instant::now();
+    }
+
+    /// The deepest ply reached so far, including extensions and quiescence.
+    #[must_use]
+    pub const fn seldepth(&self) -> i32 {
+        self.seldepth
+    }
+
+    #[must_use]
+    pub const fn nullmove_attempts(&self) -> u64 {
+        self.stats.nullmove_attempts
+    }
+
+    /// The number of null move searches that produced a cutoff.
+    #[must_use]
+    pub const fn nullmove_cutoffs(&self) -> u64 {
+        self.stats.nullmove_cutoffs
     }
 
+    /// The percentage of null move searches that produced a cutoff.
     #[must_use]
     pub fn nullmove_success(&self) -> f64 {
-        100.0 * (self.nullmove_success as f64) / (self.nullmove_attempts as f64)
+        self.stats.nullmove_success()
+    }
+
+    /// Search decision counts since the search was created or the stats were last reset.
+    #[must_use]
+    pub const fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    /// Zero the search decision counts, so they can be read per move.
+    pub fn reset_stats(&mut self) {
+        self.stats = SearchStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tinyvec::ArrayVec;
+    use yukari_movegen::{Board, Colour, MoveList, Zobrist, BENCH_FENS};
+
+    use super::{
+        choose_root_move, is_repetition_draw, mate_in, NodeCounts, Search, SearchInfo,
+        SearchParams, SearchProgress, MATE_VALUE, MAX_EVAL,
+    };
+    use crate::eval::{EvalParams, EvalState};
+    use crate::{Bound, TranspositionTable};
+
+    #[test]
+    fn root_margin_keeps_best_score() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let plain = s.search_root(&board, 3, &mut pv, &mut Vec::new());
+        let best = pv[0];
+
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_root_margin(10);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 3, &mut pv, &mut Vec::new());
+
+        assert_eq!(score, plain);
+        assert!(pv[0] == best);
+        assert_eq!(s.root_moves().len(), 20);
+        assert_eq!(s.root_moves().iter().map(|rm| rm.score).max(), Some(score));
+    }
+
+    #[test]
+    fn seldepth_includes_quiescence() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.search_root(&board, 4, &mut ArrayVec::new(), &mut Vec::new());
+        assert!(s.seldepth() >= 4);
+
+        // Plenty of captures are available here, so quiescence must reach past the nominal depth.
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.search_root(&board, 2, &mut ArrayVec::new(), &mut Vec::new());
+        assert!(s.seldepth() > 2);
+    }
+
+    #[test]
+    fn no_null_move_in_pawn_endings() {
+        let zobrist = Zobrist::new();
+
+        let board = Board::from_fen("8/4k3/8/8/2p5/8/1P2K3/8 w - - 0 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.search_root(&board, 6, &mut ArrayVec::new(), &mut Vec::new());
+        assert_eq!(s.nullmove_attempts(), 0);
+
+        let board = Board::from_fen("8/4k3/8/8/2p5/8/1P2K3/4R3 w - - 0 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.search_root(&board, 6, &mut ArrayVec::new(), &mut Vec::new());
+        assert!(s.nullmove_attempts() > 0);
+    }
+
+    #[test]
+    fn easy_move() {
+        let zobrist = Zobrist::new();
+
+        // Taking the hanging queen is much better than anything else. With the king behind the
+        // queen there are no rook checks, whose fail-low bounds would stay loose.
+        let board = Board::from_fen("3k4/8/8/3q4/8/8/3R4/4K3 w - - 0 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 4, &mut pv, &mut Vec::new());
+        assert_eq!(pv[0].to_string(), "d2d5");
+        assert!(s.is_easy_move(pv[0], score, 200));
+
+        // The opening has plenty of reasonable moves.
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 4, &mut pv, &mut Vec::new());
+        assert!(!s.is_easy_move(pv[0], score, 200));
+    }
+
+    #[test]
+    fn random_root_moves() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_root_margin(10);
+        let mut pv = ArrayVec::new();
+        s.search_root(&board, 3, &mut pv, &mut Vec::new());
+
+        let pick = |seed| choose_root_move(s.root_moves(), 10, &mut StdRng::seed_from_u64(seed));
+
+        // The same seed always picks the same move...
+        assert!(pick(1) == pick(1));
+        // ...but different seeds do not all pick the same move.
+        let first = pick(0);
+        assert!((1..32).any(|seed| pick(seed) != first));
+        // A zero margin only ever picks a move with the best score.
+        let score_of = |m| {
+            s.root_moves()
+                .iter()
+                .find(|rm| Some(rm.m) == m)
+                .map(|rm| rm.score)
+        };
+        for seed in 0..32 {
+            let m = choose_root_move(s.root_moves(), 0, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(score_of(m), score_of(Some(pv[0])));
+        }
+    }
+
+    #[test]
+    fn tt_persists_between_searches() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        s.search_root(&board, 5, &mut pv, &mut Vec::new());
+        let first = s.nodes() + s.qnodes();
+        let best = pv[0];
+        assert!(tt.filled() > 0);
+
+        // A second search of the same position builds on the first.
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        s.search_root(&board, 5, &mut pv, &mut Vec::new());
+        assert!(s.nodes() + s.qnodes() < first);
+        assert!(pv[0] == best);
+    }
+
+    #[test]
+    fn node_counters() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+
+        // Two iterations of one move: the iterations add up to the move.
+        s.search_root(&board, 1, &mut pv, &mut Vec::new());
+        let first = s.iteration_nodes();
+        assert!(first.nodes > 0);
+        assert_eq!(s.move_nodes(), first);
+        s.search_root(&board, 2, &mut pv, &mut Vec::new());
+        let second = s.iteration_nodes();
+        assert!(second.total() > first.total());
+        assert_eq!(s.move_nodes(), first + second);
+        assert_eq!(s.nodes() + s.qnodes(), (first + second).total());
+        assert_eq!(s.lifetime_nodes(), first + second);
+
+        // A new move starts from zero but keeps the lifetime count.
+        s.start_move();
+        assert_eq!(s.move_nodes(), NodeCounts::default());
+        assert_eq!(s.iteration_nodes(), NodeCounts::default());
+        assert_eq!(s.nodes(), 0);
+        s.search_root(&board, 1, &mut pv, &mut Vec::new());
+        let third = s.iteration_nodes();
+        assert_eq!(s.move_nodes(), third);
+        assert_eq!(s.lifetime_nodes(), first + second + third);
+    }
+
+    #[test]
+    fn quiescence_finds_quiet_mates() {
+        let zobrist = Zobrist::new();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut qsearch = |fen| {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let eval = EvalState::eval(&board, &EvalParams::DEFAULT);
+            let mut pv = ArrayVec::new();
+            let score = s.quiesce(&board, -MATE_VALUE, MATE_VALUE, &eval, &mut pv, 0, 0);
+            (score, pv.first().map(ToString::to_string))
+        };
+
+        // Ra8 is a quiet move, so a capture-only quiescence would only see the stand-pat score.
+        let (score, best) = qsearch("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(score, MATE_VALUE - 1);
+        assert_eq!(best.as_deref(), Some("a1a8"));
+
+        // With luft the check is answered, and the score is not a mate.
+        let (score, _) = qsearch("6k1/5pp1/7p/8/8/8/8/R5K1 w - - 0 1");
+        assert!(score < 1000);
+    }
+
+    #[test]
+    fn deep_null_moves_keep_mates() {
+        let zobrist = Zobrist::new();
+
+        // Nf6+ gxf6 Bxf7#; at depth 6 the null move searches reduce by 4.
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 6, &mut pv, &mut Vec::new());
+        assert_eq!(mate_in(score), Some(2));
+        assert_eq!(pv[0].to_string(), "d5f6");
+        assert!(s.nullmove_attempts() > 0);
+    }
+
+    #[test]
+    fn mates_outscore_huge_evals() {
+        let zobrist = Zobrist::new();
+
+        // Eight queens and every other piece is worth more than a mate before clamping.
+        let board = Board::from_fen("7k/8/8/QQQ5/QQQ5/QQ6/RRBBNN2/7K w - - 0 1", &zobrist).unwrap();
+        let score = EvalState::eval(&board, &EvalParams::DEFAULT).get(
+            &board,
+            Colour::White,
+            &EvalParams::DEFAULT,
+        );
+        assert_eq!(score, MAX_EVAL);
+        assert_eq!(mate_in(score), None);
+
+        // So the search still prefers the mate to grabbing ever more material.
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 2, &mut pv, &mut Vec::new());
+        assert_eq!(mate_in(score), Some(1));
+        let mated = board.make(pv[0], &zobrist);
+        assert!(mated.in_check());
+    }
+
+    #[test]
+    fn search_stats() {
+        let zobrist = Zobrist::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.search_root(&board, 5, &mut ArrayVec::new(), &mut Vec::new());
+        let stats = *s.stats();
+        assert!(stats.beta_cutoffs > 0);
+        assert!(stats.tt_probes >= stats.tt_hits && stats.tt_hits >= stats.tt_cutoffs);
+        assert!(stats.scout_searches >= stats.researches);
+        assert_eq!(stats.nullmove_cutoffs, s.nullmove_cutoffs());
+        // Ordering regressions should show up here before they show up in playing strength.
+        assert!(stats.first_move_cutoff_rate() > 70.0);
+
+        s.reset_stats();
+        assert_eq!(s.stats().beta_cutoffs, 0);
+        assert_eq!(s.nullmove_attempts(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, ignore = "timing needs a release build")]
+    fn quiescence_checks_the_clock() {
+        // Capture-heavy positions used to spend most of their time in quiescence, which
+        // never looked at the clock, and ran tens of milliseconds past the deadline.
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen(
+            "r2qk2r/ppp2ppp/2npbn2/2b1p3/2B1P3/2NPBN2/PPP2PPP/R2QK2R w KQkq - 0 1",
+            &zobrist,
+        )
+        .unwrap();
+        let mut tt = TranspositionTable::default();
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let mut s = Search::new(Some(deadline), &zobrist, &mut tt);
+        for depth in 1..40 {
+            s.search_root(&board, depth, &mut ArrayVec::new(), &mut Vec::new());
+            if s.stopped() {
+                break;
+            }
+        }
+        assert!(s.stopped());
+        let overrun = Instant::now().saturating_duration_since(deadline);
+        assert!(
+            overrun < Duration::from_millis(15),
+            "overran by {overrun:?}"
+        );
+    }
+
+    #[test]
+    fn stop_flag_interrupts_search() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let flag = Arc::new(AtomicBool::new(false));
+        s.set_stop_flag(Arc::clone(&flag));
+
+        let start = Instant::now();
+        let setter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            flag.store(true, Ordering::Relaxed);
+        });
+        // Deepen the way the engine does, keeping the move of the last complete iteration.
+        let mut best = None;
+        for depth in 1..30 {
+            let mut pv = ArrayVec::new();
+            s.search_root(&board, depth, &mut pv, &mut Vec::new());
+            if s.stopped() {
+                break;
+            }
+            best = pv.first().copied();
+        }
+        setter.join().unwrap();
+
+        assert!(s.stopped());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let best = best.unwrap();
+        assert!(board.parse_move(&best.to_string()).is_ok_and(|m| m == best));
+    }
+
+    #[test]
+    fn multi_pv_reports_second_best() {
+        let zobrist = Zobrist::new();
+        // Rxd5 wins the queen, Bxh4 only the rook, so it scores well below but above the rest.
+        let board = Board::from_fen("4k3/8/8/3q4/7r/8/3R1B2/4K3 w - - 0 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let plain = s.search_root(&board, 4, &mut pv, &mut Vec::new());
+        let root_moves = s.root_moves().len();
+        assert_eq!(s.lines().len(), 1);
+
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_multi_pv(3);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 4, &mut pv, &mut Vec::new());
+        let lines = s.lines();
+        assert_eq!(score, plain);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].pv[0].to_string(), "d2d5");
+        assert_eq!(lines[1].pv[0].to_string(), "f2h4");
+        assert_eq!(lines[0].score, score);
+        assert!(lines[0].score > lines[1].score + 200);
+        assert!(lines[1].score >= lines[2].score);
+        assert_eq!(
+            lines.iter().map(|line| line.multipv).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        // The extra lines do not change the root move scores.
+        assert_eq!(s.root_moves().len(), root_moves);
+    }
+
+    #[test]
+    fn root_fail_high_is_lower_bound() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        s.search_root(&board, 3, &mut pv, &mut Vec::new());
+        let exact = &s.lines()[0];
+        assert_eq!(exact.bound, Bound::Exact);
+        assert!(!exact.uci_score().contains("bound"));
+
+        // The start position is nowhere near lost, so a window far below its score fails high.
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_root_window(-1000, -500);
+        let score = s.search_root(&board, 3, &mut pv, &mut Vec::new());
+        let line = &s.lines()[0];
+        assert!(score >= -500);
+        assert_eq!(line.bound, Bound::Lower);
+        assert_eq!(line.uci_score(), format!("cp {score} lowerbound"));
+        assert!(!pv.is_empty());
+
+        // ...and one far above it fails low.
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_root_window(500, 1000);
+        let score = s.search_root(&board, 3, &mut pv, &mut Vec::new());
+        assert!(score <= 500);
+        assert_eq!(s.lines()[0].bound, Bound::Upper);
+        assert!(s.lines()[0].uci_score().ends_with(" upperbound"));
+    }
+
+    #[test]
+    fn internal_iteration_without_best_move() {
+        let zobrist = Zobrist::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+
+        // Searching straight to depth 7 leaves the principal variation without best moves.
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.search_root(&board, 7, &mut ArrayVec::new(), &mut Vec::new());
+        assert!(s.stats().internal_iterations > 0);
+    }
+
+    #[test]
+    fn stat01_line() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let progress = SearchProgress {
+            elapsed: Duration::from_millis(12_345),
+            nodes: 987_654,
+            depth: 9,
+            moves_left: 14,
+            total_moves: 20,
+            current_move: board.parse_move("g1f3").unwrap(),
+        };
+        assert_eq!(progress.to_string(), "stat01: 1234 987654 9 14 20 g1f3");
+
+        // A search reports on the root move it is working on.
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        assert!(s.progress().is_none());
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        s.set_progress(Duration::ZERO, move |progress| {
+            sink.lock().unwrap().push(progress.to_string());
+        });
+        s.search_root(&board, 3, &mut ArrayVec::new(), &mut Vec::new());
+        let (index, m) = s.current_root_move().unwrap();
+        assert_eq!(index, 19);
+        assert!(m == s.root_moves()[19].m);
+        let reports = reports.lock().unwrap();
+        assert!(reports.len() >= 20);
+        for report in reports.iter() {
+            let fields: Vec<_> = report.split(' ').collect();
+            assert_eq!(fields.len(), 7, "{report}");
+            assert_eq!(fields[0], "stat01:");
+            assert!(
+                fields[1..6].iter().all(|n| n.parse::<u64>().is_ok()),
+                "{report}"
+            );
+            assert_eq!((fields[3], fields[5]), ("3", "20"), "{report}");
+        }
+    }
+
+    #[test]
+    fn mates_survive_fifty_move_fading() {
+        let zobrist = Zobrist::new();
+        // The fading evaluation is nearly level here, but mate scores do not fade.
+        let board = Board::from_fen("6k1/8/5K2/8/8/8/8/1R6 w - - 90 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 5, &mut pv, &mut Vec::new());
+        assert_eq!(mate_in(score), Some(2));
+    }
+
+    /// The best score of a root move that resets the halfmove clock and the best of one that
+    /// does not, searching every root move to `depth`.
+    fn best_resetting_and_reversible(fen: &str, depth: i32) -> (i32, i32) {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_multi_pv(64);
+        s.search_root(&board, depth, &mut ArrayVec::new(), &mut Vec::new());
+        let best = |resets: bool| {
+            s.lines()
+                .iter()
+                .filter(|line| (board.make(line.pv[0], &zobrist).halfmove_clock() == 0) == resets)
+                .map(|line| line.score)
+                .max()
+                .unwrap()
+        };
+        (best(true), best(false))
+    }
+
+    #[test]
+    fn shuffles_toward_fifty_moves_when_worse() {
+        // A pawn down behind a locked pawn chain: pushing the h-pawn only gives up the clock.
+        let fresh = "8/7p/5k2/2p1p1p1/2P1P1P1/4PKP1/8/8 b - - 0 80";
+        let (resetting, reversible) = best_resetting_and_reversible(fresh, 4);
+        let (late_resetting, late_reversible) =
+            best_resetting_and_reversible(&fresh.replace(" 0 80", " 60 80"), 4);
+        assert!(
+            late_reversible - late_resetting > reversible - resetting,
+            "{late_reversible} against {late_resetting}"
+        );
+        // Still a pawn down, however long black shuffles.
+        assert!(late_reversible < 0);
+    }
+
+    #[test]
+    fn makes_progress_when_better() {
+        // Two pawns up: with a fresh clock the king walks up first, but late in the count a
+        // pawn move that resets it is worth at least as much.
+        let fresh = "4k3/8/8/8/8/8/PP6/4K3 w - - 0 60";
+        let (resetting, reversible) = best_resetting_and_reversible(fresh, 3);
+        assert!(reversible > resetting, "{reversible} against {resetting}");
+        let (resetting, reversible) =
+            best_resetting_and_reversible(&fresh.replace(" 0 60", " 58 60"), 3);
+        assert!(resetting >= reversible, "{resetting} against {reversible}");
+    }
+
+    #[test]
+    fn reduces_trailing_root_moves() {
+        let zobrist = Zobrist::new();
+        // Most rook and pawn moves allow a back-rank mate.
+        let board = Board::from_fen("3r2k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", &zobrist).unwrap();
+        let deepen = |multi_pv: usize| {
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
+            s.set_multi_pv(multi_pv);
+            for depth in 1..=7 {
+                s.search_root(&board, depth, &mut ArrayVec::new(), &mut Vec::new());
+            }
+            let lines = s
+                .lines()
+                .iter()
+                .map(|line| (line.pv[0], line.score))
+                .collect::<Vec<_>>();
+            (lines, s.stats().root_reductions)
+        };
+        let (lines, reductions) = deepen(1);
+        assert!(reductions > 0);
+
+        // A single search has no earlier iterations to go by, so reduces nothing.
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        let score = s.search_root(&board, 7, &mut pv, &mut Vec::new());
+        assert_eq!(s.stats().root_reductions, 0);
+        assert!(lines[0] == (pv[0], score));
+
+        // Every line MultiPV asks for is still found.
+        let (lines, _) = deepen(3);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0] == (pv[0], score));
+
+        // With only two legal moves, both are always searched in full.
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/7P/q5K1 w - - 0 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        for depth in 1..=7 {
+            s.search_root(&board, depth, &mut ArrayVec::new(), &mut Vec::new());
+        }
+        assert_eq!(s.root_moves().len(), 2);
+        assert_eq!(s.stats().root_reductions, 0);
+    }
+
+    #[test]
+    fn avoids_allowing_threefold() {
+        let zobrist = Zobrist::new();
+        let hash = |fen: &str| Board::from_fen(fen, &zobrist).unwrap().hash();
+        // A rook up, white has shuffled Re1-d1 while black shuffled Kg8-h8.
+        let board = Board::from_fen("6k1/5p1p/6p1/8/8/8/5PPP/4R1K1 w - - 0 1", &zobrist).unwrap();
+        let kh8_rd1 = hash("7k/5p1p/6p1/8/8/8/5PPP/3R2K1 w - - 0 1");
+        let kh8_re1 = hash("7k/5p1p/6p1/8/8/8/5PPP/4R1K1 b - - 0 1");
+        let kg8_rd1 = hash("6k1/5p1p/6p1/8/8/8/5PPP/3R2K1 b - - 0 1");
+        let rd1 = board.parse_move("e1d1").unwrap();
+        let score_of = |keystack: &mut Vec<u64>| {
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
+            s.set_multi_pv(64);
+            s.search_root(&board, 4, &mut ArrayVec::new(), keystack);
+            let best = &s.lines()[0];
+            assert!(best.pv[0] != rd1);
+            assert!(best.score > 200, "{}", best.score);
+            let line = s.lines().iter().find(|line| line.pv[0] == rd1).unwrap();
+            line.score
+        };
+
+        // Having seen Kh8 with the rook on d1 once, white may allow it again.
+        let mut keystack = vec![kh8_rd1, kh8_re1];
+        assert!(score_of(&mut keystack) > 200);
+
+        // But a second time, Rd1 lets black claim a draw by repetition with Kh8.
+        let mut keystack = vec![kh8_rd1, kh8_re1, board.hash(), kg8_rd1, kh8_rd1, kh8_re1];
+        assert_eq!(score_of(&mut keystack), 0);
+    }
+
+    #[test]
+    fn no_repetitions_through_null_moves() {
+        let zobrist = Zobrist::new();
+        // With the kings boxed in, only the knights can shuffle, and they cannot lose a tempo.
+        // So the placement can only come back with white to move, unless a null move passes.
+        let board = Board::from_fen("6nk/6pp/8/8/8/8/6PP/5NNK w - - 0 1", &zobrist).unwrap();
+        let passed = Board::from_fen("6nk/6pp/8/8/8/8/6PP/5NNK b - - 0 1", &zobrist).unwrap();
+        let search = |mut keystack: Vec<u64>| {
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
+            s.search_root(&board, 8, &mut ArrayVec::new(), &mut keystack);
+            assert!(s.nullmove_attempts() > 0);
+            (s.nodes() + s.qnodes(), s.stats().repetitions)
+        };
+
+        // Pretend the placement has come up twice already with black to move. The shuffles
+        // within the search repeat positions of their own, but the history only matters if a
+        // null move reaches it, so the search goes just as it does after an unrelated history.
+        let unrelated = Board::startpos(&zobrist).hash();
+        assert_eq!(
+            search(vec![passed.hash(), passed.hash()]),
+            search(vec![unrelated, unrelated])
+        );
+    }
+
+    #[test]
+    fn king_escorts_passer() {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen("8/8/5k2/8/8/8/4P3/2K5 w - - 0 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        for depth in 1..=6 {
+            s.search_root(&board, depth, &mut pv, &mut Vec::new());
+        }
+        // Pushing the pawn alone lets the black king win it; the white king must come up first.
+        let best = pv[0];
+        assert_eq!(
+            best.from.to_string(),
+            "c1",
+            "{best} does not bring the king up"
+        );
+    }
+
+    #[test]
+    fn rook_infiltrates() {
+        let zobrist = Zobrist::new();
+        let board =
+            Board::from_fen("6k1/p4ppp/1p6/8/8/1P6/P4PPP/5RK1 w - - 0 1", &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        let mut pv = ArrayVec::new();
+        for depth in 1..=4 {
+            s.search_root(&board, depth, &mut pv, &mut Vec::new());
+        }
+        // The rook heads for an open file, and down it to the seventh rank.
+        let line = pv.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert!(
+            ["f1c1", "f1d1", "f1e1"].contains(&line[0].as_str()),
+            "{line:?} does not open with a rook move to an open file"
+        );
+        assert!(
+            line[2].starts_with(&line[0][2..]) && line[2].ends_with('7'),
+            "{line:?} does not take the rook to the seventh rank"
+        );
+    }
+
+    #[test]
+    fn root_hint_searched_first() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let hint = board.parse_move("g2g3").unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_root_hint(Some(hint));
+        let mut pv = ArrayVec::new();
+        s.search_root(&board, 3, &mut pv, &mut Vec::new());
+        assert!(s.root_moves()[0].m == hint);
+        assert_eq!(s.root_moves().len(), 20);
+    }
+
+    #[test]
+    fn wrong_tt_move_ignored() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let search = |tt: &mut TranspositionTable| {
+            let mut s = Search::new(None, &zobrist, tt);
+            let mut pv = ArrayVec::new();
+            let score = s.search_root(&board, 4, &mut pv, &mut Vec::new());
+            (score, pv.first().copied(), s.nodes() + s.qnodes())
+        };
+        let (score, best, nodes) = search(&mut TranspositionTable::default());
+
+        // Fill the entries of the replies to every root move with a move of the start position,
+        // as if another position with the same hash had stored it.
+        let mut tt = TranspositionTable::default();
+        let wrong = board.parse_move("e2e4").unwrap();
+        let mut moves = MoveList::new();
+        board.generate(&mut moves);
+        for m in moves {
+            let reply = board.make(m, &zobrist);
+            assert!(!reply.is_pseudo_legal(wrong));
+            tt.store(reply.hash(), -100, 0, Bound::Upper, Some(wrong));
+        }
+        let (wrong_score, wrong_best, wrong_nodes) = search(&mut tt);
+        assert_eq!(wrong_score, score);
+        assert!(wrong_best == best);
+        assert_eq!(wrong_nodes, nodes);
+    }
+
+    #[test]
+    fn tt_move_ordering() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let search = |use_tt_move: bool| {
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
+            s.set_use_tt_move(use_tt_move);
+            let mut pv = ArrayVec::new();
+            let mut score = 0;
+            for depth in 1..=6 {
+                score = s.search_root(&board, depth, &mut pv, &mut Vec::new());
+            }
+            (score, pv.first().copied(), s.nodes() + s.qnodes())
+        };
+        let (score, best, nodes) = search(true);
+        let (unordered_score, unordered_best, unordered_nodes) = search(false);
+        assert_eq!(score, unordered_score);
+        assert!(best == unordered_best);
+        // Iterative deepening to depth 6 searches about half the nodes with the ordering, and
+        // the saving grows with depth.
+        assert!(
+            nodes * 3 < unordered_nodes * 2,
+            "{nodes} against {unordered_nodes}"
+        );
+    }
+
+    #[test]
+    fn pruning_toggles() {
+        let zobrist = Zobrist::new();
+        // White wins only by the quiet Kh6, after which black must break the pin or lose a
+        // piece; the null move search lets black pass instead, and misses it.
+        let board = Board::from_fen("1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1", &zobrist).unwrap();
+        let kh6 = board.parse_move("g5h6").unwrap();
+        let search = |nullmove: bool| {
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
+            s.set_use_nullmove(nullmove);
+            let mut pv = ArrayVec::new();
+            let score = s.search_root(&board, 6, &mut pv, &mut Vec::new());
+            (score, pv[0], s.stats().nullmove_attempts)
+        };
+        let (pruned_score, pruned_best, attempts) = search(true);
+        assert!(attempts > 0);
+        assert!(pruned_best != kh6);
+        let (score, best, attempts) = search(false);
+        assert_eq!(attempts, 0);
+        assert!(best == kh6);
+        assert!(score > pruned_score + 200, "{score} {pruned_score}");
+
+        // Without futility pruning, nothing is pruned at the horizon.
+        let board = Board::startpos(&zobrist);
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+        s.set_use_futility(false);
+        s.search_root(&board, 4, &mut ArrayVec::new(), &mut Vec::new());
+        assert_eq!(s.stats().futility_prunes, 0);
+    }
+
+    /// Replay `line` from `board`, checking every move is legal and, for an exact score, that
+    /// the position it ends in evaluates to the score: by the static evaluation, or as mate or
+    /// a draw by the rules.
+    fn assert_pv_consistent(board: &Board, line: &SearchInfo, zobrist: &Zobrist) {
+        let mut board = board.clone();
+        let mut keystack = vec![board.hash()];
+        for &m in &line.pv {
+            assert!(board.is_legal(m), "{m} in {}", board.to_fen(1));
+            board = board.make(m, zobrist);
+            keystack.push(board.hash());
+        }
+        if line.bound != Bound::Exact {
+            return;
+        }
+
+        // The score is from the root's side, and the leaf's side to move moved last if the
+        // line has an odd length.
+        let sign = if line.pv.len().is_multiple_of(2) {
+            1
+        } else {
+            -1
+        };
+        if mate_in(line.score).is_some() {
+            assert!(
+                board.is_checkmate(),
+                "{} ends {}",
+                line.score,
+                board.to_fen(1)
+            );
+        } else if is_repetition_draw(&keystack, board.hash()) || board.is_stalemate() {
+            assert_eq!(line.score, 0);
+        } else {
+            let params = EvalParams::default();
+            let eval = EvalState::eval(&board, &params);
+            let leaf = eval.get_with_mobility(&board, board.side(), &params);
+            assert_eq!(sign * leaf, line.score, "line ends {}", board.to_fen(1));
+        }
+    }
+
+    #[test]
+    fn pvs_replay() {
+        let zobrist = Zobrist::new();
+        let mut fens = BENCH_FENS.to_vec();
+        // Quiet mates, and a mate found through quiescence checks.
+        fens.extend([
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            "1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1",
+        ]);
+        for fen in fens {
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
+            s.set_multi_pv(3);
+            for depth in 1..=4 {
+                let mut pv = ArrayVec::new();
+                s.search_root(&board, depth, &mut pv, &mut vec![board.hash()]);
+                for line in s.lines() {
+                    assert_pv_consistent(&board, line, &zobrist);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn best_move_changes() {
+        let zobrist = Zobrist::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let mut tt = TranspositionTable::default();
+        let mut s = Search::new(None, &zobrist, &mut tt);
+
+        // Every iteration's best move is compared with the one before.
+        let mut changes = 0;
+        let mut best = None;
+        for depth in 1..=5 {
+            let mut pv = ArrayVec::new();
+            s.search_root(&board, depth, &mut pv, &mut Vec::new());
+            if best.is_some() && pv.first() != best.as_ref() {
+                changes += 1;
+            }
+            best = pv.first().copied();
+        }
+        assert_eq!(s.stats().best_move_changes, changes);
+        let line = s.stats().to_string();
+        let reductions = s.stats().root_reductions;
+        assert!(line.ends_with(&format!(
+            "best move changes {changes}, root reductions {reductions}"
+        )));
+    }
+
+    #[test]
+    fn tuned_params() {
+        let zobrist = Zobrist::new();
+        let board = Board::startpos(&zobrist);
+        let search = |params: SearchParams| {
+            let mut tt = TranspositionTable::default();
+            let mut s = Search::new(None, &zobrist, &mut tt);
+            assert_eq!(*s.params(), SearchParams::default());
+            s.tune_params(params);
+            s.search_root(&board, 6, &mut ArrayVec::new(), &mut Vec::new());
+            (s.nodes() + s.qnodes(), s.stats().nullmove_cutoffs)
+        };
+
+        // Reducing null moves less changes the tree searched.
+        let default = search(SearchParams::DEFAULT);
+        let shallow = search(SearchParams {
+            null_move_reduction: 1,
+            ..SearchParams::DEFAULT
+        });
+        assert_ne!(default, shallow);
     }
 }
@@ -0,0 +1,107 @@
+use yukari_movegen::{Board, Move, Piece};
+
+/// Halfmove clock margin before the fifty-move limit within which a winning line must reach a
+/// zeroing move before zeroing moves are preferred outright.
+const ZEROING_MARGIN: u32 = 20;
+
+/// A root move with its tablebase distance-to-zeroing.
+#[derive(Clone, Copy)]
+pub struct TablebaseMove {
+    pub m: Move,
+    /// Plies from the position after the move until the next pawn move or capture with best play:
+    /// positive if the side moving wins, negative if it loses, zero if the move draws.
+    pub dtz: i32,
+}
+
+/// Whether a move resets the halfmove clock.
+fn is_zeroing(board: &Board, m: Move) -> bool {
+    m.is_capture() || board.piece_from_square(m.from) == Some(Piece::Pawn)
+}
+
+/// Pick the root move that converts a tablebase win without running into the fifty-move rule.
+///
+/// A win only counts if its next zeroing move comes before the halfmove clock reaches 100.
+/// Normally the move with the lowest DTZ is picked; if that line only zeroes within
+/// `ZEROING_MARGIN` plies of the limit, a winning pawn move or capture is played right away.
+/// Returns `None` if no move keeps a win, leaving the choice to the search.
+#[must_use]
+pub fn choose_tablebase_move(board: &Board, moves: &[TablebaseMove]) -> Option<Move> {
+    let clock = board.halfmove_clock();
+    // The halfmove clock at the next zeroing move of the line after this one.
+    let clock_at_zeroing = |tm: &TablebaseMove| {
+        let after = if is_zeroing(board, tm.m) {
+            0
+        } else {
+            clock + 1
+        };
+        after + tm.dtz.unsigned_abs()
+    };
+    let wins = moves
+        .iter()
+        .filter(|tm| tm.dtz > 0 && clock_at_zeroing(tm) <= 100)
+        .collect::<Vec<_>>();
+
+    let fastest = wins.iter().min_by_key(|tm| tm.dtz)?;
+    if clock_at_zeroing(fastest) + ZEROING_MARGIN > 100 {
+        if let Some(tm) = wins
+            .iter()
+            .filter(|tm| is_zeroing(board, tm.m))
+            .min_by_key(|tm| tm.dtz)
+        {
+            return Some(tm.m);
+        }
+    }
+    Some(fastest.m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{choose_tablebase_move, TablebaseMove};
+    use yukari_movegen::{Board, Zobrist};
+
+    /// KRPvKR, a long win; the DTZ values stand in for a tablebase probe.
+    fn choose(fen: &str) -> Option<String> {
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let moves = [
+            ("Kb2", 15),
+            ("Rf1", 21),
+            ("a3", 38),
+            ("Kb1", -4),
+            ("Re1", 0),
+        ]
+        .map(|(m, dtz)| TablebaseMove {
+            m: board.parse_move(m).unwrap(),
+            dtz,
+        });
+        choose_tablebase_move(&board, &moves).map(|m| m.to_string())
+    }
+
+    #[test]
+    fn fastest_win_with_a_fresh_clock() {
+        let fen = "8/8/2k5/8/8/8/P7/K2R3r w - - 0 1";
+        assert_eq!(choose(fen).as_deref(), Some("a1b2"));
+    }
+
+    #[test]
+    fn pawn_moves_near_the_fifty_move_limit() {
+        // Kb2 still wins in time, but with only four plies to spare, so push the pawn instead of shuffling.
+        let fen = "8/8/2k5/8/8/8/P7/K2R3r w - - 80 60";
+        assert_eq!(choose(fen).as_deref(), Some("a2a3"));
+    }
+
+    #[test]
+    fn wins_past_the_limit_do_not_count() {
+        // Only the pawn push zeroes the clock before the fifty-move rule draws the game.
+        let fen = "8/8/2k5/8/8/8/P7/K2R3r w - - 95 60";
+        assert_eq!(choose(fen).as_deref(), Some("a2a3"));
+
+        let zobrist = Zobrist::new();
+        let board = Board::from_fen(fen, &zobrist).unwrap();
+        let moves = [TablebaseMove {
+            m: board.parse_move("Kb2").unwrap(),
+            dtz: 15,
+        }];
+        assert!(choose_tablebase_move(&board, &moves).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use yukari_movegen::Move;
+
+/// Default transposition table size, in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// What a stored score says about the true score of a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The true score is at least this score.
+    Lower,
+    /// The true score is at most this score.
+    Upper,
+}
+
+/// A search result for a single position.
+#[derive(Clone, Copy)]
+pub struct Entry {
+    /// Full hash of the position, to detect index collisions
+    key: u64,
+    /// Depth the position was searched to
+    pub depth: i32,
+    /// Score of the position, with mate scores relative to this position
+    pub score: i32,
+    /// Whether the score is exact or a bound
+    pub bound: Bound,
+    /// Best move found, if any move raised alpha
+    pub m: Option<Move>,
+}
+
+/// A hash table of search results, kept between searches so later searches can reuse earlier work.
+#[derive(Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    /// Create a table using about `megabytes` megabytes of memory.
+    #[must_use]
+    pub fn new(megabytes: usize) -> Self {
+        Self {
+            entries: vec![None; Self::slots(megabytes)],
+        }
+    }
+
+    /// Number of slots that fit in `megabytes` megabytes, and at least one.
+    fn slots(megabytes: usize) -> usize {
+        (megabytes * 1024 * 1024 / std::mem::size_of::<Option<Entry>>()).max(1)
+    }
+
+    /// Change the table to use about `megabytes` megabytes of memory, forgetting every stored
+    /// position. The allocation is reused when it is big enough, and given back when it shrinks.
+    pub fn resize(&mut self, megabytes: usize) {
+        let size = Self::slots(megabytes);
+        self.entries.clear();
+        self.entries.resize(size, None);
+        self.entries.shrink_to_fit();
+    }
+
+    /// Forget every stored position.
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    /// Count the slots in the table, filled or not.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Count the positions stored in the table.
+    #[must_use]
+    pub fn filled(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_some()).count()
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.entries.len() as u64) as usize
+    }
+
+    /// Look up the stored result for a position.
+    #[must_use]
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        self.entries[self.index(hash)].filter(|entry| entry.key == hash)
+    }
+
+    /// Start loading the slot for a position into the cache, ahead of probing it.
+    pub fn prefetch(&self, hash: u64) {
+        let slot = &self.entries[self.index(hash)];
+        #[cfg(target_arch = "x86_64")]
+        // SAFETY: prefetching is only a hint to the processor, and `slot` is a valid reference.
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            _mm_prefetch::<_MM_HINT_T0>(std::ptr::from_ref(slot).cast());
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = slot;
+    }
+
+    /// Store a search result, unless the same position is already stored from a deeper search.
+    pub fn store(&mut self, hash: u64, depth: i32, score: i32, bound: Bound, m: Option<Move>) {
+        let index = self.index(hash);
+        let slot = &mut self.entries[index];
+        if let Some(entry) = slot {
+            if entry.key == hash && entry.depth > depth {
+                return;
+            }
+        }
+        *slot = Some(Entry {
+            key: hash,
+            depth,
+            score,
+            bound,
+            m,
+        });
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bound, TranspositionTable};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn store_and_probe() {
+        let mut tt = TranspositionTable::new(1);
+        assert!(tt.probe(12345).is_none());
+
+        tt.store(12345, 3, 50, Bound::Exact, None);
+        let entry = tt.probe(12345).unwrap();
+        assert_eq!(
+            (entry.depth, entry.score, entry.bound),
+            (3, 50, Bound::Exact)
+        );
+        assert_eq!(tt.filled(), 1);
+
+        // A shallower result for the same position does not replace a deeper one...
+        tt.store(12345, 2, -20, Bound::Upper, None);
+        assert_eq!(tt.probe(12345).unwrap().depth, 3);
+        // ...but a deeper one does.
+        tt.store(12345, 5, 10, Bound::Lower, None);
+        assert_eq!(tt.probe(12345).unwrap().bound, Bound::Lower);
+
+        // A different position in the same slot is not mistaken for this one.
+        let other = 12345 + tt.entries.len() as u64;
+        assert!(tt.probe(other).is_none());
+
+        tt.clear();
+        assert!(tt.probe(12345).is_none());
+        assert_eq!(tt.filled(), 0);
+    }
+
+    #[test]
+    fn random_keys() {
+        let mut rng = StdRng::seed_from_u64(0x7961_6b75_7261);
+        let mut tt = TranspositionTable::new(1);
+        for _ in 0..10_000 {
+            let hash: u64 = rng.gen();
+            let depth = rng.gen_range(0..20);
+            let previous = tt.probe(hash);
+            let evicted = tt.entries[tt.index(hash)].filter(|entry| entry.key != hash);
+            tt.store(hash, depth, 0, Bound::Exact, None);
+
+            // A stored key can be found again, unless a deeper result for it was kept instead.
+            let entry = tt.probe(hash).unwrap();
+            match previous {
+                Some(previous) if previous.depth > depth => assert_eq!(entry.depth, previous.depth),
+                _ => assert_eq!(entry.depth, depth),
+            }
+            // Whatever held the slot before is gone.
+            if let Some(evicted) = evicted {
+                assert!(tt.probe(evicted.key).is_none());
+            }
+        }
+        assert!(tt.filled() <= tt.capacity());
+    }
+
+    #[test]
+    fn resize() {
+        let mut tt = TranspositionTable::new(2);
+        let capacity = tt.capacity();
+        tt.store(0, 1, 0, Bound::Exact, None);
+        assert_eq!(tt.filled(), 1);
+
+        tt.resize(1);
+        assert_eq!(tt.capacity(), capacity / 2);
+        assert_eq!(tt.filled(), 0);
+        assert!(tt.probe(0).is_none());
+
+        // A hash of zero is as good as any other.
+        tt.store(0, 1, 0, Bound::Exact, None);
+        assert!(tt.probe(0).is_some());
+
+        tt.resize(2);
+        assert_eq!(tt.capacity(), capacity);
+        assert_eq!(tt.filled(), 0);
+
+        // Even no memory leaves a usable table.
+        tt.resize(0);
+        assert_eq!(tt.capacity(), 1);
+        tt.store(7, 1, 0, Bound::Exact, None);
+        assert!(tt.probe(7).is_some());
+    }
+}
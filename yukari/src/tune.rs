@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use rand::{rngs::StdRng, seq::index, SeedableRng};
+use yukari_movegen::{Board, Zobrist};
+
+/// Where a tuner gets its training positions from, a batch at a time.
+pub trait PositionSource {
+    /// Up to `n` positions chosen at random, none twice; fewer if the source holds fewer, or
+    /// some of those chosen turn out not to be positions.
+    fn sample(&mut self, n: usize) -> Vec<Board>;
+
+    /// How many positions the source holds.
+    fn len(&self) -> usize;
+
+    /// Whether the source holds no positions at all.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Positions held in memory, for sets small enough to keep as boards.
+pub struct MemoryPositions {
+    boards: Vec<Board>,
+    rng: StdRng,
+}
+
+impl MemoryPositions {
+    /// Sample from `boards`, in an order fixed by `seed`.
+    #[must_use]
+    pub fn new(boards: Vec<Board>, seed: u64) -> Self {
+        Self {
+            boards,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl PositionSource for MemoryPositions {
+    fn sample(&mut self, n: usize) -> Vec<Board> {
+        let n = n.min(self.boards.len());
+        index::sample(&mut self.rng, self.boards.len(), n)
+            .into_iter()
+            .map(|i| self.boards[i].clone())
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.boards.len()
+    }
+}
+
+/// Positions read from a FEN or EPD file as they are sampled, one per line.
+///
+/// Opening the file only notes where each line starts, eight bytes a position, so a file of
+/// millions of positions is ready in seconds and never held in memory; a line that does not
+/// parse is dropped from the sample it was drawn for.
+pub struct EpdFile {
+    reader: BufReader<File>,
+    /// Offset of the start of every non-blank line
+    offsets: Vec<u64>,
+    zobrist: Zobrist,
+    rng: StdRng,
+}
+
+impl EpdFile {
+    /// Index the lines of the file at `path`, to sample them in an order fixed by `seed`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read.
+    pub fn open(path: impl AsRef<Path>, seed: u64) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            if !line.trim_ascii().is_empty() {
+                offsets.push(offset);
+            }
+            offset += read as u64;
+        }
+        Ok(Self {
+            reader,
+            offsets,
+            zobrist: Zobrist::new(),
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    /// Read the position of the line starting at `offset`.
+    fn read_at(&mut self, offset: u64) -> io::Result<Option<Board>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        Ok(Board::from_fen(line.trim(), &self.zobrist).ok())
+    }
+}
+
+impl PositionSource for EpdFile {
+    fn sample(&mut self, n: usize) -> Vec<Board> {
+        let n = n.min(self.offsets.len());
+        let mut chosen: Vec<u64> = index::sample(&mut self.rng, self.offsets.len(), n)
+            .into_iter()
+            .map(|i| self.offsets[i])
+            .collect();
+        // Reading in file order keeps the seeks short.
+        chosen.sort_unstable();
+        chosen
+            .into_iter()
+            .filter_map(|offset| self.read_at(offset).ok().flatten())
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use yukari_movegen::{Board, Zobrist, BENCH_FENS};
+
+    use super::{EpdFile, MemoryPositions, PositionSource};
+
+    #[test]
+    fn file_and_memory_agree() {
+        let path = std::env::temp_dir().join(format!("yukari-tune-{}.epd", std::process::id()));
+        // EPD operations and blank lines are allowed; a line that is no position is not.
+        let mut text = String::new();
+        for fen in BENCH_FENS {
+            text.push_str(fen);
+            text.push_str(" c9 \"1/2-1/2\";\n\n");
+        }
+        text.push_str("not a position\n");
+        std::fs::write(&path, &text).unwrap();
+
+        let mut file = EpdFile::open(&path, 1).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(file.len(), BENCH_FENS.len() + 1);
+
+        let zobrist = Zobrist::new();
+        let boards: Vec<_> = BENCH_FENS
+            .iter()
+            .map(|fen| Board::from_fen(fen, &zobrist).unwrap())
+            .collect();
+        let hashes: HashSet<_> = boards.iter().map(Board::hash).collect();
+        let mut memory = MemoryPositions::new(boards, 1);
+        assert_eq!(memory.len(), BENCH_FENS.len());
+
+        // Samples are of distinct positions from the set, and cover it all when large enough.
+        let sample = file.sample(10);
+        assert!(sample.len() >= 9);
+        let sampled: HashSet<_> = sample.iter().map(Board::hash).collect();
+        assert_eq!(sampled.len(), sample.len());
+        assert!(sampled.is_subset(&hashes));
+        let all: HashSet<_> = file.sample(1000).iter().map(Board::hash).collect();
+        assert_eq!(all, hashes);
+
+        let sample = memory.sample(10);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.iter().all(|board| hashes.contains(&board.hash())));
+        assert_eq!(memory.sample(1000).len(), BENCH_FENS.len());
+    }
+}
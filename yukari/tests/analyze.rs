@@ -0,0 +1,52 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use yukari_movegen::{Board, Zobrist};
+
+/// Run a batch analysis with `args`, feeding `input` on standard input, returning its output.
+fn analyze(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yukari"))
+        .arg("analyze")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start engine");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("engine did not finish");
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn positions_from_stdin() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - -",
+    ];
+    let zobrist = Zobrist::new();
+    for args in [&["--depth", "4"][..], &["--movetime", "100"][..]] {
+        let output = analyze(args, &format!("{}\n{}\n", fens[0], fens[1]));
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2, "{output}");
+        for (fen, line) in fens.iter().zip(lines) {
+            assert!(line.starts_with(&format!("{fen}: ")), "{line}");
+            let (_, pv) = line.split_once(" pv ").expect("no principal variation");
+            let best = pv.split(' ').next().unwrap();
+            let board = Board::from_fen(fen, &zobrist).unwrap();
+            assert!(
+                board.parse_move(best).is_ok(),
+                "{best} is not legal in {fen}"
+            );
+        }
+    }
+
+    // The back rank mate is found.
+    let output = analyze(&["--depth", "4"], fens[1]);
+    assert!(output.contains(" pv a1a8"), "{output}");
+}
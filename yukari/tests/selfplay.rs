@@ -0,0 +1,128 @@
+use std::process::Command;
+
+#[test]
+fn short_match() {
+    let pgn = std::env::temp_dir().join(format!("yukari-selfplay-{}.pgn", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_yukari"))
+        .args([
+            "selfplay",
+            "--games",
+            "2",
+            "--tc",
+            "1+0.01",
+            "--openings",
+            "--pgn",
+        ])
+        .arg(&pgn)
+        .output()
+        .expect("failed to run match");
+    assert!(output.status.success());
+
+    let log = String::from_utf8(output.stdout).unwrap();
+    assert!(log.contains("Game 1: "));
+    assert!(log.contains("Game 2: "));
+    let summary = log.lines().last().unwrap();
+    assert!(summary.starts_with("Score of "));
+    assert!(summary.contains(" [") && summary.contains("] 2, Elo "));
+
+    let games = std::fs::read_to_string(&pgn).unwrap();
+    std::fs::remove_file(&pgn).unwrap();
+    assert_eq!(games.matches("[Result \"").count(), 2);
+    // Both games start from the first built-in opening, once with each colour.
+    assert_eq!(games.matches("\n1. e4 e5 2. Nf3 Nc6 ").count(), 2);
+}
+
+#[test]
+fn openings_from_file() {
+    let dir = std::env::temp_dir();
+    let id = std::process::id();
+    let epd = dir.join(format!("yukari-openings-{id}.epd"));
+    let pgn = dir.join(format!("yukari-openings-{id}.pgn"));
+    let openings = "\
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1
+r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5;
+1. d4 d5 2. c4 e6
+1. c4 e5 2. Nc3 Nf6
+g1f3 d7d5 g2g3
+";
+    std::fs::write(&epd, openings).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_yukari"))
+        .args([
+            "selfplay",
+            "--games",
+            "10",
+            "--tc",
+            "0.5+0.01",
+            "--openings",
+        ])
+        .arg(&epd)
+        .args(["--opening-plies", "2", "--pgn"])
+        .arg(&pgn)
+        .output()
+        .expect("failed to run match");
+    assert!(output.status.success());
+
+    let games = std::fs::read_to_string(&pgn).unwrap();
+    std::fs::remove_file(&epd).unwrap();
+    std::fs::remove_file(&pgn).unwrap();
+
+    // Each game is its tags, a blank line, then its movetext.
+    let sections: Vec<_> = games.trim_end().split("\n\n").collect();
+    assert_eq!(sections.len(), 20, "{games}");
+    let tag = |tags: &str, name: &str| {
+        let prefix = format!("[{name} \"");
+        tags.lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .map(|value| value.trim_end_matches("\"]").to_string())
+    };
+    let games: Vec<_> = sections
+        .chunks(2)
+        .map(|game| {
+            let (tags, movetext) = (game[0], game[1]);
+            // The opening is the position, or the first two plies played from the start.
+            let opening = tag(tags, "FEN").unwrap_or_else(|| {
+                let moves = movetext.split_whitespace().take(3);
+                moves.collect::<Vec<_>>().join(" ")
+            });
+            (
+                opening,
+                tag(tags, "White").unwrap(),
+                tag(tags, "Black").unwrap(),
+            )
+        })
+        .collect();
+
+    let mut distinct = Vec::new();
+    for pair in games.chunks(2) {
+        let [(opening, white, black), (again, white_again, black_again)] = pair else {
+            panic!("games come in pairs");
+        };
+        assert_eq!(opening, again);
+        assert_ne!(white, black);
+        assert_eq!((white, black), (black_again, white_again));
+        assert!(!distinct.contains(opening), "{opening} played twice over");
+        distinct.push(opening.clone());
+    }
+    assert_eq!(distinct.len(), 5);
+    assert!(distinct.contains(&"1. d4 d5".to_string()));
+    assert!(distinct.contains(&"1. Nf3 d5".to_string()));
+}
+
+#[test]
+fn bad_arguments_are_usage_errors() {
+    for args in [
+        &["selfplay", "--opening-order", "shuffled"][..],
+        &["selfplay", "--opening-plies", "many"],
+        &["analyze", "--depth"],
+        &["--move-overhead", "slow"],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_yukari"))
+            .args(args)
+            .output()
+            .expect("failed to run engine");
+        assert_eq!(output.status.code(), Some(1), "{args:?}");
+        let err = String::from_utf8(output.stderr).unwrap();
+        assert!(err.contains("expects"), "{args:?}: {err}");
+        assert!(!err.contains("panicked"), "{args:?}: {err}");
+    }
+}
@@ -0,0 +1,439 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Run the engine on a scripted xboard session, returning everything it printed.
+fn session(script: &str) -> String {
+    session_with_args(&[], script)
+}
+
+/// Like `session`, with command-line arguments for the engine.
+fn session_with_args(args: &[&str], script: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yukari"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start engine");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("engine did not finish");
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Run the engine on a script sent a line at a time, each after the delay it is paired with,
+/// returning every line it printed along with when it was printed.
+fn timed_session(script: &[(Duration, &'static str)]) -> Vec<(Duration, String)> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yukari"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start engine");
+    let mut stdin = child.stdin.take().unwrap();
+    let script = script.to_vec();
+    let writer = thread::spawn(move || {
+        for (delay, line) in script {
+            thread::sleep(delay);
+            writeln!(stdin, "{line}").unwrap();
+        }
+    });
+    let start = Instant::now();
+    let output = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|line| (start.elapsed(), line.unwrap()))
+        .collect();
+    writer.join().unwrap();
+    assert!(child.wait().unwrap().success());
+    output
+}
+
+/// Check that a string looks like a move in coordinate notation.
+fn is_coordinate_move(s: &str) -> bool {
+    let s = s.as_bytes();
+    (s.len() == 4 || s.len() == 5)
+        && (b'a'..=b'h').contains(&s[0])
+        && (b'1'..=b'8').contains(&s[1])
+        && (b'a'..=b'h').contains(&s[2])
+        && (b'1'..=b'8').contains(&s[3])
+}
+
+#[test]
+fn hint_and_book_before_search() {
+    let output = session("xboard\nprotover 2\nnew\nhint\nbk\nquit\n");
+    assert!(!output.contains("Hint:"));
+    assert!(!output.contains("Error"));
+    assert!(output.contains(" no book\n\n"));
+}
+
+#[test]
+fn hint_and_book_mid_game() {
+    let output = session("xboard\nprotover 2\nnew\nst 1\ne2e4\nhint\nbk\nquit\n");
+    let reply = output
+        .lines()
+        .find_map(|line| line.strip_prefix("move "))
+        .expect("engine did not reply to e2e4");
+    assert!(is_coordinate_move(reply));
+    let hint = output
+        .lines()
+        .find_map(|line| line.strip_prefix("Hint: "))
+        .expect("engine gave no hint");
+    assert!(is_coordinate_move(hint));
+    assert!(output.contains(" no book\n\n"));
+}
+
+/// Find the moves the engine played, in order.
+fn engine_moves(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("move "))
+        .collect()
+}
+
+#[test]
+fn new_turns_random_off() {
+    // Every move from the start is within the margin, so random mode picks any of them,
+    // while without it the shallow search plays the same move every game.
+    let game = "new\nrandom\nst 1\ngo\n";
+    let script = format!(
+        "xboard\nprotover 2\noption Random Margin=1000\noption DepthLimit=3\n{}quit\n",
+        game.repeat(8)
+    );
+    let output = session_with_args(&["seed", "1"], &script);
+    let moves = engine_moves(&output);
+    assert_eq!(moves.len(), 8);
+    // Had `new` left random mode on, every other `random` would turn it off again.
+    let every_other = moves.iter().skip(1).step_by(2).collect::<Vec<_>>();
+    assert!(
+        every_other.iter().any(|&m| m != every_other[0]),
+        "{moves:?}"
+    );
+}
+
+#[test]
+fn force_then_go() {
+    // The engine plays black, the side on move, straight away.
+    let output = session("xboard\nprotover 2\nnew\nst 1\nforce\ne2e4\ngo\nquit\n");
+    let moves = engine_moves(&output);
+    assert_eq!(moves.len(), 1);
+    assert!(matches!(moves[0].as_bytes()[1], b'7' | b'8'));
+}
+
+#[test]
+fn force_then_playother() {
+    // The engine plays white, the side not on move, so it waits for black to move.
+    let output = session("xboard\nprotover 2\nnew\nst 1\nforce\ne2e4\nplayother\nquit\n");
+    assert!(engine_moves(&output).is_empty());
+    assert!(!output.contains("Error"));
+
+    let output = session("xboard\nprotover 2\nnew\nst 1\nforce\ne2e4\nplayother\ne7e5\nquit\n");
+    let moves = engine_moves(&output);
+    assert_eq!(moves.len(), 1);
+    assert!(matches!(moves[0].as_bytes()[1], b'1' | b'2' | b'4'));
+}
+
+#[test]
+fn only_move_is_instant() {
+    // Black is in double check and Kg8 is the only way out.
+    let start = Instant::now();
+    let output = session(
+        "xboard\nprotover 2\nnew\nforce\nsetboard 7k/8/8/8/8/8/K7/B6R b - - 0 1\nst 30\ngo\nquit\n",
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(engine_moves(&output), ["h8g8"]);
+}
+
+#[test]
+fn go_after_game_over() {
+    // Each position has already ended the game; there is nothing to search.
+    for (fen, result) in [
+        ("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", "1-0 {White mates}"),
+        ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", "1/2-1/2 {Stalemate}"),
+        (
+            "8/8/4k3/8/8/3K4/5N2/8 w - - 0 1",
+            "1/2-1/2 {Insufficient material}",
+        ),
+    ] {
+        let output = session(&format!(
+            "xboard\nprotover 2\nnew\nforce\nsetboard {fen}\nst 1\ngo\nquit\n"
+        ));
+        assert!(engine_moves(&output).is_empty(), "{fen}");
+        assert_eq!(output.matches(result).count(), 1, "{fen}");
+    }
+
+    // Nor is there a reply to the opponent's mating move.
+    let output = session(
+        "xboard\nprotover 2\nnew\nforce\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\
+         st 1\nplayother\na1a8\ngo\nquit\n",
+    );
+    assert!(engine_moves(&output).is_empty());
+    assert_eq!(output.matches("1-0 {White mates}").count(), 2);
+}
+
+#[test]
+fn malformed_time_control() {
+    // The engine reports the bad commands and keeps playing on the last good control.
+    let output = session("xboard\nprotover 2\nnew\nst 1\nlevel 40 foo 0\nst abc\ngo\nquit\n");
+    assert_eq!(output.matches("# bad time control").count(), 2);
+    assert_eq!(engine_moves(&output).len(), 1);
+}
+
+#[test]
+fn malformed_clock_reported() {
+    let output = session("xboard\nprotover 2\nnew\ntime abc\notim\nst 1\ngo\nquit\n");
+    assert!(output.contains("Error (bad time): time abc"));
+    assert!(output.contains("Error (bad time): otim"));
+    assert_eq!(engine_moves(&output).len(), 1);
+}
+
+#[test]
+fn malformed_setboard_keeps_board() {
+    // Kxb2 is the only legal move, so the reply shows which board the engine kept.
+    let output = session(
+        "xboard\nprotover 2\nnew\nforce\nsetboard 7k/8/8/8/8/8/1q6/K7 w - - 0 1\n\
+         setboard garbage\nsetboard 4k3/8/8/8/8/8/8/7K w K - 0 1\nst 1\ngo\nquit\n",
+    );
+    assert_eq!(output.matches("tellusererror Illegal position").count(), 2);
+    assert!(output.contains("Error (FEN is missing a field): setboard garbage"));
+    assert_eq!(engine_moves(&output), ["a1b2"]);
+}
+
+#[test]
+fn illegal_move_rejected() {
+    // Castling with pieces in the way, then a real move to show the engine is still playing.
+    let output = session("xboard\nprotover 2\nnew\nforce\ne1g1\ne2e4\nst 1\ngo\nquit\n");
+    assert_eq!(
+        output.matches("Illegal move (illegal move): e1g1").count(),
+        1
+    );
+    assert_eq!(engine_moves(&output).len(), 1);
+}
+
+#[test]
+fn desynced_move_leaves_board_alone() {
+    // The GUI sends e2e4 twice, as if our board had missed a move. The second is rejected
+    // without touching the board, so black's reply is still legal and it is white's move.
+    let output =
+        session("xboard\nprotover 2\nnew\nforce\ne2e4\ne2e4\ne7e5\n\ne2\nst 1\ngo\nquit\n");
+    assert_eq!(
+        output.matches("Illegal move (illegal move): e2e4").count(),
+        1
+    );
+    assert!(!output.contains("e7e5"));
+    assert_eq!(output.matches("Error (unknown command): e2").count(), 1);
+    let moves = engine_moves(&output);
+    assert_eq!(moves.len(), 1);
+    assert!(matches!(moves[0].as_bytes()[1], b'1' | b'2'));
+}
+
+#[test]
+fn learning_recalls_earlier_games() {
+    let path = std::env::temp_dir().join(format!("yukari-learn-{}.bin", std::process::id()));
+    let game = "new\nforce\ne2e4\ngo\nresult 1-0 {Adjudicated}\n";
+    let script = format!(
+        "xboard\nprotover 2\noption LearnFile={}\noption Learn=1\noption DepthLimit=5\nst 1\n\
+         {game}{game}quit\n",
+        path.display()
+    );
+    let output = session(&script);
+    let _ = std::fs::remove_file(&path);
+
+    // The first game has nothing to recall; the second recalls its move.
+    let moves = engine_moves(&output);
+    assert_eq!(moves.len(), 2);
+    let (first, second) = output.split_once(&format!("move {}", moves[0])).unwrap();
+    assert!(!first.contains("# learned "));
+    assert!(
+        second.contains(&format!("# learned {} ", moves[0])),
+        "{output}"
+    );
+
+    // With learning off, nothing is recalled or written.
+    let script = format!(
+        "xboard\nprotover 2\noption LearnFile={}\noption DepthLimit=5\nst 1\n{game}{game}quit\n",
+        path.display()
+    );
+    let output = session(&script);
+    assert!(!output.contains("# learned "));
+    assert!(!path.exists());
+}
+
+#[test]
+fn reports_package_version() {
+    let output = session("xboard\nprotover 2\nquit\n");
+    let myname = format!("feature myname=\"Yukari {}\"\n", env!("CARGO_PKG_VERSION"));
+    assert!(output.contains(&myname));
+    // The build's git hash only shows up in debug output.
+    let debug = format!("# Yukari {} (", env!("CARGO_PKG_VERSION"));
+    assert!(output.starts_with(&debug));
+
+    let version = Command::new(env!("CARGO_BIN_EXE_yukari"))
+        .arg("--version")
+        .output()
+        .expect("failed to run engine");
+    assert!(version.status.success());
+    let version = String::from_utf8(version.stdout).unwrap();
+    assert_eq!(version, format!("Yukari {}\n", env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn repetition_claimed_on_third_occurrence() {
+    // Shuffling the knights twice brings the start position back for the third time on the eighth ply.
+    let mut script = String::from("xboard\nprotover 2\nnew\nforce\n");
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    for (ply, m) in shuffle.iter().chain(&shuffle).enumerate() {
+        script.push_str(&format!("{m}\nping {}\n", ply + 1));
+    }
+    script.push_str("quit\n");
+    let output = session(&script);
+    assert_eq!(output.matches("Draw by repetition").count(), 1);
+    assert!(output.contains("pong 7\n1/2-1/2 {Draw by repetition}\npong 8\n"));
+}
+
+#[test]
+fn negotiated_settings() {
+    let script = "xboard\nprotover 2\nmemory 32\ncores 4\negtpath syzygy /tb/syzygy\n\
+                  egtpath gaviota /tb/gaviota\nping 1\nmemory 0\ncores lots\nquit\n";
+    let output = session(script);
+    assert!(output.contains("feature memory=1\n"));
+    // Good values and other tablebase kinds are accepted quietly.
+    let (before, after) = output.split_once("pong 1\n").unwrap();
+    assert!(!before.contains("Error"));
+    assert!(after.contains("Error (bad option value): Hash\n"));
+    assert!(after.contains("Error (bad option value): Threads\n"));
+}
+
+#[test]
+fn move_now_stops_search() {
+    let start = Instant::now();
+    let output = session("xboard\nprotover 2\nnew\nst 30\ngo\n?\nquit\n");
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let moves = engine_moves(&output);
+    assert_eq!(moves.len(), 1);
+    assert!(is_coordinate_move(moves[0]));
+}
+
+#[test]
+fn move_now_while_idle_is_ignored() {
+    // A move-now request with nothing to stop must not cut the next search short.
+    let output = session("xboard\nprotover 2\nnew\nst 1\n?\ngo\nquit\n");
+    assert_eq!(engine_moves(&output).len(), 1);
+    let seldepth: i32 = output
+        .lines()
+        .find_map(|line| line.strip_prefix("# Seldepth: "))
+        .expect("engine reported no search")
+        .parse()
+        .unwrap();
+    assert!(seldepth > 1, "{output}");
+}
+
+#[test]
+fn checkmate_claimed() {
+    let output = session("xboard\nprotover 2\nnew\nforce\nf2f3\ne7e5\ng2g4\nd8h4\nquit\n");
+    assert!(output.contains("0-1 {Black mates}\n"));
+
+    // When the opponent mates us, there is no move to reply with.
+    let output =
+        session("xboard\nprotover 2\nnew\nforce\nf2f3\ne7e5\ng2g4\nplayother\nd8h4\nquit\n");
+    assert!(output.contains("0-1 {Black mates}\n"));
+    assert!(engine_moves(&output).is_empty());
+}
+
+#[test]
+fn pause_and_resume() {
+    let ms = Duration::from_millis;
+    let script = [
+        (ms(0), "xboard\nprotover 2\nnew\nst 3"),
+        (ms(0), "go"),
+        (ms(1000), "pause"),
+        (ms(100), "e7e5"),
+        (ms(2900), "resume"),
+        (ms(3000), "quit"),
+    ];
+    let output = timed_session(&script);
+    assert!(output.iter().any(|(_, line)| line == "feature pause=1"));
+    let resumed = ms(4000);
+    let moves: Vec<_> = output
+        .iter()
+        .filter(|(_, line)| line.starts_with("move "))
+        .collect();
+    assert_eq!(moves.len(), 1, "{output:?}");
+    assert!(output
+        .iter()
+        .any(|(_, line)| line == "Error (game paused): e7e5"));
+
+    // The move comes after the resume, once the two seconds the search had left are used.
+    let (played, _) = moves[0];
+    assert!(*played > resumed + ms(1500), "moved at {played:?}");
+    assert!(*played < resumed + ms(2900), "moved at {played:?}");
+}
+
+#[test]
+fn analyze_reports_progress() {
+    let ms = Duration::from_millis;
+    let script = [
+        (ms(0), "xboard\nprotover 2\nnew\nforce\nanalyze"),
+        (ms(1500), "."),
+        (ms(1000), "e2e4"),
+        (ms(500), "exit"),
+        (ms(100), "quit"),
+    ];
+    let output = timed_session(&script);
+    assert!(output.iter().any(|(_, line)| line == "feature analyze=1"));
+    assert!(!output.iter().any(|(_, line)| line.starts_with("move ")));
+    assert!(!output.iter().any(|(_, line)| line.starts_with("Error")));
+
+    let stats: Vec<_> = output
+        .iter()
+        .filter_map(|(_, line)| line.strip_prefix("stat01: "))
+        .collect();
+    assert!(!stats.is_empty(), "{output:?}");
+    for stat in stats {
+        let fields: Vec<_> = stat.split(' ').collect();
+        assert_eq!(fields.len(), 6, "{stat}");
+        assert!(is_coordinate_move(fields[5]), "{stat}");
+    }
+
+    // Analysis goes on after a move, from the new position where black is to move.
+    let (_, after) = output
+        .iter()
+        .rev()
+        .find(|(_, line)| line.starts_with("1 "))
+        .expect("no analysis after e2e4");
+    let (_, pv) = after.split_once('\t').expect("no tab before the PV");
+    let reply = pv.split_whitespace().next().unwrap();
+    assert!(matches!(reply.as_bytes()[1], b'7' | b'8'), "{after}");
+}
+
+#[test]
+fn protocol_version_one() {
+    let ms = Duration::from_millis;
+    let script = [
+        (ms(0), "xboard\nprotover 1\nnew\nst 1\ngo"),
+        (ms(2000), "quit"),
+    ];
+    let output = timed_session(&script);
+    assert!(!output.iter().any(|(_, line)| line.starts_with("feature")));
+    let moves: Vec<_> = output
+        .iter()
+        .filter(|(_, line)| line.starts_with("move "))
+        .collect();
+    assert_eq!(moves.len(), 1, "{output:?}");
+}
+
+#[test]
+fn rejected_features() {
+    let start = Instant::now();
+    let output = session(
+        "xboard\nprotover 2\nrejected setboard\nrejected ping\nnew\nst 30\ngo\nforce\nquit\n",
+    );
+    assert!(output.contains("tellusererror"));
+    // Without ping, a search the GUI has moved on from is abandoned, and its move never sent.
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(engine_moves(&output).is_empty(), "{output}");
+}